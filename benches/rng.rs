@@ -0,0 +1,63 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use num_bigint::BigUint;
+use std::hint::black_box;
+use std::str::FromStr;
+
+use true_random_on_ton::{rng, utils};
+use true_random_on_ton::generator::GeneratorKind;
+
+const SEED: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+const LIMIT: u64 = 1_000_000;
+
+// The original string round-trip implementation, kept as the baseline. `rng`'s tests check
+// that the byte implementation still matches it.
+fn legacy_random(initial_seed: &str, iterations: usize, limit: u64) -> Vec<rng::RngResult> {
+    let mut current_number = BigUint::from_str(&utils::convert_to_int_string(initial_seed.to_string(), utils::ConvertInputValueType::Be).unwrap())
+        .expect("Invalid initial seed");
+    let mut results = Vec::with_capacity(iterations);
+
+    for _ in 0..iterations {
+        let hash = utils::sha512(utils::convert_to_hex_string(current_number.to_string(), utils::ConvertInputValueType::Be).unwrap()).unwrap();
+        let (first_half_hex, second_half_hex) = hash.split_at(64);
+        let first_half_bytes = hex::decode(first_half_hex).expect("Invalid hex");
+        let second_half_bytes = hex::decode(second_half_hex).expect("Invalid hex");
+        current_number = BigUint::from_bytes_be(&first_half_bytes);
+        let random_int = BigUint::from_bytes_be(&second_half_bytes);
+        let ticket_number = (random_int * BigUint::from(limit)) >> 256usize;
+        let ticket_number = ticket_number.to_u64_digits().first().copied().unwrap_or(0);
+
+        results.push(rng::RngResult {
+            ticket_number,
+            new_seed     : Some(first_half_hex.to_string()),
+            random_result: second_half_hex.to_string(),
+        });
+    }
+
+    results
+}
+
+fn bench_random(c: &mut Criterion) {
+    let mut group = c.benchmark_group("random");
+    for iterations in [100usize, 3100, 100_000] {
+        group.throughput(Throughput::Elements(iterations as u64));
+        group.bench_with_input(BenchmarkId::new("legacy", iterations), &iterations, |b, &iterations| {
+            b.iter(|| legacy_random(black_box(SEED), iterations, LIMIT))
+        });
+        group.bench_with_input(BenchmarkId::new("bytes", iterations), &iterations, |b, &iterations| {
+            b.iter(|| rng::random(black_box(SEED), iterations, LIMIT, rng::ChainVersion::V1, GeneratorKind::Sha512Chain).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("seed_chain", iterations), &iterations, |b, &iterations| {
+            let seed = hex::decode(SEED).unwrap();
+            b.iter(|| {
+                rng::SeedChain::new(black_box(&seed), LIMIT, rng::ChainVersion::V1)
+                    .unwrap()
+                    .take(iterations)
+                    .fold(0u64, |acc, step| acc ^ step.ticket_number)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_random);
+criterion_main!(benches);
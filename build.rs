@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use prost::Message;
+
+// Compiles `proto/` with protox, so building needs no `protoc`. The descriptor set is kept
+// for gRPC server reflection. The client is only built for the service's own tests.
+fn main() {
+    println!("cargo:rerun-if-changed=proto");
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").expect("cargo sets OUT_DIR"));
+    let fds = protox::compile(["true_random.proto"], ["proto"]).unwrap_or_else(|err| panic!("proto/true_random.proto is invalid: {}", err));
+    std::fs::write(out_dir.join("true_random_descriptor.bin"), fds.encode_to_vec()).expect("OUT_DIR is writable");
+    tonic_prost_build::configure()
+        .client_mod_attribute(".", "#[cfg(test)]")
+        .compile_fds(fds)
+        .expect("gRPC code is generated");
+}
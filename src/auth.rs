@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use strum::{Display, EnumString};
+
+// Authentication and per-route scopes. A request carries either an API key in `X-Api-Key`
+// or a JWT in `Authorization: Bearer <token>`:
+//
+// - API keys are configured by the hex SHA-256 of the key, so the config holds no secrets.
+// - JWTs are HS256/384/512-signed with `AUTH_JWT_SECRET` or ES256/384-signed with the key in
+//   `auth.jwt.es_public_key`. `exp` is required and scopes come from the space-separated
+//   `scope` claim.
+//
+// Scopes listed in `auth.public` need no credentials. Without any API key or JWT settings
+// authentication is off and every route is public, as before.
+
+pub const DEFAULT_LEEWAY_SECS: u64 = 60;
+
+#[derive(Debug)]
+pub struct CredentialsInvalid;
+
+#[derive(Debug)]
+pub struct ScopeMissing;
+
+#[derive(Debug)]
+pub enum AuthInputError {
+    CredentialsInvalid(CredentialsInvalid),
+    ScopeMissing(ScopeMissing),
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    JwtSecretMissing,
+    JwtKeyInvalid(std::io::Error),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::JwtSecretMissing => write!(f, "AUTH_JWT_SECRET must be set for HS* JWT algorithms"),
+            AuthError::JwtKeyInvalid(err) => write!(f, "auth.jwt.es_public_key must be a PEM EC public key: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, Deserialize, Serialize)]
+pub enum Scope {
+    #[strum(serialize = "vrf:prove")]
+    #[serde(rename = "vrf:prove")]
+    VrfProve,       // /api/vrf/prove, /api/vrf/keys/{id}/prove, /api/merkle/prove
+    #[strum(serialize = "vrf:verify")]
+    #[serde(rename = "vrf:verify")]
+    VrfVerify,      // /api/vrf/verify, /api/vrf/pk
+    #[strum(serialize = "keys:admin")]
+    #[serde(rename = "keys:admin")]
+    KeysAdmin,      // /api/vrf/sk/..., /api/vrf/pk/derive, /api/vrf/keys
+    #[strum(serialize = "rng:draw")]
+    #[serde(rename = "rng:draw")]
+    RngDraw,        // /api/random/...
+    #[strum(serialize = "merkle:commit")]
+    #[serde(rename = "merkle:commit")]
+    MerkleCommit,   // /api/merkle/commit, /api/merkle/proofs
+    #[strum(serialize = "merkle:verify")]
+    #[serde(rename = "merkle:verify")]
+    MerkleVerify,   // /api/merkle/verify
+    #[strum(serialize = "drand:verify")]
+    #[serde(rename = "drand:verify")]
+    DrandVerify,    // /api/drand/...
+    #[strum(serialize = "beacon:read")]
+    #[serde(rename = "beacon:read")]
+    BeaconRead,     // /beacon/...
+    #[strum(serialize = "utils")]
+    #[serde(rename = "utils")]
+    Utils,          // /api/utils/...
+    #[strum(serialize = "health:read")]
+    #[serde(rename = "health:read")]
+    HealthRead,     // /api/health/...
+    #[strum(serialize = "admin:selftest")]
+    #[serde(rename = "admin:selftest")]
+    AdminSelfTest,  // /api/admin/selftest
+    #[strum(serialize = "admin:read")]
+    #[serde(rename = "admin:read")]
+    AdminRead,      // /api/admin/ratelimit
+    #[strum(serialize = "metrics:read")]
+    #[serde(rename = "metrics:read")]
+    MetricsRead,    // /metrics
+    #[strum(serialize = "docs:read")]
+    #[serde(rename = "docs:read")]
+    DocsRead,       // /openapi.json, /docs/...
+}
+
+// Read-only routes that stay public by default once authentication is enabled.
+pub const DEFAULT_PUBLIC_SCOPES: [Scope; 6] = [Scope::VrfVerify, Scope::MerkleVerify, Scope::BeaconRead, Scope::Utils, Scope::HealthRead, Scope::DocsRead];
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize)]
+pub enum JwtAlgorithm {
+    HS256,
+    HS384,
+    HS512,
+    ES256,
+    ES384,
+}
+
+impl JwtAlgorithm {
+    fn algorithm(self) -> Algorithm {
+        match self {
+            JwtAlgorithm::HS256 => Algorithm::HS256,
+            JwtAlgorithm::HS384 => Algorithm::HS384,
+            JwtAlgorithm::HS512 => Algorithm::HS512,
+            JwtAlgorithm::ES256 => Algorithm::ES256,
+            JwtAlgorithm::ES384 => Algorithm::ES384,
+        }
+    }
+
+    pub fn is_hmac(self) -> bool {
+        matches!(self, JwtAlgorithm::HS256 | JwtAlgorithm::HS384 | JwtAlgorithm::HS512)
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    pub id    : String,
+    pub sha256: String, // Hex SHA-256 of the key, see `auth key`.
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtConfig {
+    pub algorithms   : Vec<JwtAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer       : Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience     : Option<String>,
+    #[serde(default = "default_leeway_secs")]
+    pub leeway_secs  : u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub es_public_key: Option<std::path::PathBuf>, // PEM, for ES256/ES384.
+}
+
+fn default_leeway_secs() -> u64 {
+    DEFAULT_LEEWAY_SECS
+}
+
+// The caller behind a request: `key:<id>` for API keys, `jwt:<sub>` for tokens.
+#[derive(Clone, Debug)]
+pub struct Principal {
+    pub id    : String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    #[serde(default)]
+    sub  : Option<String>,
+    #[serde(default)]
+    scope: String,
+}
+
+struct JwtVerifier {
+    algorithms: Vec<Algorithm>,
+    secret    : Option<DecodingKey>,
+    public_key: Option<DecodingKey>,
+    config    : JwtConfig,
+}
+
+pub struct Authenticator {
+    enabled : bool,
+    public  : Vec<Scope>,
+    api_keys: HashMap<[u8; 32], Principal>,
+    jwt     : Option<JwtVerifier>,
+}
+
+pub fn key_hash(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+impl JwtVerifier {
+    fn new(config: JwtConfig, secret: Option<&[u8]>) -> Result<JwtVerifier, AuthError> {
+        let secret = match config.algorithms.iter().any(|algorithm| algorithm.is_hmac()) {
+            true => Some(DecodingKey::from_secret(secret.ok_or(AuthError::JwtSecretMissing)?)),
+            false => None,
+        };
+        let public_key = match &config.es_public_key {
+            Some(path) => {
+                let pem = std::fs::read(path).map_err(AuthError::JwtKeyInvalid)?;
+                Some(DecodingKey::from_ec_pem(&pem).map_err(|err| AuthError::JwtKeyInvalid(std::io::Error::other(err)))?)
+            },
+            None => None,
+        };
+        Ok(JwtVerifier{ algorithms: config.algorithms.iter().map(|algorithm| algorithm.algorithm()).collect(), secret, public_key, config })
+    }
+
+    fn verify(&self, token: &str) -> Option<Principal> {
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        if !self.algorithms.contains(&header.alg) {
+            return None;
+        }
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => self.secret.as_ref()?,
+            _ => self.public_key.as_ref()?,
+        };
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.config.leeway_secs;
+        validation.validate_nbf = true;
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = jsonwebtoken::decode::<Claims>(token, key, &validation).ok()?.claims;
+        Some(Principal {
+            id    : format!("jwt:{}", claims.sub.unwrap_or_default()),
+            // Unknown scopes are ignored, so tokens can be shared with other services.
+            scopes: claims.scope.split_whitespace().filter_map(|scope| scope.parse().ok()).collect(),
+        })
+    }
+}
+
+impl Authenticator {
+    pub fn new(public: &[Scope], api_keys: &[ApiKeyConfig], jwt: Option<&JwtConfig>, jwt_secret: Option<&[u8]>) -> Result<Authenticator, AuthError> {
+        let api_keys = api_keys.iter()
+            .filter_map(|api_key| {
+                let hash = hex::decode(&api_key.sha256).ok()?.try_into().ok()?;
+                Some((hash, Principal{ id: format!("key:{}", api_key.id), scopes: api_key.scopes.clone() }))
+            })
+            .collect::<HashMap<_, _>>();
+        let jwt = jwt.cloned().map(|jwt| JwtVerifier::new(jwt, jwt_secret)).transpose()?;
+        Ok(Authenticator{ enabled: !api_keys.is_empty() || jwt.is_some(), public: public.to_vec(), api_keys, jwt })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Credentials that are present must be valid, even on public routes.
+    pub fn authenticate(&self, api_key: Option<&str>, authorization: Option<&str>) -> Result<Option<Principal>, CredentialsInvalid> {
+        if let Some(api_key) = api_key {
+            return self.api_keys.get(&key_hash(api_key)).cloned().map(Some).ok_or(CredentialsInvalid);
+        }
+        if let Some(authorization) = authorization {
+            let token = authorization.split_once(' ')
+                .filter(|(scheme, _token)| scheme.eq_ignore_ascii_case("bearer"))
+                .map(|(_scheme, token)| token.trim())
+                .ok_or(CredentialsInvalid)?;
+            return self.jwt.as_ref().and_then(|jwt| jwt.verify(token)).map(Some).ok_or(CredentialsInvalid);
+        }
+        Ok(None)
+    }
+
+    pub fn authorize(&self, api_key: Option<&str>, authorization: Option<&str>, scope: Scope) -> Result<Option<Principal>, AuthInputError> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let principal = self.authenticate(api_key, authorization).map_err(AuthInputError::CredentialsInvalid)?;
+        if self.public.contains(&scope) {
+            return Ok(principal);
+        }
+        match principal {
+            Some(principal) if principal.scopes.contains(&scope) => Ok(Some(principal)),
+            Some(_principal) => Err(AuthInputError::ScopeMissing(ScopeMissing)),
+            None => Err(AuthInputError::CredentialsInvalid(CredentialsInvalid)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use jsonwebtoken::{EncodingKey, Header};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn now() -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn authenticator() -> Authenticator {
+        let api_keys = [ApiKeyConfig{ id: "ops".to_string(), sha256: hex::encode(key_hash("ops-key")), scopes: vec![Scope::KeysAdmin] }];
+        let jwt = JwtConfig {
+            algorithms   : vec![JwtAlgorithm::HS256],
+            issuer       : Some("https://issuer.example".to_string()),
+            audience     : Some("vrf".to_string()),
+            leeway_secs  : DEFAULT_LEEWAY_SECS,
+            es_public_key: None,
+        };
+        Authenticator::new(&DEFAULT_PUBLIC_SCOPES, &api_keys, Some(&jwt), Some(SECRET)).unwrap()
+    }
+
+    fn token(algorithm: Algorithm, claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(&Header::new(algorithm), &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "sub"  : "alice",
+            "iss"  : "https://issuer.example",
+            "aud"  : "vrf",
+            "exp"  : now() + 600,
+            "scope": "vrf:prove unknown:scope",
+        })
+    }
+
+    fn bearer(token: &str) -> String {
+        format!("Bearer {}", token)
+    }
+
+    fn is_credentials_invalid(result: Result<Option<Principal>, AuthInputError>) -> bool {
+        matches!(result, Err(AuthInputError::CredentialsInvalid(CredentialsInvalid)))
+    }
+
+    #[test]
+    fn api_keys_are_looked_up_by_hash() {
+        let authenticator = authenticator();
+        let principal = authenticator.authorize(Some("ops-key"), None, Scope::KeysAdmin).unwrap().unwrap();
+        assert_eq!(principal.id, "key:ops");
+        assert_eq!(principal.scopes, vec![Scope::KeysAdmin]);
+        // The configured hash is not itself a key.
+        let hash = hex::encode(key_hash("ops-key"));
+        assert!(is_credentials_invalid(authenticator.authorize(Some(&hash), None, Scope::KeysAdmin)));
+        assert!(is_credentials_invalid(authenticator.authorize(Some("other-key"), None, Scope::KeysAdmin)));
+    }
+
+    #[test]
+    fn jwt_grants_its_scopes() {
+        let authenticator = authenticator();
+        let authorization = bearer(&token(Algorithm::HS256, claims()));
+        let principal = authenticator.authorize(None, Some(&authorization), Scope::VrfProve).unwrap().unwrap();
+        assert_eq!(principal.id, "jwt:alice");
+        assert_eq!(principal.scopes, vec![Scope::VrfProve]);
+    }
+
+    #[test]
+    fn jwt_with_unlisted_algorithm_is_rejected() {
+        let authenticator = authenticator();
+        for algorithm in [Algorithm::HS384, Algorithm::HS512] {
+            let authorization = bearer(&token(algorithm, claims()));
+            assert!(is_credentials_invalid(authenticator.authorize(None, Some(&authorization), Scope::VrfProve)));
+        }
+    }
+
+    #[test]
+    fn expired_jwt_is_rejected() {
+        let authenticator = authenticator();
+        let mut claims = claims();
+        claims["exp"] = (now() - DEFAULT_LEEWAY_SECS - 60).into();
+        let authorization = bearer(&token(Algorithm::HS256, claims));
+        assert!(is_credentials_invalid(authenticator.authorize(None, Some(&authorization), Scope::VrfProve)));
+    }
+
+    #[test]
+    fn jwt_with_wrong_issuer_or_audience_is_rejected() {
+        let authenticator = authenticator();
+        for (claim, value) in [("iss", "https://other.example"), ("aud", "other")] {
+            let mut claims = claims();
+            claims[claim] = value.into();
+            let authorization = bearer(&token(Algorithm::HS256, claims));
+            assert!(is_credentials_invalid(authenticator.authorize(None, Some(&authorization), Scope::VrfProve)));
+        }
+    }
+
+    #[test]
+    fn missing_scope_is_forbidden() {
+        let authenticator = authenticator();
+        let authorization = bearer(&token(Algorithm::HS256, claims()));
+        assert!(matches!(authenticator.authorize(None, Some(&authorization), Scope::KeysAdmin), Err(AuthInputError::ScopeMissing(ScopeMissing))));
+        assert!(matches!(authenticator.authorize(Some("ops-key"), None, Scope::VrfProve), Err(AuthInputError::ScopeMissing(ScopeMissing))));
+    }
+
+    #[test]
+    fn missing_credentials_are_only_accepted_on_public_routes() {
+        let authenticator = authenticator();
+        assert!(is_credentials_invalid(authenticator.authorize(None, None, Scope::VrfProve)));
+        assert!(authenticator.authorize(None, None, Scope::VrfVerify).unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_credentials_are_rejected_on_public_routes() {
+        let authenticator = authenticator();
+        assert!(is_credentials_invalid(authenticator.authorize(Some("other-key"), None, Scope::VrfVerify)));
+        assert!(is_credentials_invalid(authenticator.authorize(None, Some("Bearer not-a-token"), Scope::VrfVerify)));
+        assert!(is_credentials_invalid(authenticator.authorize(None, Some("Basic b3BzOm9wcw=="), Scope::VrfVerify)));
+    }
+
+    #[test]
+    fn everything_is_public_without_settings() {
+        let authenticator = Authenticator::new(&[], &[], None, None).unwrap();
+        assert!(!authenticator.is_enabled());
+        assert!(authenticator.authorize(Some("other-key"), None, Scope::KeysAdmin).unwrap().is_none());
+    }
+}
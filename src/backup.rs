@@ -0,0 +1,270 @@
+use serde::Serialize;
+use serde::Deserialize;
+use bip39::Mnemonic;
+use blahaj::{Sharks, Share};
+use rand_core::OsRng;
+use vrf_r255::{PublicKey, SecretKey};
+
+use crate::ecvrf;
+
+// Paper backups for VRF secret keys.
+//
+// Mnemonic: the 32-byte secret key is used as BIP-39 entropy, giving 24 English words
+// whose last word carries an 8-bit SHA-256 checksum.
+//
+// Shares: Shamir k-of-n over GF(256), with 2 <= k <= n. Polynomial coefficients are drawn
+// uniformly from all of GF(256), 0 included, by `blahaj`; `sharks` excluded 0, which biases the
+// shares (RUSTSEC-2024-0398). Each share is hex encoded as
+// `threshold || x || y[32]`, so recovery only needs the shares themselves. x is never 0 and
+// never repeated: a repeated share cancels out of the interpolation and yields a wrong key.
+
+#[derive(Debug)]
+pub struct MnemonicInvalid;
+
+#[derive(Debug)]
+pub struct SharesInvalid;
+
+#[derive(Debug)]
+pub struct ThresholdInvalid;
+
+#[derive(Debug)]
+pub struct PKMismatch;
+
+#[derive(Debug)]
+pub enum BackupInputError {
+    SKInvalid(ecvrf::SKInvalid),
+    PKInvalid(ecvrf::PKInvalid),
+    MnemonicInvalid(MnemonicInvalid),
+    SharesInvalid(SharesInvalid),
+    ThresholdInvalid(ThresholdInvalid),
+    PKMismatch(PKMismatch),
+}
+
+#[derive(Deserialize)]
+pub struct MnemonicInputs {
+    sk: String
+}
+
+#[derive(Serialize)]
+pub struct MnemonicOutput {
+    pub mnemonic: String,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreInputs {
+    mnemonic: String,
+    pk      : Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SplitInputs {
+    sk       : String,
+    threshold: u8,
+    shares   : u8,
+}
+
+#[derive(Serialize)]
+pub struct SplitOutput {
+    pub shares: Vec<String>,
+    pub pk    : String,
+}
+
+#[derive(Deserialize)]
+pub struct RecoverInputs {
+    shares: Vec<String>,
+    pk    : Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RecoveredOutput {
+    pub sk: String,
+    pub pk: String,
+}
+
+pub fn sk_to_mnemonic(sk: &SecretKey) -> String {
+    // 32 bytes is always a valid BIP-39 entropy length.
+    Mnemonic::from_entropy(&sk.to_bytes()).expect("32-byte entropy").to_string()
+}
+
+pub fn sk_from_mnemonic(mnemonic: &str) -> Result<SecretKey, MnemonicInvalid> {
+    let mnemonic = Mnemonic::parse(mnemonic).map_err(|_err| MnemonicInvalid)?;
+    let (entropy, len) = mnemonic.to_entropy_array();
+    if len != 32 {
+        return Err(MnemonicInvalid);
+    }
+
+    let mut sk_bytes = [0u8; 32];
+    sk_bytes.copy_from_slice(&entropy[..32]);
+    let sk = SecretKey::from_bytes(sk_bytes);
+    if sk.is_some().into() {
+        Ok(sk.unwrap())
+    } else {
+        Err(MnemonicInvalid)
+    }
+}
+
+pub fn split_sk(sk: &SecretKey, threshold: u8, shares: u8) -> Result<Vec<String>, ThresholdInvalid> {
+    // With a threshold of 1 every share is the plain secret key.
+    if threshold < 2 || threshold > shares {
+        return Err(ThresholdInvalid);
+    }
+
+    let dealer = Sharks(threshold).dealer_rng(&sk.to_bytes(), &mut OsRng);
+    let shares: Vec<String> = dealer
+        .take(shares as usize)
+        .map(|share| {
+            let mut bytes = vec![threshold];
+            bytes.extend(Vec::from(&share));
+            hex::encode(bytes)
+        })
+        .collect();
+
+    Ok(shares)
+}
+
+pub fn recover_sk(share_strings: &[String]) -> Result<SecretKey, SharesInvalid> {
+    let mut threshold: Option<u8> = None;
+    let mut shares: Vec<Share> = Vec::with_capacity(share_strings.len());
+    let mut xs: Vec<u8> = Vec::with_capacity(share_strings.len());
+
+    for share_string in share_strings {
+        let bytes = hex::decode(share_string).map_err(|_err| SharesInvalid)?;
+        if bytes.len() != 2 + 32 {
+            return Err(SharesInvalid);
+        }
+        if threshold.is_some_and(|threshold| threshold != bytes[0]) {
+            return Err(SharesInvalid);
+        }
+        if bytes[1] == 0 || xs.contains(&bytes[1]) {
+            return Err(SharesInvalid);
+        }
+        threshold = Some(bytes[0]);
+        xs.push(bytes[1]);
+        shares.push(Share::try_from(&bytes[1..]).map_err(|_err| SharesInvalid)?);
+    }
+
+    let threshold = threshold.ok_or(SharesInvalid)?;
+    let secret = Sharks(threshold).recover(&shares).map_err(|_err| SharesInvalid)?;
+    let sk_bytes: [u8; 32] = secret.try_into().map_err(|_err| SharesInvalid)?;
+    let sk = SecretKey::from_bytes(sk_bytes);
+    if sk.is_some().into() {
+        Ok(sk.unwrap())
+    } else {
+        Err(SharesInvalid)
+    }
+}
+
+fn check_pk(sk: &SecretKey, pk_string: Option<String>) -> Result<(), BackupInputError> {
+    if let Some(pk_string) = pk_string {
+        let expected = hex::decode(pk_string).map_err(|_err| BackupInputError::PKInvalid(ecvrf::PKInvalid))?;
+        if PublicKey::from(*sk).to_bytes().as_slice() != expected.as_slice() {
+            return Err(BackupInputError::PKMismatch(PKMismatch));
+        }
+    }
+    Ok(())
+}
+
+fn recovered_output(sk: SecretKey) -> RecoveredOutput {
+    RecoveredOutput{ sk: hex::encode(sk.to_bytes()), pk: ecvrf::get_pk(sk) }
+}
+
+// Api endpoints.
+pub fn api_sk_to_mnemonic(mnemonic_inputs: MnemonicInputs) -> Result<MnemonicOutput, BackupInputError> {
+    match ecvrf::decode_sk(mnemonic_inputs.sk) {
+        Ok(sk) => Ok(MnemonicOutput{ mnemonic: sk_to_mnemonic(&sk) }),
+        Err(err) => Err(BackupInputError::SKInvalid(err))
+    }
+}
+
+pub fn api_sk_from_mnemonic(restore_inputs: RestoreInputs) -> Result<RecoveredOutput, BackupInputError> {
+    let sk = sk_from_mnemonic(&restore_inputs.mnemonic).map_err(BackupInputError::MnemonicInvalid)?;
+    check_pk(&sk, restore_inputs.pk)?;
+    Ok(recovered_output(sk))
+}
+
+pub fn api_split_sk(split_inputs: SplitInputs) -> Result<SplitOutput, BackupInputError> {
+    let sk = ecvrf::decode_sk(split_inputs.sk).map_err(BackupInputError::SKInvalid)?;
+    let shares = split_sk(&sk, split_inputs.threshold, split_inputs.shares).map_err(BackupInputError::ThresholdInvalid)?;
+
+    // Never hand out shares that do not reproduce the key.
+    let pk = ecvrf::get_pk(sk);
+    match recover_sk(&shares[..split_inputs.threshold as usize]) {
+        Ok(recovered) if ecvrf::get_pk(recovered) == pk => Ok(SplitOutput{ shares, pk }),
+        _ => Err(BackupInputError::SharesInvalid(SharesInvalid))
+    }
+}
+
+pub fn api_recover_sk(recover_inputs: RecoverInputs) -> Result<RecoveredOutput, BackupInputError> {
+    let sk = recover_sk(&recover_inputs.shares).map_err(BackupInputError::SharesInvalid)?;
+    check_pk(&sk, recover_inputs.pk)?;
+    Ok(recovered_output(sk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sk() -> SecretKey {
+        SecretKey::from_bytes([7u8; 32]).unwrap()
+    }
+
+    fn pk(sk: SecretKey) -> [u8; 32] {
+        PublicKey::from(sk).to_bytes()
+    }
+
+    #[test]
+    fn mnemonic_round_trip() {
+        let mnemonic = sk_to_mnemonic(&sk());
+        assert_eq!(mnemonic.split_whitespace().count(), 24);
+        assert_eq!(pk(sk_from_mnemonic(&mnemonic).unwrap()), pk(sk()));
+    }
+
+    #[test]
+    fn every_threshold_subset_recovers_the_key() {
+        for (threshold, count) in [(2, 2), (2, 3), (3, 5), (4, 7)] {
+            let shares = split_sk(&sk(), threshold, count).unwrap();
+            for mask in 0u32..(1 << count) {
+                if mask.count_ones() != threshold as u32 {
+                    continue;
+                }
+                let subset: Vec<String> = (0..count as usize).filter(|i| mask & (1 << i) != 0).map(|i| shares[i].clone()).collect();
+                let recovered = recover_sk(&subset).unwrap();
+                assert_eq!(recovered.to_bytes(), sk().to_bytes(), "{}-of-{} subset {:b}", threshold, count, mask);
+                assert_eq!(pk(recovered), pk(sk()));
+            }
+        }
+    }
+
+    #[test]
+    fn fewer_than_threshold_shares_do_not_recover_the_key() {
+        let shares = split_sk(&sk(), 3, 5).unwrap();
+        assert!(recover_sk(&shares[..2]).is_err());
+    }
+
+    #[test]
+    fn api_split_round_trips_through_recover() {
+        let sk_hex = hex::encode(sk().to_bytes());
+        let split = api_split_sk(SplitInputs{ sk: sk_hex.clone(), threshold: 2, shares: 3 }).unwrap();
+        let recovered = api_recover_sk(RecoverInputs{ shares: split.shares[1..].to_vec(), pk: Some(split.pk.clone()) }).unwrap();
+        assert_eq!(recovered.sk, sk_hex);
+        assert_eq!(recovered.pk, split.pk);
+    }
+
+    #[test]
+    fn repeated_or_zero_shares_are_rejected() {
+        let shares = split_sk(&sk(), 2, 3).unwrap();
+        let repeated = vec![shares[0].clone(), shares[1].clone(), shares[0].clone()];
+        assert!(recover_sk(&repeated).is_err());
+
+        let mut zero = hex::decode(&shares[2]).unwrap();
+        zero[1] = 0;
+        assert!(recover_sk(&[shares[0].clone(), hex::encode(zero)]).is_err());
+    }
+
+    #[test]
+    fn threshold_below_two_is_rejected() {
+        assert!(split_sk(&sk(), 1, 3).is_err());
+        assert!(split_sk(&sk(), 0, 3).is_err());
+        assert!(split_sk(&sk(), 4, 3).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use vrf_r255::{PublicKey, SecretKey, Proof};
+
+// Public randomness beacon, specified in `docs/beacon.md`. Every interval the service proves
+//
+//   alpha_n = SHA-256(u64_be(n) || beta_{n-1})               (beta_0 = 64 zero bytes)
+//
+// and publishes a round record chained to the previous one by
+//
+//   hash_n = SHA-256(u64_be(n) || i64_be(timestamp_n) || prev_hash_n || alpha_n || pi_n || beta_n)
+//
+// with prev_hash_1 = 32 zero bytes. Rounds start at 1 and are appended to an NDJSON file when
+// one is configured, so the chain survives restarts. A round is published only once its line
+// is stored; a line torn by a crash mid-append is dropped on the next start. With a file only
+// the latest round is kept in memory, and older rounds are read back by their line offset.
+
+pub const GENESIS_BETA: [u8; 64] = [0u8; 64];
+pub const GENESIS_HASH: [u8; 32] = [0u8; 32];
+pub const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+#[derive(Debug)]
+pub struct BeaconDisabled;
+
+#[derive(Debug)]
+pub struct RoundNotFound;
+
+#[derive(Debug)]
+pub struct ChainInvalid {
+    pub round : u64,
+    pub reason: &'static str,
+}
+
+#[derive(Debug)]
+pub enum BeaconInputError {
+    BeaconDisabled(BeaconDisabled),
+    RoundNotFound(RoundNotFound),
+}
+
+#[derive(Debug)]
+pub enum BeaconError {
+    ChainInvalid(ChainInvalid),
+    Io(std::io::Error),
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct BeaconRound {
+    pub round    : u64,
+    pub timestamp: i64,    // Unix seconds.
+    pub prev_hash: String, // Hex, 32 bytes.
+    pub alpha    : String, // Hex, 32 bytes.
+    pub pi       : String, // Hex, Gamma || c || s.
+    pub beta     : String, // Hex, 64 bytes.
+    pub hash     : String, // Hex, 32 bytes.
+}
+
+#[derive(Serialize)]
+pub struct BeaconInfo {
+    pub pk           : String,
+    pub interval_secs: u64,
+    pub rounds       : u64,
+}
+
+pub fn round_alpha(round: u64, prev_beta: &[u8]) -> [u8; 32] {
+    Sha256::new().chain_update(round.to_be_bytes()).chain_update(prev_beta).finalize().into()
+}
+
+pub fn round_hash(round: u64, timestamp: i64, prev_hash: &[u8], alpha: &[u8], pi: &[u8], beta: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(round.to_be_bytes())
+        .chain_update(timestamp.to_be_bytes())
+        .chain_update(prev_hash)
+        .chain_update(alpha)
+        .chain_update(pi)
+        .chain_update(beta)
+        .finalize()
+        .into()
+}
+
+fn decode(hex_string: &str, round: u64, field: &'static str) -> Result<Vec<u8>, ChainInvalid> {
+    hex::decode(hex_string).map_err(|_err| ChainInvalid{ round, reason: field })
+}
+
+// Checks one round against its predecessor (`None` for round 1).
+pub fn verify_round(pk: &PublicKey, previous: Option<&BeaconRound>, record: &BeaconRound) -> Result<(), ChainInvalid> {
+    let round = record.round;
+    let (expected_round, prev_beta, prev_hash) = match previous {
+        Some(previous) => (previous.round + 1, decode(&previous.beta, previous.round, "beta is not hex")?, decode(&previous.hash, previous.round, "hash is not hex")?),
+        None => (1, GENESIS_BETA.to_vec(), GENESIS_HASH.to_vec()),
+    };
+    if round != expected_round {
+        return Err(ChainInvalid{ round, reason: "round number is out of sequence" });
+    }
+    if previous.is_some_and(|previous| record.timestamp < previous.timestamp) {
+        return Err(ChainInvalid{ round, reason: "timestamp goes backwards" });
+    }
+    if decode(&record.prev_hash, round, "prev_hash is not hex")? != prev_hash {
+        return Err(ChainInvalid{ round, reason: "prev_hash does not match the previous round" });
+    }
+    let alpha = decode(&record.alpha, round, "alpha is not hex")?;
+    if alpha != round_alpha(round, &prev_beta) {
+        return Err(ChainInvalid{ round, reason: "alpha does not match the round and previous beta" });
+    }
+    let pi_bytes = decode(&record.pi, round, "pi is not hex")?;
+    let pi = pi_bytes.clone().try_into().ok().and_then(Proof::from_bytes)
+        .ok_or(ChainInvalid{ round, reason: "pi is not a valid proof" })?;
+    let beta: Option<[u8; 64]> = pk.verify(&alpha, &pi).into();
+    let beta = beta.ok_or(ChainInvalid{ round, reason: "VRF proof does not verify" })?;
+    if decode(&record.beta, round, "beta is not hex")? != beta {
+        return Err(ChainInvalid{ round, reason: "beta does not match the proof" });
+    }
+    if decode(&record.hash, round, "hash is not hex")? != round_hash(round, record.timestamp, &prev_hash, &alpha, &pi_bytes, &beta) {
+        return Err(ChainInvalid{ round, reason: "hash does not match the record" });
+    }
+    Ok(())
+}
+
+// Checks a whole chain from genesis.
+pub fn verify_chain(pk: &PublicKey, rounds: &[BeaconRound]) -> Result<(), ChainInvalid> {
+    let mut previous = None;
+    for record in rounds {
+        verify_round(pk, previous, record)?;
+        previous = Some(record);
+    }
+    Ok(())
+}
+
+// Rounds from an NDJSON file, one record per line.
+pub fn read_rounds(path: &Path) -> Result<Vec<BeaconRound>, BeaconError> {
+    let contents = fs::read_to_string(path).map_err(BeaconError::Io)?;
+    contents.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| serde_json::from_str(line).map_err(|_err| BeaconError::ChainInvalid(ChainInvalid{ round: index as u64 + 1, reason: "record is not valid JSON" })))
+        .collect()
+}
+
+pub struct Beacon {
+    sk           : SecretKey,
+    interval_secs: u64,
+    file         : Option<PathBuf>,
+    rounds       : RwLock<Rounds>,
+}
+
+// Without a file the rounds live only in memory; with one, `offsets[n - 1]` is where the
+// line of round `n` starts and `len` is where the next one will.
+enum Rounds {
+    Memory(Vec<BeaconRound>),
+    File{ offsets: Vec<u64>, len: u64, latest: Option<BeaconRound> },
+}
+
+impl Rounds {
+    fn count(&self) -> u64 {
+        match self {
+            Rounds::Memory(rounds) => rounds.len() as u64,
+            Rounds::File{ offsets, .. } => offsets.len() as u64,
+        }
+    }
+
+    fn latest(&self) -> Option<&BeaconRound> {
+        match self {
+            Rounds::Memory(rounds) => rounds.last(),
+            Rounds::File{ latest, .. } => latest.as_ref(),
+        }
+    }
+}
+
+// Drops a last line without its newline, left by a crash during `append`, so the file ends
+// with the last complete round.
+fn truncate_torn_line(path: &Path) -> Result<(), BeaconError> {
+    let contents = fs::read(path).map_err(BeaconError::Io)?;
+    if contents.is_empty() || contents.ends_with(b"\n") {
+        return Ok(());
+    }
+    let len = contents.iter().rposition(|&byte| byte == b'\n').map_or(0, |i| i + 1);
+    log::warn!(target: "api", "Dropping a torn last line of {} ({} bytes)", path.display(), contents.len() - len);
+    let file = fs::OpenOptions::new().write(true).open(path).map_err(BeaconError::Io)?;
+    file.set_len(len as u64).and_then(|()| file.sync_all()).map_err(BeaconError::Io)
+}
+
+// Appends `line` and its newline with a single write, then syncs.
+fn append(path: &Path, line: &str) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{}\n", line).as_bytes())?;
+    file.sync_data()
+}
+
+fn parse_round(line: &str, round: u64) -> Result<BeaconRound, ChainInvalid> {
+    serde_json::from_str(line).map_err(|_err| ChainInvalid{ round, reason: "record is not valid JSON" })
+}
+
+// Indexes the lines of `path` and checks the last round against the one before it. Earlier
+// rounds were checked when they were stored; `beacon verify` checks the whole file.
+fn index_file(pk: &PublicKey, path: &Path) -> Result<Rounds, BeaconError> {
+    let mut reader = BufReader::new(fs::File::open(path).map_err(BeaconError::Io)?);
+    let (mut offsets, mut len) = (Vec::new(), 0u64);
+    let (mut previous_line, mut last_line, mut line) = (String::new(), String::new(), String::new());
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).map_err(BeaconError::Io)?;
+        if read == 0 {
+            break;
+        }
+        if !line.trim().is_empty() {
+            offsets.push(len);
+            std::mem::swap(&mut previous_line, &mut last_line);
+            std::mem::swap(&mut last_line, &mut line);
+        }
+        len += read as u64;
+    }
+
+    let count = offsets.len() as u64;
+    let latest = match count {
+        0 => None,
+        _ => {
+            let previous = (count > 1).then(|| parse_round(&previous_line, count - 1)).transpose().map_err(BeaconError::ChainInvalid)?;
+            let latest = parse_round(&last_line, count).map_err(BeaconError::ChainInvalid)?;
+            if previous.as_ref().is_some_and(|previous| previous.round != count - 1) {
+                return Err(BeaconError::ChainInvalid(ChainInvalid{ round: count - 1, reason: "round number is out of sequence" }));
+            }
+            verify_round(pk, previous.as_ref(), &latest).map_err(BeaconError::ChainInvalid)?;
+            Some(latest)
+        },
+    };
+    Ok(Rounds::File{ offsets, len, latest })
+}
+
+// The line starting at `offset`.
+fn read_line_at(path: &Path, offset: u64) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut line = String::new();
+    BufReader::new(file).read_line(&mut line)?;
+    Ok(line)
+}
+
+impl Beacon {
+    // Resumes the chain stored in `file`, after checking its last round against the key.
+    pub fn new(sk: SecretKey, interval_secs: u64, file: Option<PathBuf>) -> Result<Beacon, BeaconError> {
+        let rounds = match &file {
+            Some(path) if path.exists() => {
+                truncate_torn_line(path)?;
+                index_file(&PublicKey::from(sk), path)?
+            },
+            Some(_path) => Rounds::File{ offsets: Vec::new(), len: 0, latest: None },
+            None => Rounds::Memory(Vec::new()),
+        };
+        Ok(Beacon{ sk, interval_secs, file, rounds: RwLock::new(rounds) })
+    }
+
+    pub fn interval_secs(&self) -> u64 {
+        self.interval_secs
+    }
+
+    pub fn latest(&self) -> Option<BeaconRound> {
+        self.rounds.read().unwrap_or_else(|err| err.into_inner()).latest().cloned()
+    }
+
+    // Reads the file when one is configured, so call it off the async workers.
+    pub fn round(&self, round: u64) -> Option<BeaconRound> {
+        let index = usize::try_from(round.checked_sub(1)?).ok()?;
+        let offset = match &*self.rounds.read().unwrap_or_else(|err| err.into_inner()) {
+            Rounds::Memory(rounds) => return rounds.get(index).cloned(),
+            Rounds::File{ offsets, .. } => *offsets.get(index)?,
+        };
+        let path = self.file.as_ref()?;
+        match read_line_at(path, offset).map_err(BeaconError::Io).and_then(|line| parse_round(&line, round).map_err(BeaconError::ChainInvalid)) {
+            Ok(record) => Some(record),
+            Err(err) => {
+                log::error!(target: "api", "Beacon round {} could not be read from {}: {:?}", round, path.display(), err);
+                None
+            }
+        }
+    }
+
+    pub fn info(&self) -> BeaconInfo {
+        BeaconInfo {
+            pk           : hex::encode(PublicKey::from(self.sk).to_bytes()),
+            interval_secs: self.interval_secs,
+            rounds       : self.rounds.read().unwrap_or_else(|err| err.into_inner()).count(),
+        }
+    }
+
+    // Proves the next round, stores it, then publishes it. Readers are never blocked on the
+    // proof or the file; only the beacon task calls this, so rounds cannot race.
+    pub async fn next_round(&self, timestamp: i64) -> Result<BeaconRound, BeaconError> {
+        let previous = self.latest();
+        let (round, prev_beta, prev_hash) = match &previous {
+            Some(previous) => (
+                previous.round + 1,
+                hex::decode(&previous.beta).expect("stored rounds are verified"),
+                hex::decode(&previous.hash).expect("stored rounds are verified"),
+            ),
+            None => (1, GENESIS_BETA.to_vec(), GENESIS_HASH.to_vec()),
+        };
+        let timestamp = previous.as_ref().map_or(timestamp, |previous| timestamp.max(previous.timestamp));
+
+        let alpha = round_alpha(round, &prev_beta);
+        let pi = self.sk.prove(&alpha);
+        let beta = PublicKey::from(self.sk).verify(&alpha, &pi).unwrap();
+        let pi = pi.to_bytes();
+        let record = BeaconRound {
+            round,
+            timestamp,
+            prev_hash: hex::encode(&prev_hash),
+            alpha    : hex::encode(alpha),
+            pi       : hex::encode(pi),
+            beta     : hex::encode(beta),
+            hash     : hex::encode(round_hash(round, timestamp, &prev_hash, &alpha, &pi, &beta)),
+        };
+
+        let mut appended = 0;
+        if let Some(path) = self.file.clone() {
+            let line = serde_json::to_string(&record).expect("beacon round serializes to JSON");
+            appended = line.len() as u64 + 1;
+            tokio::task::spawn_blocking(move || append(&path, &line))
+                .await
+                .map_err(std::io::Error::other)
+                .and_then(|appended| appended)
+                .map_err(BeaconError::Io)?;
+        }
+        match &mut *self.rounds.write().unwrap_or_else(|err| err.into_inner()) {
+            Rounds::Memory(rounds) => rounds.push(record.clone()),
+            Rounds::File{ offsets, len, latest } => {
+                offsets.push(*len);
+                *len += appended;
+                *latest = Some(record.clone());
+            },
+        }
+        Ok(record)
+    }
+}
+
+// Api endpoints.
+pub fn api_info(beacon: Option<&Beacon>) -> Result<BeaconInfo, BeaconInputError> {
+    match beacon {
+        Some(beacon) => Ok(beacon.info()),
+        None => Err(BeaconInputError::BeaconDisabled(BeaconDisabled))
+    }
+}
+
+pub fn api_latest(beacon: Option<&Beacon>) -> Result<BeaconRound, BeaconInputError> {
+    let beacon = beacon.ok_or(BeaconInputError::BeaconDisabled(BeaconDisabled))?;
+    beacon.latest().ok_or(BeaconInputError::RoundNotFound(RoundNotFound))
+}
+
+pub fn api_round(beacon: Option<&Beacon>, round: u64) -> Result<BeaconRound, BeaconInputError> {
+    let beacon = beacon.ok_or(BeaconInputError::BeaconDisabled(BeaconDisabled))?;
+    beacon.round(round).ok_or(BeaconInputError::RoundNotFound(RoundNotFound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sk() -> SecretKey {
+        SecretKey::from_bytes([7u8; 32]).unwrap()
+    }
+
+    async fn chain(beacon: &Beacon, rounds: i64) -> Vec<BeaconRound> {
+        let mut chain = Vec::new();
+        for timestamp in 0..rounds {
+            chain.push(beacon.next_round(1_760_000_000 + timestamp).await.unwrap());
+        }
+        chain
+    }
+
+    #[tokio::test]
+    async fn published_chain_verifies() {
+        let rounds = chain(&Beacon::new(sk(), 30, None).unwrap(), 3).await;
+        assert!(verify_chain(&PublicKey::from(sk()), &rounds).is_ok());
+    }
+
+    #[tokio::test]
+    async fn tampered_prev_hash_fails() {
+        let mut rounds = chain(&Beacon::new(sk(), 30, None).unwrap(), 3).await;
+        rounds[1].prev_hash = hex::encode([1u8; 32]);
+        let err = verify_chain(&PublicKey::from(sk()), &rounds).unwrap_err();
+        assert_eq!((err.round, err.reason), (2, "prev_hash does not match the previous round"));
+    }
+
+    #[tokio::test]
+    async fn tampered_beta_fails() {
+        let mut rounds = chain(&Beacon::new(sk(), 30, None).unwrap(), 3).await;
+        rounds[1].beta = rounds[0].beta.clone();
+        let err = verify_chain(&PublicKey::from(sk()), &rounds).unwrap_err();
+        assert_eq!((err.round, err.reason), (2, "beta does not match the proof"));
+    }
+
+    #[tokio::test]
+    async fn torn_last_line_is_dropped() {
+        let path = std::env::temp_dir().join(format!("beacon-test-{}.ndjson", uuid::Uuid::new_v4()));
+        let beacon = Beacon::new(sk(), 30, Some(path.clone())).unwrap();
+        chain(&beacon, 2).await;
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"round":3,"timest"#).unwrap();
+
+        let beacon = Beacon::new(sk(), 30, Some(path.clone())).unwrap();
+        assert_eq!(beacon.info().rounds, 2);
+        assert_eq!(chain(&beacon, 1).await[0].round, 3);
+        assert_eq!(read_rounds(&path).unwrap().len(), 3);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stored_rounds_are_read_back_after_a_restart() {
+        let path = std::env::temp_dir().join(format!("beacon-test-{}.ndjson", uuid::Uuid::new_v4()));
+        let stored = chain(&Beacon::new(sk(), 30, Some(path.clone())).unwrap(), 3).await;
+
+        let beacon = Beacon::new(sk(), 30, Some(path.clone())).unwrap();
+        let appended = chain(&beacon, 2).await;
+        let served: Vec<BeaconRound> = (1..=5).map(|round| beacon.round(round).unwrap()).collect();
+        let expected: Vec<String> = stored.iter().chain(&appended).map(|record| record.hash.clone()).collect();
+        assert_eq!(served.iter().map(|record| record.hash.clone()).collect::<Vec<_>>(), expected);
+        assert_eq!(beacon.latest().unwrap().round, 5);
+        assert!(beacon.round(0).is_none());
+        assert!(beacon.round(6).is_none());
+        assert!(verify_chain(&PublicKey::from(sk()), &read_rounds(&path).unwrap()).is_ok());
+        fs::remove_file(path).unwrap();
+    }
+
+    // Only the last round is checked at startup; `verify_chain` finds earlier damage.
+    #[tokio::test]
+    async fn startup_checks_the_last_round() {
+        let path = std::env::temp_dir().join(format!("beacon-test-{}.ndjson", uuid::Uuid::new_v4()));
+        let mut rounds = chain(&Beacon::new(sk(), 30, Some(path.clone())).unwrap(), 3).await;
+        let write = |rounds: &[BeaconRound]| {
+            let lines: Vec<String> = rounds.iter().map(|record| serde_json::to_string(record).unwrap() + "\n").collect();
+            fs::write(&path, lines.concat()).unwrap();
+        };
+
+        rounds[0].timestamp += 1;
+        write(&rounds);
+        assert!(Beacon::new(sk(), 30, Some(path.clone())).is_ok());
+        assert_eq!(verify_chain(&PublicKey::from(sk()), &read_rounds(&path).unwrap()).unwrap_err().round, 1);
+
+        rounds[2].beta = rounds[1].beta.clone();
+        write(&rounds);
+        match Beacon::new(sk(), 30, Some(path.clone())) {
+            Err(BeaconError::ChainInvalid(err)) => assert_eq!((err.round, err.reason), (3, "beta does not match the proof")),
+            _ => panic!("a tampered last round is rejected"),
+        }
+
+        write(&rounds[1..]);
+        match Beacon::new(sk(), 30, Some(path.clone())) {
+            Err(BeaconError::ChainInvalid(err)) => assert_eq!((err.round, err.reason), (1, "round number is out of sequence")),
+            _ => panic!("a file that does not start at round 1 is rejected"),
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
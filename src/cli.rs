@@ -0,0 +1,278 @@
+use clap::{Parser, Subcommand, Args};
+use std::path::{Path, PathBuf};
+use rand_core::RngCore;
+
+use crate::auth;
+use crate::beacon;
+use crate::config::ServeArgs;
+use crate::drand;
+use crate::ecvrf;
+use crate::keystore;
+use crate::generator::GeneratorKind;
+use crate::rng::ChainVersion;
+use crate::selftest;
+
+#[derive(Parser)]
+#[command(name = "true-random-on-ton", about = "RandomTON Proof-of-Work Service.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Server options, used when no command is given.
+    #[command(flatten)]
+    pub serve: ServeArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage encrypted VRF keystores.
+    #[command(subcommand)]
+    Keystore(KeystoreCommand),
+    /// Work with beacon round records.
+    #[command(subcommand)]
+    Beacon(BeaconCommand),
+    /// Verify drand rounds.
+    #[command(subcommand)]
+    Drand(DrandCommand),
+    /// Manage API keys.
+    #[command(subcommand)]
+    Auth(AuthCommand),
+    /// Run the statistical self-test battery over generated output.
+    Selftest {
+        /// Sample generator blocks (`rng`) or VRF betas (`vrf`).
+        #[arg(long, default_value_t = selftest::Source::Rng)]
+        source: selftest::Source,
+        /// Number of samples.
+        #[arg(long, default_value_t = selftest::DEFAULT_SAMPLES)]
+        samples: usize,
+        /// Ticket buckets for the chi-square test.
+        #[arg(long, default_value_t = selftest::DEFAULT_BUCKETS)]
+        buckets: u64,
+        /// Hex seed; a random one is used otherwise.
+        #[arg(long)]
+        seed: Option<String>,
+        /// Seed-chain version for `sha512-chain`.
+        #[arg(long, default_value_t = ChainVersion::V1)]
+        version: ChainVersion,
+        /// Ticket generator.
+        #[arg(long, default_value_t = GeneratorKind::Sha512Chain)]
+        generator: GeneratorKind,
+        /// Print the report as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct KeystoreDir {
+    /// Directory holding keystore files.
+    #[arg(long, env = "VRF_KEYSTORE_DIR", default_value = "keystore")]
+    pub dir: PathBuf,
+}
+
+#[derive(Subcommand)]
+pub enum KeystoreCommand {
+    /// Encrypt a new (or given) VRF secret key into a keystore file.
+    Create {
+        #[command(flatten)]
+        dir: KeystoreDir,
+        /// Existing secret key to import (hex); a new one is generated otherwise.
+        #[arg(long)]
+        sk: Option<String>,
+        /// Read the passphrase from this file instead of the environment or a prompt.
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// List keystore ids and public keys.
+    List {
+        #[command(flatten)]
+        dir: KeystoreDir,
+    },
+    /// Re-encrypt a keystore under a new passphrase.
+    ChangePassphrase {
+        #[command(flatten)]
+        dir: KeystoreDir,
+        /// Keystore id.
+        id: String,
+        /// Read the current passphrase from this file.
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+        /// Read the new passphrase from this file.
+        #[arg(long)]
+        new_passphrase_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BeaconCommand {
+    /// Check a beacon chain from genesis.
+    Verify {
+        /// Beacon public key (hex).
+        #[arg(long)]
+        pk: String,
+        /// NDJSON file with one round record per line, e.g. `BEACON_FILE`.
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DrandCommand {
+    /// Verify a drand round from a file or a drand HTTP endpoint.
+    Verify {
+        /// Chain info JSON file; fetched from `--url` otherwise.
+        #[arg(long)]
+        info: Option<PathBuf>,
+        /// drand HTTP endpoint serving `/info` and `/public/{round}`.
+        #[arg(long)]
+        url: Option<String>,
+        /// Round to fetch from `--url`, latest when omitted.
+        #[arg(long)]
+        round: Option<u64>,
+        /// Round JSON file; fetched from `--url` otherwise.
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuthCommand {
+    /// Generate an API key and print its `[[auth.api_keys]]` entry.
+    Key {
+        /// Key id, shown in logs.
+        #[arg(long)]
+        id: String,
+        /// Scope to grant, e.g. `vrf:prove`; repeat for several.
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<auth::Scope>,
+    },
+}
+
+fn drand_error_message(err: drand::DrandError) -> String {
+    match err {
+        drand::DrandError::ChainInfoInvalid(_) => "drand chain info is invalid.".to_string(),
+        drand::DrandError::DrandInvalid(_) => "drand round is invalid.".to_string(),
+        drand::DrandError::DrandUnavailable(_) => "drand endpoint is unavailable.".to_string(),
+        drand::DrandError::Io(err) => format!("drand file I/O error: {}", err),
+    }
+}
+
+pub fn keystore_error_message(err: keystore::KeystoreError) -> String {
+    match err {
+        keystore::KeystoreError::KeystoreInvalid(_) => "Keystore is invalid.".to_string(),
+        keystore::KeystoreError::PassphraseInvalid(_) => "Passphrase is invalid.".to_string(),
+        keystore::KeystoreError::PassphraseEmpty(_) => "Passphrase must not be empty.".to_string(),
+        keystore::KeystoreError::IdMismatch(keystore::IdMismatch(path)) => format!("Keystore {} must be named after its id.", path.display()),
+        keystore::KeystoreError::IdDuplicate(keystore::IdDuplicate(id)) => format!("Keystore id {} is used more than once.", id),
+        keystore::KeystoreError::Io(err) => format!("Keystore I/O error: {}", err),
+    }
+}
+
+fn prompt_passphrase(prompt: &str, confirm: bool) -> Result<String, String> {
+    let passphrase = rpassword::prompt_password(prompt).map_err(|err| err.to_string())?;
+    if confirm && rpassword::prompt_password("Repeat passphrase: ").map_err(|err| err.to_string())? != passphrase {
+        return Err("Passphrases do not match.".to_string());
+    }
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty.".to_string());
+    }
+    Ok(passphrase)
+}
+
+// File flag first, then `VRF_KEYSTORE_PASSPHRASE(_FILE)`, then an interactive prompt.
+fn passphrase(file: Option<&Path>, prompt: &str, confirm: bool) -> Result<String, String> {
+    if let Some(file) = file {
+        return keystore::read_passphrase_file(file).map_err(keystore_error_message);
+    }
+    match keystore::passphrase_from_env().map_err(keystore_error_message)? {
+        Some(passphrase) => Ok(passphrase),
+        None => prompt_passphrase(prompt, confirm)
+    }
+}
+
+pub fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Keystore(KeystoreCommand::Create { dir, sk, passphrase_file }) => {
+            let sk = match sk {
+                Some(sk) => ecvrf::decode_sk(sk).map_err(|_err| "VRF secret key is invalid.".to_string())?,
+                None => {
+                    let sk = ecvrf::generate_sk().map_err(|_err| "Entropy source failed health tests.".to_string())?;
+                    ecvrf::decode_sk(sk).expect("generated key is valid")
+                },
+            };
+            let passphrase = passphrase(passphrase_file.as_deref(), "Passphrase: ", true)?;
+            let keystore = keystore::encrypt(&sk, &passphrase);
+            let path = keystore::path(&dir.dir, &keystore.id);
+            keystore::write(&path, &keystore).map_err(keystore_error_message)?;
+            println!("{}\t{}\t{}", keystore.id, keystore.pk, path.display());
+        },
+        Command::Keystore(KeystoreCommand::List { dir }) => {
+            for (path, keystore) in keystore::list(&dir.dir).map_err(keystore_error_message)? {
+                println!("{}\t{}\t{}", keystore.id, keystore.pk, path.display());
+            }
+        },
+        Command::Keystore(KeystoreCommand::ChangePassphrase { dir, id, passphrase_file, new_passphrase_file }) => {
+            let (path, old_keystore) = keystore::find(&dir.dir, &id).map_err(keystore_error_message)?;
+            let old_passphrase = passphrase(passphrase_file.as_deref(), "Current passphrase: ", false)?;
+            let new_passphrase = match new_passphrase_file {
+                Some(file) => keystore::read_passphrase_file(&file).map_err(keystore_error_message)?,
+                None => prompt_passphrase("New passphrase: ", true)?,
+            };
+            let new_keystore = keystore::change_passphrase(&old_keystore, &old_passphrase, &new_passphrase)
+                .map_err(keystore_error_message)?;
+            keystore::write(&path, &new_keystore).map_err(keystore_error_message)?;
+            println!("{}\t{}\t{}", new_keystore.id, new_keystore.pk, path.display());
+        },
+        Command::Beacon(BeaconCommand::Verify { pk, file }) => {
+            let pk = hex::decode(pk).ok()
+                .and_then(|pk| pk.try_into().ok())
+                .and_then(vrf_r255::PublicKey::from_bytes)
+                .ok_or("VRF public key is invalid.".to_string())?;
+            let rounds = beacon::read_rounds(&file).map_err(|err| match err {
+                beacon::BeaconError::ChainInvalid(err) => format!("Round {}: {}.", err.round, err.reason),
+                beacon::BeaconError::Io(err) => format!("Beacon file I/O error: {}", err),
+            })?;
+            beacon::verify_chain(&pk, &rounds).map_err(|err| format!("Round {}: {}.", err.round, err.reason))?;
+            println!("{} round(s) verified from genesis.", rounds.len());
+        },
+        Command::Drand(DrandCommand::Verify { info, url, round, file }) => {
+            let info = match (&info, &url) {
+                (Some(path), _) => drand::read_info(path),
+                (None, Some(url)) => drand::fetch_info(url),
+                (None, None) => return Err("Either --info or --url is required.".to_string()),
+            }.map_err(drand_error_message)?;
+            let drand_round = match (&file, &url) {
+                (Some(path), _) => drand::read_round(path),
+                (None, Some(url)) => drand::fetch_round(url, round),
+                (None, None) => return Err("Either a round file or --url is required.".to_string()),
+            }.map_err(drand_error_message)?;
+            let verifier = drand::Verifier::new(info).map_err(|_err| "drand chain info is invalid.".to_string())?;
+            let randomness = verifier.verify(&drand_round).map_err(|_err| "drand round is invalid.".to_string())?;
+            println!("{}\t{}", drand_round.round, hex::encode(randomness));
+        },
+        Command::Auth(AuthCommand::Key { id, scopes }) => {
+            let mut key = [0u8; 32];
+            rand_core::OsRng.fill_bytes(&mut key);
+            let key = hex::encode(key);
+            let scopes = scopes.iter().map(|scope| format!("\"{}\"", scope)).collect::<Vec<_>>();
+            eprintln!("API key (send as X-Api-Key, shown once): {}", key);
+            println!("[[auth.api_keys]]\nid = \"{}\"\nsha256 = \"{}\"\nscopes = [{}]", id, hex::encode(auth::key_hash(&key)), scopes.join(", "));
+        },
+        Command::Selftest { source, samples, buckets, seed, version, generator, json } => {
+            let report = selftest::self_test(source, samples, buckets, seed.as_deref(), version, generator).map_err(|err| match err {
+                selftest::SelfTestInputError::SeedInvalid(_) => "Seed is invalid.".to_string(),
+                selftest::SelfTestInputError::IterationsExceeded(_) => "Iterations exceed maximum allowed limit.".to_string(),
+                selftest::SelfTestInputError::SamplesInvalid(_) => "Sample count is too small for the requested buckets.".to_string(),
+            })?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).map_err(|err| err.to_string())?);
+            } else {
+                println!("source {}, seed {}, {} samples, {} bits, significance {}", report.source, report.seed, report.samples, report.bits, report.significance);
+                for test in &report.tests {
+                    println!("{:<20}{:>16.6}{:>12.6}  {}", test.name, test.statistic, test.p_value, if test.passed { "PASS" } else { "FAIL" });
+                }
+            }
+            if !report.passed {
+                return Err("Self-test failed.".to_string());
+            }
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::{auth, beacon, hdkey, merkle, ratelimit, rng, shuffle, tls};
+
+// Server configuration: the TOML file given with `--config`, overridden by environment
+// variables and then by command-line flags (see `ServeArgs`). Everything that is not a secret
+// lives here; the master seed and keystore passphrase stay in the environment.
+
+pub const DEFAULT_LISTEN: &str = "0.0.0.0:3111";
+pub const DEFAULT_BODY_LIMIT: u64 = 1024 * 16;
+pub const DEFAULT_LARGE_BODY_LIMIT: u64 = 1024 * 1024 * 8;
+pub const DEFAULT_LOG_FILTER: &str = "api=info";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(String),
+    Invalid(Vec<String>),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "Config file {} is not readable: {}", path.display(), err),
+            ConfigError::Parse(message) => write!(f, "Config file is invalid: {}", message),
+            ConfigError::Invalid(problems) => write!(f, "Config is invalid:\n  {}", problems.join("\n  ")),
+        }
+    }
+}
+
+// Route groups that can be disabled and carry their own body limit.
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RouteGroup {
+    Vrf,     // /api/vrf/prove, /api/vrf/verify, /api/vrf/pk
+    Keys,    // /api/vrf/sk/..., /api/vrf/pk/derive, /api/vrf/keys/...
+    Utils,   // /api/utils/...
+    Random,  // /api/random/...
+    Merkle,  // /api/merkle/...
+    Drand,   // /api/drand/...
+    Admin,   // /api/admin/...
+    Health,  // /api/health/...
+    Beacon,  // /beacon/...
+    Metrics, // /metrics
+    Docs,    // /openapi.json, /docs/...
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server    : ServerConfig,
+    pub limits    : LimitsConfig,
+    pub routes    : RoutesConfig,
+    pub auth      : AuthConfig,
+    pub rate_limit: RateLimitConfig,
+    pub log       : LogConfig,
+    pub keystore  : KeystoreConfig,
+    pub beacon    : BeaconConfig,
+    pub drand     : DrandConfig,
+    pub grpc      : GrpcConfig,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls   : Option<TlsConfig>,
+}
+
+// The gRPC service shares `server.tls`, auth, rate limits, limits and route groups.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrpcConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen    : Option<SocketAddr>, // Enables the gRPC service.
+    pub reflection: bool,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert       : PathBuf, // PEM certificate chain.
+    pub key        : PathBuf, // PEM private key.
+    #[serde(default = "default_reload_secs")]
+    pub reload_secs: u64,     // How often `cert` and `key` are checked for changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca  : Option<PathBuf>, // PEM CA certificates; enables mTLS for the prove and key routes.
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_iterations       : usize, // `/api/random`, pages, distributions and prize winners.
+    pub max_stream_iterations: usize, // Streams, page offsets, shuffle ranks and self-test samples.
+    pub max_shuffle_items    : usize,
+    pub max_participants     : usize,
+    pub body                 : BodyLimits,
+}
+
+// Request body limits in bytes, per route group.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BodyLimits {
+    pub vrf   : u64,
+    pub keys  : u64,
+    pub utils : u64,
+    pub random: u64,
+    pub merkle: u64,
+    pub drand : u64,
+    pub admin : u64,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutesConfig {
+    pub disabled: Vec<RouteGroup>,
+}
+
+// Credentials and public scopes, see `auth`. The HS* JWT secret comes from `AUTH_JWT_SECRET`.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub public  : Vec<auth::Scope>,
+    pub api_keys: Vec<auth::ApiKeyConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt     : Option<auth::JwtConfig>,
+}
+
+// Token buckets per client, see `ratelimit`. Behind a proxy, set `trust_forwarded_for` so
+// anonymous clients are told apart by the address the proxy appends to `X-Forwarded-For`
+// instead of the proxy's own address.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled            : bool,
+    pub trust_forwarded_for: bool,
+    pub prove              : ratelimit::BucketConfig,
+    pub verify             : ratelimit::BucketConfig,
+    pub random             : ratelimit::BucketConfig,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub filter: String, // `RUST_LOG` syntax.
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeystoreConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeaconConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id       : Option<String>,
+    pub interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file         : Option<PathBuf>,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DrandConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_info: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url       : Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig{ listen: DEFAULT_LISTEN.parse().expect("default listen address is valid"), tls: None }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> TlsConfig {
+        TlsConfig{ cert: PathBuf::new(), key: PathBuf::new(), reload_secs: default_reload_secs(), client_ca: None }
+    }
+}
+
+impl Default for GrpcConfig {
+    fn default() -> GrpcConfig {
+        GrpcConfig{ listen: None, reflection: true }
+    }
+}
+
+fn default_reload_secs() -> u64 {
+    tls::DEFAULT_RELOAD_SECS
+}
+
+impl Default for LimitsConfig {
+    fn default() -> LimitsConfig {
+        LimitsConfig {
+            max_iterations       : rng::MAX_ITERATIONS,
+            max_stream_iterations: rng::DEFAULT_MAX_STREAM_ITERATIONS,
+            max_shuffle_items    : shuffle::MAX_SHUFFLE_ITEMS,
+            max_participants     : merkle::MAX_PARTICIPANTS,
+            body                 : BodyLimits::default(),
+        }
+    }
+}
+
+impl Default for BodyLimits {
+    fn default() -> BodyLimits {
+        BodyLimits {
+            vrf   : DEFAULT_BODY_LIMIT,
+            keys  : DEFAULT_BODY_LIMIT,
+            utils : DEFAULT_BODY_LIMIT,
+            random: DEFAULT_BODY_LIMIT,
+            merkle: DEFAULT_LARGE_BODY_LIMIT, // Participant lists.
+            drand : DEFAULT_BODY_LIMIT,
+            admin : DEFAULT_BODY_LIMIT,
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> AuthConfig {
+        AuthConfig{ public: auth::DEFAULT_PUBLIC_SCOPES.to_vec(), api_keys: Vec::new(), jwt: None }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            enabled            : true,
+            trust_forwarded_for: false,
+            prove              : ratelimit::DEFAULT_PROVE,
+            verify             : ratelimit::DEFAULT_VERIFY,
+            random             : ratelimit::DEFAULT_RANDOM,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn budgets(&self) -> HashMap<ratelimit::Budget, ratelimit::BucketConfig> {
+        HashMap::from([
+            (ratelimit::Budget::Prove, self.prove),
+            (ratelimit::Budget::Verify, self.verify),
+            (ratelimit::Budget::Random, self.random),
+        ])
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig{ format: LogFormat::default(), filter: DEFAULT_LOG_FILTER.to_string() }
+    }
+}
+
+impl Default for BeaconConfig {
+    fn default() -> BeaconConfig {
+        BeaconConfig{ key_id: None, interval_secs: beacon::DEFAULT_INTERVAL_SECS, file: None }
+    }
+}
+
+impl BodyLimits {
+    // Body limit for a group; groups without bodies get the default.
+    pub fn get(&self, group: RouteGroup) -> u64 {
+        match group {
+            RouteGroup::Vrf => self.vrf,
+            RouteGroup::Keys => self.keys,
+            RouteGroup::Utils => self.utils,
+            RouteGroup::Random => self.random,
+            RouteGroup::Merkle => self.merkle,
+            RouteGroup::Drand => self.drand,
+            RouteGroup::Admin => self.admin,
+            RouteGroup::Health | RouteGroup::Beacon | RouteGroup::Metrics | RouteGroup::Docs => DEFAULT_BODY_LIMIT,
+        }
+    }
+}
+
+impl RoutesConfig {
+    pub fn enabled(&self, group: RouteGroup) -> bool {
+        !self.disabled.contains(&group)
+    }
+}
+
+// Overrides for the config file, from flags or their environment variables.
+#[derive(Args, Default)]
+pub struct ServeArgs {
+    /// TOML config file.
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+    /// Print the effective configuration as TOML and exit.
+    #[arg(long)]
+    pub print_config: bool,
+    /// Listen address.
+    #[arg(long, env = "SERVER_LISTEN")]
+    pub listen: Option<SocketAddr>,
+    /// gRPC listen address; enables the gRPC service.
+    #[arg(long, env = "GRPC_LISTEN")]
+    pub grpc_listen: Option<SocketAddr>,
+    /// PEM certificate chain; serves HTTPS together with `--tls-key`.
+    #[arg(long, env = "SERVER_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key.
+    #[arg(long, env = "SERVER_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+    /// PEM CA certificates; prove and key routes then require a client certificate signed by them.
+    #[arg(long, env = "SERVER_TLS_CLIENT_CA")]
+    pub tls_client_ca: Option<PathBuf>,
+    /// Iteration cap for `/api/random`, pages, distributions and prize draws.
+    #[arg(long, env = "RNG_MAX_ITERATIONS")]
+    pub max_iterations: Option<usize>,
+    /// Iteration cap for streams, page offsets, shuffle ranks and self-tests.
+    #[arg(long, env = "RNG_MAX_STREAM_ITERATIONS")]
+    pub max_stream_iterations: Option<usize>,
+    /// Log format.
+    #[arg(long, env = "LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+    /// Log filter, in `RUST_LOG` syntax.
+    #[arg(long, env = "RUST_LOG")]
+    pub log_filter: Option<String>,
+    /// Directory of keystores to unlock at startup.
+    #[arg(long, env = "VRF_KEYSTORE_DIR")]
+    pub keystore_dir: Option<PathBuf>,
+    /// Keystore id of the beacon key; enables the beacon.
+    #[arg(long, env = "BEACON_KEY_ID")]
+    pub beacon_key_id: Option<String>,
+    /// Seconds between beacon rounds.
+    #[arg(long, env = "BEACON_INTERVAL_SECS")]
+    pub beacon_interval_secs: Option<u64>,
+    /// NDJSON file the beacon chain is stored in.
+    #[arg(long, env = "BEACON_FILE")]
+    pub beacon_file: Option<PathBuf>,
+    /// drand chain info JSON file.
+    #[arg(long, env = "DRAND_CHAIN_INFO")]
+    pub drand_chain_info: Option<PathBuf>,
+    /// drand HTTP endpoint.
+    #[arg(long, env = "DRAND_URL")]
+    pub drand_url: Option<String>,
+    /// Key anonymous clients by the address the proxy appends to `X-Forwarded-For`.
+    #[arg(long, env = "RATE_LIMIT_TRUST_FORWARDED_FOR")]
+    pub trust_forwarded_for: Option<bool>,
+}
+
+impl Config {
+    pub fn read(path: &Path) -> Result<Config, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(err.to_string()))
+    }
+
+    // File, then overrides, then validation.
+    pub fn load(args: &ServeArgs) -> Result<Config, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => Config::read(path)?,
+            None => Config::default(),
+        };
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn apply(&mut self, args: &ServeArgs) {
+        if let Some(listen) = args.listen {
+            self.server.listen = listen;
+        }
+        if let Some(grpc_listen) = args.grpc_listen {
+            self.grpc.listen = Some(grpc_listen);
+        }
+        if args.tls_cert.is_some() || args.tls_key.is_some() || args.tls_client_ca.is_some() {
+            let tls = self.server.tls.get_or_insert_with(TlsConfig::default);
+            if let Some(cert) = &args.tls_cert {
+                tls.cert = cert.clone();
+            }
+            if let Some(key) = &args.tls_key {
+                tls.key = key.clone();
+            }
+            if let Some(client_ca) = &args.tls_client_ca {
+                tls.client_ca = Some(client_ca.clone());
+            }
+        }
+        if let Some(max_iterations) = args.max_iterations {
+            self.limits.max_iterations = max_iterations;
+        }
+        if let Some(max_stream_iterations) = args.max_stream_iterations {
+            self.limits.max_stream_iterations = max_stream_iterations;
+        }
+        if let Some(log_format) = args.log_format {
+            self.log.format = log_format;
+        }
+        if let Some(log_filter) = &args.log_filter {
+            self.log.filter = log_filter.clone();
+        }
+        if let Some(dir) = &args.keystore_dir {
+            self.keystore.dir = Some(dir.clone());
+        }
+        if let Some(key_id) = &args.beacon_key_id {
+            self.beacon.key_id = Some(key_id.clone());
+        }
+        if let Some(interval_secs) = args.beacon_interval_secs {
+            self.beacon.interval_secs = interval_secs;
+        }
+        if let Some(file) = &args.beacon_file {
+            self.beacon.file = Some(file.clone());
+        }
+        if let Some(chain_info) = &args.drand_chain_info {
+            self.drand.chain_info = Some(chain_info.clone());
+        }
+        if let Some(url) = &args.drand_url {
+            self.drand.url = Some(url.clone());
+        }
+        if let Some(trust_forwarded_for) = args.trust_forwarded_for {
+            self.rate_limit.trust_forwarded_for = trust_forwarded_for;
+        }
+    }
+
+    // Collects every problem instead of stopping at the first one.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if let Some(tls) = &self.server.tls {
+            for (name, path) in [("server.tls.cert", &tls.cert), ("server.tls.key", &tls.key)] {
+                if path.as_os_str().is_empty() {
+                    problems.push(format!("{} is required when TLS is enabled", name));
+                } else if !path.is_file() {
+                    problems.push(format!("{} ({}) is not a readable file", name, path.display()));
+                }
+            }
+            if let Some(client_ca) = &tls.client_ca && !client_ca.is_file() {
+                problems.push(format!("server.tls.client_ca ({}) is not a readable file", client_ca.display()));
+            }
+            if tls.reload_secs == 0 {
+                problems.push("server.tls.reload_secs must be positive".to_string());
+            }
+        }
+
+        if self.grpc.listen == Some(self.server.listen) {
+            problems.push("grpc.listen must differ from server.listen".to_string());
+        }
+
+        let limits = &self.limits;
+        if limits.max_iterations == 0 {
+            problems.push("limits.max_iterations must be positive".to_string());
+        }
+        if limits.max_stream_iterations < limits.max_iterations {
+            problems.push("limits.max_stream_iterations must be at least limits.max_iterations".to_string());
+        }
+        if limits.max_shuffle_items == 0 {
+            problems.push("limits.max_shuffle_items must be positive".to_string());
+        }
+        if limits.max_participants == 0 {
+            problems.push("limits.max_participants must be positive".to_string());
+        }
+        for group in [RouteGroup::Vrf, RouteGroup::Keys, RouteGroup::Utils, RouteGroup::Random, RouteGroup::Merkle, RouteGroup::Drand, RouteGroup::Admin] {
+            if limits.body.get(group) < 1024 {
+                problems.push(format!("limits.body.{} must be at least 1024 bytes", group));
+            }
+        }
+
+        for (index, api_key) in self.auth.api_keys.iter().enumerate() {
+            if hex::decode(&api_key.sha256).map_or(true, |hash| hash.len() != 32) {
+                problems.push(format!("auth.api_keys.{}.sha256 must be a hex SHA-256", api_key.id));
+            }
+            if api_key.scopes.is_empty() {
+                problems.push(format!("auth.api_keys.{}.scopes must not be empty", api_key.id));
+            }
+            if self.auth.api_keys[..index].iter().any(|other| other.id == api_key.id) {
+                problems.push(format!("auth.api_keys.{} is listed twice", api_key.id));
+            }
+        }
+        if let Some(jwt) = &self.auth.jwt {
+            if jwt.algorithms.is_empty() {
+                problems.push("auth.jwt.algorithms must not be empty".to_string());
+            }
+            match &jwt.es_public_key {
+                Some(path) if !path.is_file() => problems.push(format!("auth.jwt.es_public_key ({}) is not a readable file", path.display())),
+                None if jwt.algorithms.iter().any(|algorithm| !algorithm.is_hmac()) => problems.push("auth.jwt.es_public_key is required for ES* algorithms".to_string()),
+                _ => {},
+            }
+        }
+
+        for (budget, bucket) in self.rate_limit.budgets() {
+            if !(bucket.rate.is_finite() && bucket.rate > 0.0) {
+                problems.push(format!("rate_limit.{}.rate must be positive", budget));
+            }
+            if bucket.burst == 0 {
+                problems.push(format!("rate_limit.{}.burst must be positive", budget));
+            }
+        }
+
+        if self.log.filter.trim().is_empty() {
+            problems.push("log.filter must not be empty".to_string());
+        }
+
+        if let Some(dir) = &self.keystore.dir && !dir.is_dir() {
+            problems.push(format!("keystore.dir ({}) is not a directory", dir.display()));
+        }
+
+        if hdkey::master_seed_from_env().is_err() {
+            problems.push(format!("VRF_MASTER_SEED must be a {}-{} byte hex string", hdkey::MIN_SEED_LEN, hdkey::MAX_SEED_LEN));
+        }
+
+        if self.beacon.interval_secs == 0 {
+            problems.push("beacon.interval_secs must be positive".to_string());
+        }
+        if self.beacon.key_id.is_some() && self.keystore.dir.is_none() {
+            problems.push("beacon.key_id requires keystore.dir".to_string());
+        }
+        if self.beacon.key_id.is_some() && !self.routes.enabled(RouteGroup::Beacon) {
+            problems.push("beacon.key_id is set but the beacon routes are disabled".to_string());
+        }
+
+        if let Some(chain_info) = &self.drand.chain_info && !chain_info.is_file() {
+            problems.push(format!("drand.chain_info ({}) is not a readable file", chain_info.display()));
+        }
+        if let Some(url) = &self.drand.url && !(url.starts_with("http://") || url.starts_with("https://")) {
+            problems.push("drand.url must be an http:// or https:// URL".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config serializes to TOML")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid(problems)) => problems,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[limits]\nmax_iteration = 1\n").is_err());
+        assert!(toml::from_str::<Config>("[server]\nlisten = \"nowhere\"\n").is_err());
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(problems(&Config::default()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn validate_collects_every_problem() {
+        let mut config = Config::default();
+        config.limits.max_iterations = 0;
+        config.limits.body.random = 10;
+        config.grpc.listen = Some(config.server.listen);
+        config.auth.api_keys = vec![auth::ApiKeyConfig{ id: "ops".to_string(), sha256: "00".to_string(), scopes: Vec::new() }];
+        config.rate_limit.prove.burst = 0;
+        config.beacon.key_id = Some("beacon".to_string());
+        config.drand.url = Some("ftp://drand.example".to_string());
+
+        assert_eq!(problems(&config), vec![
+            "grpc.listen must differ from server.listen",
+            "limits.max_iterations must be positive",
+            "limits.body.random must be at least 1024 bytes",
+            "auth.api_keys.ops.sha256 must be a hex SHA-256",
+            "auth.api_keys.ops.scopes must not be empty",
+            "rate_limit.prove.burst must be positive",
+            "beacon.key_id requires keystore.dir",
+            "drand.url must be an http:// or https:// URL",
+        ]);
+    }
+
+    #[test]
+    fn stream_limit_must_cover_the_iteration_limit() {
+        let mut config = Config::default();
+        config.limits.max_stream_iterations = config.limits.max_iterations - 1;
+        assert_eq!(problems(&config), vec!["limits.max_stream_iterations must be at least limits.max_iterations"]);
+    }
+
+    #[test]
+    fn drand_urls_may_use_https() {
+        let mut config = Config::default();
+        for url in ["https://api.drand.sh", "http://127.0.0.1:8080"] {
+            config.drand.url = Some(url.to_string());
+            assert_eq!(problems(&config), Vec::<String>::new(), "{}", url);
+        }
+    }
+}
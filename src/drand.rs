@@ -0,0 +1,391 @@
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use bls12_381::{G1Affine, G1Projective, G2Affine, G2Projective, pairing};
+use bls12_381::hash_to_curve::{HashToCurve, ExpandMsgXmd};
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use strum::{Display, EnumString};
+
+use crate::utils::HexStringInvalid;
+
+// drand compatibility, specified in `docs/drand.md`. Rounds are verified against the chain's
+// BLS12-381 public key with the scheme from its `/info` document:
+//
+//   pedersen-bls-chained     msg = SHA-256(previous_signature || u64_be(round)), signature on G2
+//   pedersen-bls-unchained   msg = SHA-256(u64_be(round)), signature on G2
+//   bls-unchained-g1-rfc9380 msg = SHA-256(u64_be(round)), signature on G1
+//
+// and randomness = SHA-256(signature). A verified round can be mixed into VRF alpha so a draw
+// is anchored to the external beacon.
+
+pub const DST_G2: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+pub const DST_G1: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+// bls12_381 implements hash-to-curve over the `digest` 0.9 traits.
+type HashToCurveSha256 = ExpandMsgXmd<sha2_09::Sha256>;
+
+#[derive(Debug)]
+pub struct DrandDisabled;
+
+#[derive(Debug)]
+pub struct DrandInvalid;
+
+#[derive(Debug)]
+pub struct ChainInfoInvalid;
+
+#[derive(Debug)]
+pub struct DrandUnavailable;
+
+pub enum DrandInputError {
+    DrandDisabled(DrandDisabled),
+    DrandInvalid(DrandInvalid),
+    DrandUnavailable(DrandUnavailable),
+    HexStringInvalid(HexStringInvalid),
+}
+
+pub enum DrandError {
+    ChainInfoInvalid(ChainInfoInvalid),
+    DrandInvalid(DrandInvalid),
+    DrandUnavailable(DrandUnavailable),
+    Io(std::io::Error),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize)]
+pub enum Scheme {
+    #[default]
+    #[strum(serialize = "pedersen-bls-chained")]
+    #[serde(rename = "pedersen-bls-chained")]
+    PedersenBlsChained,
+    #[strum(serialize = "pedersen-bls-unchained")]
+    #[serde(rename = "pedersen-bls-unchained")]
+    PedersenBlsUnchained,
+    #[strum(serialize = "bls-unchained-g1-rfc9380")]
+    #[serde(rename = "bls-unchained-g1-rfc9380")]
+    BlsUnchainedG1,
+}
+
+// The `/info` document of a drand chain; other fields are ignored.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ChainInfo {
+    pub public_key  : String, // Hex, compressed G1 (G2 for `bls-unchained-g1-rfc9380`).
+    pub period      : u64,
+    pub genesis_time: i64,
+    pub hash        : String,
+    #[serde(rename = "schemeID", default)]
+    pub scheme      : Scheme,
+}
+
+// A round as served by `/public/{round}`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DrandRound {
+    pub round             : u64,
+    pub randomness        : String,
+    pub signature         : String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_signature: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AlphaInputs {
+    #[serde(default)]
+    drand: Option<DrandRound>, // Verified as given; fetched from `DRAND_URL` otherwise.
+    #[serde(default)]
+    round: Option<u64>, // Round to fetch, latest when omitted.
+    #[serde(default)]
+    data: String, // Hex string, e.g. a Merkle commitment alpha.
+}
+
+#[derive(Serialize)]
+pub struct VerifyOutput {
+    pub round     : u64,
+    pub randomness: String,
+}
+
+#[derive(Serialize)]
+pub struct AlphaOutput {
+    pub round     : u64,
+    pub randomness: String,
+    pub alpha     : String, // SHA-256(randomness || u64_be(round) || data), to be passed to the VRF.
+}
+
+enum PublicKey {
+    G1(G1Affine),
+    G2(G2Affine),
+}
+
+pub struct Verifier {
+    info: ChainInfo,
+    pk  : PublicKey,
+}
+
+fn decode_array<const N: usize>(hex_string: &str) -> Option<[u8; N]> {
+    hex::decode(hex_string).ok()?.try_into().ok()
+}
+
+pub fn round_message(scheme: Scheme, round: u64, previous_signature: Option<&[u8]>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    if scheme == Scheme::PedersenBlsChained {
+        hasher.update(previous_signature.unwrap_or_default());
+    }
+    hasher.update(round.to_be_bytes());
+    hasher.finalize().into()
+}
+
+impl Verifier {
+    pub fn new(info: ChainInfo) -> Result<Verifier, ChainInfoInvalid> {
+        let pk = match info.scheme {
+            Scheme::PedersenBlsChained | Scheme::PedersenBlsUnchained => {
+                let bytes = decode_array::<48>(&info.public_key).ok_or(ChainInfoInvalid)?;
+                PublicKey::G1(Option::from(G1Affine::from_compressed(&bytes)).ok_or(ChainInfoInvalid)?)
+            },
+            Scheme::BlsUnchainedG1 => {
+                let bytes = decode_array::<96>(&info.public_key).ok_or(ChainInfoInvalid)?;
+                PublicKey::G2(Option::from(G2Affine::from_compressed(&bytes)).ok_or(ChainInfoInvalid)?)
+            },
+        };
+        Ok(Verifier{ info, pk })
+    }
+
+    pub fn info(&self) -> &ChainInfo {
+        &self.info
+    }
+
+    // Returns the round's randomness once its signature verifies.
+    pub fn verify(&self, round: &DrandRound) -> Result<[u8; 32], DrandInvalid> {
+        let signature = hex::decode(&round.signature).map_err(|_err| DrandInvalid)?;
+        let previous_signature = match (&round.previous_signature, self.info.scheme) {
+            (Some(previous_signature), Scheme::PedersenBlsChained) => Some(hex::decode(previous_signature).map_err(|_err| DrandInvalid)?),
+            (None, Scheme::PedersenBlsChained) => return Err(DrandInvalid),
+            _ => None,
+        };
+        let message = round_message(self.info.scheme, round.round, previous_signature.as_deref());
+
+        let valid = match &self.pk {
+            PublicKey::G1(pk) => {
+                let signature: G2Affine = signature.as_slice().try_into().ok()
+                    .and_then(|bytes| Option::from(G2Affine::from_compressed(bytes)))
+                    .ok_or(DrandInvalid)?;
+                let hashed = <G2Projective as HashToCurve<HashToCurveSha256>>::hash_to_curve(message, DST_G2);
+                pairing(&G1Affine::generator(), &signature) == pairing(pk, &G2Affine::from(hashed))
+            },
+            PublicKey::G2(pk) => {
+                let signature: G1Affine = signature.as_slice().try_into().ok()
+                    .and_then(|bytes| Option::from(G1Affine::from_compressed(bytes)))
+                    .ok_or(DrandInvalid)?;
+                let hashed = <G1Projective as HashToCurve<HashToCurveSha256>>::hash_to_curve(message, DST_G1);
+                pairing(&signature, &G2Affine::generator()) == pairing(&G1Affine::from(hashed), pk)
+            },
+        };
+        let randomness: [u8; 32] = Sha256::digest(&signature).into();
+        if !valid || decode_array::<32>(&round.randomness) != Some(randomness) {
+            return Err(DrandInvalid);
+        }
+        Ok(randomness)
+    }
+}
+
+pub fn mix_alpha(round: u64, randomness: &[u8; 32], data: &[u8]) -> [u8; 32] {
+    Sha256::new().chain_update(randomness).chain_update(round.to_be_bytes()).chain_update(data).finalize().into()
+}
+
+pub fn read_info(path: &Path) -> Result<ChainInfo, DrandError> {
+    let json = std::fs::read_to_string(path).map_err(DrandError::Io)?;
+    serde_json::from_str(&json).map_err(|_err| DrandError::ChainInfoInvalid(ChainInfoInvalid))
+}
+
+pub fn read_round(path: &Path) -> Result<DrandRound, DrandError> {
+    let json = std::fs::read_to_string(path).map_err(DrandError::Io)?;
+    serde_json::from_str(&json).map_err(|_err| DrandError::DrandInvalid(DrandInvalid))
+}
+
+// A relay that stops answering fails the request instead of holding a blocking thread.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+static AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
+    ureq::AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT).timeout_read(READ_TIMEOUT).build()
+});
+
+// `GET {url}/info` of a drand HTTP(S) endpoint, e.g. `https://api.drand.sh` or a local relay.
+pub fn fetch_info(url: &str) -> Result<ChainInfo, DrandError> {
+    AGENT.get(&format!("{}/info", url.trim_end_matches('/')))
+        .call()
+        .map_err(|_err| DrandError::DrandUnavailable(DrandUnavailable))?
+        .into_json()
+        .map_err(|_err| DrandError::ChainInfoInvalid(ChainInfoInvalid))
+}
+
+// `GET {url}/public/{round}`, or `/public/latest` without a round.
+pub fn fetch_round(url: &str, round: Option<u64>) -> Result<DrandRound, DrandError> {
+    let round = round.map_or("latest".to_string(), |round| round.to_string());
+    AGENT.get(&format!("{}/public/{}", url.trim_end_matches('/'), round))
+        .call()
+        .map_err(|_err| DrandError::DrandUnavailable(DrandUnavailable))?
+        .into_json()
+        .map_err(|_err| DrandError::DrandInvalid(DrandInvalid))
+}
+
+// The configured chain: `DRAND_CHAIN_INFO` (file) and/or `DRAND_URL` for fetching rounds.
+pub struct Drand {
+    verifier: Verifier,
+    url     : Option<String>,
+}
+
+impl Drand {
+    pub fn new(info: ChainInfo, url: Option<String>) -> Result<Drand, ChainInfoInvalid> {
+        Ok(Drand{ verifier: Verifier::new(info)?, url })
+    }
+
+    pub fn verifier(&self) -> &Verifier {
+        &self.verifier
+    }
+}
+
+// Api endpoints.
+pub fn api_verify(drand: Option<&Drand>, round: DrandRound) -> Result<VerifyOutput, DrandInputError> {
+    let drand = drand.ok_or(DrandInputError::DrandDisabled(DrandDisabled))?;
+    match drand.verifier.verify(&round) {
+        Ok(randomness) => Ok(VerifyOutput{ round: round.round, randomness: hex::encode(randomness) }),
+        Err(err) => Err(DrandInputError::DrandInvalid(err))
+    }
+}
+
+pub fn api_alpha(drand: Option<&Drand>, alpha_inputs: AlphaInputs) -> Result<AlphaOutput, DrandInputError> {
+    let drand = drand.ok_or(DrandInputError::DrandDisabled(DrandDisabled))?;
+    let data = hex::decode(&alpha_inputs.data).map_err(|_err| DrandInputError::HexStringInvalid(HexStringInvalid))?;
+    let round = match (alpha_inputs.drand, &drand.url) {
+        (Some(round), _) => round,
+        (None, Some(url)) => fetch_round(url, alpha_inputs.round).map_err(|err| match err {
+            DrandError::DrandInvalid(err) => DrandInputError::DrandInvalid(err),
+            _ => DrandInputError::DrandUnavailable(DrandUnavailable),
+        })?,
+        (None, None) => return Err(DrandInputError::DrandDisabled(DrandDisabled)),
+    };
+    if alpha_inputs.round.is_some_and(|expected| expected != round.round) {
+        return Err(DrandInputError::DrandInvalid(DrandInvalid));
+    }
+    match drand.verifier.verify(&round) {
+        Ok(randomness) => Ok(AlphaOutput {
+            round     : round.round,
+            randomness: hex::encode(randomness),
+            alpha     : hex::encode(mix_alpha(round.round, &randomness, &data)),
+        }),
+        Err(err) => Err(DrandInputError::DrandInvalid(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // https://api.drand.sh/info
+    const MAINNET_INFO: &str = r#"{
+        "public_key": "868f005eb8e6e4ca0a47c8a77ceaa5309a47978a7c71bc5cce96366b5d7a569937c529eeda66c7293784a9402801af31",
+        "period": 30,
+        "genesis_time": 1595431050,
+        "hash": "8990e7a9aaed2ffed73dbd7092123d6f289930540d7651336225dc172e51b2ce",
+        "schemeID": "pedersen-bls-chained",
+        "metadata": {"beaconID": "default"}
+    }"#;
+
+    // https://api.drand.sh/public/72785
+    const MAINNET_ROUND: &str = r#"{
+        "round": 72785,
+        "randomness": "8b676484b5fb1f37f9ec5c413d7d29883504e5b669f604a1ce68b3388e9ae3d9",
+        "signature": "82f5d3d2de4db19d40a6980e8aa37842a0e55d1df06bd68bddc8d60002e8e959eb9cfa368b3c1b77d18f02a54fe047b80f0989315f83b12a74fd8679c4f12aae86eaf6ab5690b34f1fddd50ee3cc6f6cdf59e95526d5a5d82aaa84fa6f181e42",
+        "previous_signature": "a609e19a03c2fcc559e8dae14900aaefe517cb55c840f6e69bc8e4f66c8d18e8a609685d9917efbfb0c37f058c2de88f13d297c7e19e0ab24813079efe57a182554ff054c7638153f9b26a60e7111f71a0ff63d9571704905d3ca6df0b031747"
+    }"#;
+
+    // https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/info
+    const QUICKNET_INFO: &str = r#"{
+        "public_key": "83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a",
+        "period": 3,
+        "genesis_time": 1692803367,
+        "hash": "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971",
+        "schemeID": "bls-unchained-g1-rfc9380",
+        "metadata": {"beaconID": "quicknet"}
+    }"#;
+
+    // https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971/public/123
+    const QUICKNET_ROUND: &str = r#"{
+        "round": 123,
+        "randomness": "fb8f7bc29bf24db51871ec8c79f3a1e4bd0557bc0dfcee9ed1d924e69d1c60dc",
+        "signature": "b75c69d0b72a5d906e854e808ba7e2accb1542ac355ae486d591aa9d43765482e26cd02df835d3546d23c4b13e0dfc92"
+    }"#;
+
+    fn verifier(info: &str) -> Verifier {
+        Verifier::new(serde_json::from_str(info).unwrap()).unwrap()
+    }
+
+    fn round(round: &str) -> DrandRound {
+        serde_json::from_str(round).unwrap()
+    }
+
+    fn flip_bit(hex_string: &str, bit: usize) -> String {
+        let mut bytes = hex::decode(hex_string).unwrap();
+        bytes[bit / 8] ^= 1 << (bit % 8);
+        hex::encode(bytes)
+    }
+
+    fn assert_rejected(verifier: &Verifier, valid: &DrandRound) {
+        // A flipped bit either leaves the point encoding or breaks the pairing.
+        for bit in [0, 100, 8 * hex::decode(&valid.signature).unwrap().len() - 1] {
+            let mut round = valid.clone();
+            round.signature = flip_bit(&valid.signature, bit);
+            assert!(verifier.verify(&round).is_err());
+        }
+
+        let mut round = valid.clone();
+        round.round += 1;
+        assert!(verifier.verify(&round).is_err());
+
+        let mut round = valid.clone();
+        round.randomness = flip_bit(&valid.randomness, 0);
+        assert!(verifier.verify(&round).is_err());
+    }
+
+    #[test]
+    fn verifies_mainnet_chained_round() {
+        let verifier = verifier(MAINNET_INFO);
+        assert_eq!(verifier.info().scheme, Scheme::PedersenBlsChained);
+        let valid = round(MAINNET_ROUND);
+        assert_eq!(hex::encode(verifier.verify(&valid).unwrap()), valid.randomness);
+        assert_rejected(&verifier, &valid);
+
+        // The chained message commits to the previous signature.
+        let mut round = valid.clone();
+        round.previous_signature = None;
+        assert!(verifier.verify(&round).is_err());
+        let mut round = valid.clone();
+        round.previous_signature = Some(flip_bit(valid.previous_signature.as_deref().unwrap(), 0));
+        assert!(verifier.verify(&round).is_err());
+    }
+
+    #[test]
+    fn verifies_quicknet_unchained_round() {
+        let verifier = verifier(QUICKNET_INFO);
+        assert_eq!(verifier.info().scheme, Scheme::BlsUnchainedG1);
+        let valid = round(QUICKNET_ROUND);
+        assert_eq!(hex::encode(verifier.verify(&valid).unwrap()), valid.randomness);
+        assert_rejected(&verifier, &valid);
+    }
+
+    // A genuine signature from another round, with its own randomness, still fails the pairing.
+    #[test]
+    fn rejects_signature_of_another_round() {
+        let verifier = verifier(MAINNET_INFO);
+        let mut round = round(MAINNET_ROUND);
+        round.signature = "8d61d9100567de44682506aea1a7a6fa6e5491cd27a0a0ed349ef6910ac5ac20ff7bc3e09d7c046566c9f7f3c6f3b10104990e7cb424998203d8f7de586fb7fa5f60045417a432684f85093b06ca91c769f0e7ca19268375e659c2a2352b4655".to_string();
+        round.randomness = "101297f1ca7dc44ef6088d94ad5fb7ba03455dc33d53ddb412bbc4564ed986ec".to_string();
+        assert!(verifier.verify(&round).is_err());
+    }
+
+    #[test]
+    fn rejects_public_key_of_the_wrong_group() {
+        let mut info: ChainInfo = serde_json::from_str(QUICKNET_INFO).unwrap();
+        info.scheme = Scheme::PedersenBlsChained;
+        assert!(Verifier::new(info).is_err());
+    }
+}
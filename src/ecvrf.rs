@@ -1,9 +1,9 @@
 use serde::Serialize;
 use serde::Deserialize;
-use rand_core::{OsRng};
+use utoipa::ToSchema;
 use vrf_r255::{PublicKey, SecretKey, Proof};
 
-use crate::utils;
+use crate::entropy::{self, EntropyFailed, HealthTestedRng};
 
 macro_rules! to_string {
     ($e:expr) => {
@@ -11,16 +11,6 @@ macro_rules! to_string {
     };
 }
 
-macro_rules! from_string {
-    (VRFPublicKey, $e:expr) => {{
-        let v: &[u8] = &::hex::decode($e).unwrap();
-        VRFPublicKey::try_from(v).unwrap()
-    }};
-    ($t:ty, $e:expr) => {
-        <$t>::try_from(::hex::decode($e).unwrap().as_ref()).unwrap()
-    };
-}
-
 #[derive(Debug)]
 pub struct AlphaInvalid;
 
@@ -48,35 +38,37 @@ pub enum VRFVerifyInputError {
     VRFVerifyFailed(VRFVerifyFailed),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct VRFInputs {
     sk   : String,
     alpha: String
 }
 
-#[derive(Serialize)]
+#[allow(non_snake_case)]
+#[derive(Serialize, ToSchema)]
 pub struct VRFOutput {
     pub Gamma: String,
     pub c    : String,
     pub s    : String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SKOutput {
     pub sk: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct PKInputs {
     sk: String
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PKOutput {
     pub pk: String,
 }
 
-#[derive(Deserialize)]
+#[allow(non_snake_case)]
+#[derive(Deserialize, ToSchema)]
 pub struct VRFVerifyInputs {
     pk   : String,
     alpha: String,
@@ -85,21 +77,21 @@ pub struct VRFVerifyInputs {
     s    : String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct VRFVerifyOutput {
     pub beta: String
 }
 
-fn decode_sk(sk_string: String) -> Result<SecretKey, SKInvalid> {
+pub fn decode_sk(sk_string: String) -> Result<SecretKey, SKInvalid> {
     match hex::decode(sk_string) {
         Ok(sk_vec) => {
             match sk_vec.try_into() {
                 Ok(sk_bytes) => {
                     let sk = SecretKey::from_bytes(sk_bytes);
                     if sk.is_some().into() {
-                        return Ok(sk.unwrap())
+                        Ok(sk.unwrap())
                     } else {
-                        return Err(SKInvalid)
+                        Err(SKInvalid)
                     }
                 },
                 Err(_err) => Err(SKInvalid)
@@ -114,11 +106,9 @@ fn decode_pk(pk_string: String) -> Result<PublicKey, PKInvalid> {
         Ok(pk_vec) => {
             match pk_vec.try_into() {
                 Ok(pk_bytes) => {
-                    let pk = PublicKey::from_bytes(pk_bytes);
-                    if pk.is_some().into() {
-                        return Ok(pk.unwrap())
-                    } else {
-                        return Err(PKInvalid)
+                    match PublicKey::from_bytes(pk_bytes) {
+                        Some(pk) => Ok(pk),
+                        None => Err(PKInvalid)
                     }
                 },
                 Err(_err) => Err(PKInvalid)
@@ -133,11 +123,9 @@ fn decode_pi(pi_string: String) -> Result<Proof, PiInvalid> {
         Ok(pi_vec) => {
             match pi_vec.try_into() {
                 Ok(pi_bytes) => {
-                    let pi = Proof::from_bytes(pi_bytes);
-                    if pi.is_some().into() {
-                        return Ok(pi.unwrap())
-                    } else {
-                        return Err(PiInvalid)
+                    match Proof::from_bytes(pi_bytes) {
+                        Some(pi) => Ok(pi),
+                        None => Err(PiInvalid)
                     }
                 },
                 Err(_err) => Err(PiInvalid)
@@ -147,20 +135,22 @@ fn decode_pi(pi_string: String) -> Result<Proof, PiInvalid> {
     }
 }
 
+pub fn prove(sk: &SecretKey, alpha: &[u8]) -> VRFOutput {
+    let pi: Proof = sk.prove(alpha);
+    let pi_string: String = to_string!(pi);
+
+    VRFOutput {
+        Gamma: pi_string[..64].to_string(),
+        c    : pi_string[64..96].to_string(),
+        s    : pi_string[96..].to_string(),
+    }
+}
+
 pub fn api_vrf_prove(vrf_inputs: VRFInputs) -> Result<VRFOutput, VRFInputError> {
     match decode_sk(vrf_inputs.sk) {
         Ok(sk) => {
             match hex::decode(vrf_inputs.alpha) {
-                Ok(alpha) => {
-                    let pi: Proof = sk.prove(&alpha);
-                    let pi_string: String = to_string!(pi);
-
-                    Ok(VRFOutput {
-                        Gamma: pi_string[..64].to_string(),
-                        c    : pi_string[64..96].to_string(),
-                        s    : pi_string[96..].to_string(),
-                    })
-                },
+                Ok(alpha) => Ok(prove(&sk, &alpha)),
                 Err(_err) => Err(VRFInputError::AlphaInvalid(AlphaInvalid))
             }
         },
@@ -168,18 +158,22 @@ pub fn api_vrf_prove(vrf_inputs: VRFInputs) -> Result<VRFOutput, VRFInputError>
     }
 }
 
-pub fn generate_sk() -> String {
-    let sk: SecretKey = SecretKey::generate(OsRng);
-    return to_string!(sk);
+// Refuses to return a key unless the entropy source passed its startup tests and kept passing
+// the continuous tests while the key was drawn.
+pub fn generate_sk() -> Result<String, EntropyFailed> {
+    entropy::startup()?;
+    let sk: SecretKey = SecretKey::generate(HealthTestedRng);
+    entropy::check()?;
+    Ok(to_string!(sk))
 }
 
-pub fn api_generate_sk() -> SKOutput {
-    return SKOutput{ sk: generate_sk() };
+pub fn api_generate_sk() -> Result<SKOutput, EntropyFailed> {
+    Ok(SKOutput{ sk: generate_sk()? })
 }
 
 pub fn get_pk(sk: SecretKey) -> String {
     let pk: PublicKey = PublicKey::from(sk);
-    return to_string!(pk);
+    to_string!(pk)
 }
 
 pub fn api_get_pk(pk_inputs: PKInputs) -> Result<PKOutput, VRFInputError> {
@@ -189,21 +183,19 @@ pub fn api_get_pk(pk_inputs: PKInputs) -> Result<PKOutput, VRFInputError> {
     }
 }
 
-// Hex.
-pub fn api_vrf_verify(vrf_verify_inputs: VRFVerifyInputs) -> Result<VRFVerifyOutput, VRFVerifyInputError> {
-    match decode_pk(vrf_verify_inputs.pk) { // -> vrf_r255::PublicKey
+// Hex. `pi_string` is Gamma || c || s; returns beta.
+pub fn verify(pk_string: String, alpha_string: String, pi_string: String) -> Result<String, VRFVerifyInputError> {
+    match decode_pk(pk_string) { // -> vrf_r255::PublicKey
         Ok(pk) => {
-            match hex::decode(vrf_verify_inputs.alpha) {
+            match hex::decode(alpha_string) {
                 Ok(alpha) => {
-                    let pi_string: String = vrf_verify_inputs.Gamma + &vrf_verify_inputs.c + &vrf_verify_inputs.s;
-
                     match decode_pi(pi_string) { // -> vrf_r255::Proof
                         Ok(pi) => {
                             let beta = pk.verify(&alpha, &pi);
                             if beta.is_some().into() {
-                                return Ok(VRFVerifyOutput{ beta: hex::encode(beta.unwrap()) })
+                                Ok(hex::encode(beta.unwrap()))
                             } else {
-                                return Err(VRFVerifyInputError::VRFVerifyFailed(VRFVerifyFailed))
+                                Err(VRFVerifyInputError::VRFVerifyFailed(VRFVerifyFailed))
                             }
                         },
                         Err(_err) => Err(VRFVerifyInputError::PiInvalid(PiInvalid))
@@ -215,3 +207,9 @@ pub fn api_vrf_verify(vrf_verify_inputs: VRFVerifyInputs) -> Result<VRFVerifyOut
         Err(_err) => Err(VRFVerifyInputError::PKInvalid(PKInvalid))
     }
 }
+
+// Hex.
+pub fn api_vrf_verify(vrf_verify_inputs: VRFVerifyInputs) -> Result<VRFVerifyOutput, VRFVerifyInputError> {
+    let pi_string: String = vrf_verify_inputs.Gamma + &vrf_verify_inputs.c + &vrf_verify_inputs.s;
+    Ok(VRFVerifyOutput{ beta: verify(vrf_verify_inputs.pk, vrf_verify_inputs.alpha, pi_string)? })
+}
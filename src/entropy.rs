@@ -0,0 +1,264 @@
+use std::sync::Mutex;
+
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::Serialize;
+
+// NIST SP 800-90B (section 4.4) health tests on the OS entropy source used for key generation.
+// Every byte drawn through `HealthTestedRng` is one 8-bit sample for both tests:
+//
+// - Repetition count: fails when one value repeats `REPETITION_COUNT_CUTOFF` times in a row,
+//   C = 1 + ceil(-log2(alpha) / H).
+// - Adaptive proportion: fails when the first value of a `ADAPTIVE_PROPORTION_WINDOW`-sample
+//   window occurs `ADAPTIVE_PROPORTION_CUTOFF` times in it, C = 1 + CRITBINOM(W, 2^-H, 1 - alpha).
+//
+// Both cutoffs use the claimed min-entropy H = 8 bits per sample (the OS source is a full-entropy
+// CSPRNG) and a false positive probability alpha = 2^-30. `startup` runs both tests over
+// `STARTUP_SAMPLES` samples before the first key is generated. A failure is permanent for the
+// process: no further keys are issued until the source is fixed and the service restarted.
+
+pub const REPETITION_COUNT_CUTOFF: u32 = 5;
+pub const ADAPTIVE_PROPORTION_WINDOW: u32 = 512;
+pub const ADAPTIVE_PROPORTION_CUTOFF: u32 = 16;
+pub const STARTUP_SAMPLES: usize = 1024;
+
+#[derive(Debug)]
+pub struct EntropyFailed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Untested,
+    Ok,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthTest {
+    RepetitionCount,
+    AdaptiveProportion,
+}
+
+#[derive(Serialize)]
+pub struct HealthOutput {
+    pub status                    : HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_test               : Option<HealthTest>,
+    pub samples                   : u64,
+    pub repetition_count_cutoff   : u32,
+    pub adaptive_proportion_window: u32,
+    pub adaptive_proportion_cutoff: u32,
+}
+
+struct HealthTests {
+    status     : HealthStatus,
+    failed_test: Option<HealthTest>,
+    samples    : u64,
+    // Repetition count state.
+    last       : u8,
+    repeats    : u32,
+    // Adaptive proportion state.
+    first      : u8,
+    window     : u32,
+    matches    : u32,
+}
+
+static HEALTH: Mutex<HealthTests> = Mutex::new(HealthTests::new());
+
+impl HealthTests {
+    const fn new() -> HealthTests {
+        HealthTests {
+            status     : HealthStatus::Untested,
+            failed_test: None,
+            samples    : 0,
+            last       : 0,
+            repeats    : 0,
+            first      : 0,
+            window     : 0,
+            matches    : 0,
+        }
+    }
+
+    fn fail(&mut self, test: HealthTest) {
+        if self.status != HealthStatus::Failed {
+            log::error!(target: "api", "Entropy source failed the {:?} health test after {} samples", test, self.samples);
+        }
+        self.status = HealthStatus::Failed;
+        self.failed_test.get_or_insert(test);
+    }
+
+    fn sample(&mut self, value: u8) {
+        if self.samples > 0 && value == self.last {
+            self.repeats += 1;
+            if self.repeats >= REPETITION_COUNT_CUTOFF {
+                self.fail(HealthTest::RepetitionCount);
+            }
+        } else {
+            self.last = value;
+            self.repeats = 1;
+        }
+
+        if self.window == 0 {
+            self.first = value;
+            self.matches = 1;
+        } else if value == self.first {
+            self.matches += 1;
+            if self.matches >= ADAPTIVE_PROPORTION_CUTOFF {
+                self.fail(HealthTest::AdaptiveProportion);
+            }
+        }
+        self.window = (self.window + 1) % ADAPTIVE_PROPORTION_WINDOW;
+        self.samples += 1;
+    }
+}
+
+fn health() -> std::sync::MutexGuard<'static, HealthTests> {
+    HEALTH.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Draws from `OsRng` and runs every byte through the continuous health tests. `RngCore` cannot
+// report errors, so callers check `check` after drawing.
+pub struct HealthTestedRng;
+
+impl RngCore for HealthTestedRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest);
+        let mut health = health();
+        for &value in dest.iter() {
+            health.sample(value);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for HealthTestedRng {}
+
+// Startup tests; later calls only report the current status.
+pub fn startup() -> Result<(), EntropyFailed> {
+    if health().status == HealthStatus::Untested {
+        let mut samples = [0u8; STARTUP_SAMPLES];
+        HealthTestedRng.fill_bytes(&mut samples);
+        let mut health = health();
+        if health.status == HealthStatus::Untested {
+            health.status = HealthStatus::Ok;
+        }
+    }
+    check()
+}
+
+pub fn check() -> Result<(), EntropyFailed> {
+    match health().status {
+        HealthStatus::Ok => Ok(()),
+        HealthStatus::Untested | HealthStatus::Failed => Err(EntropyFailed),
+    }
+}
+
+pub fn status() -> HealthOutput {
+    let health = health();
+    HealthOutput {
+        status                    : health.status,
+        failed_test               : health.failed_test,
+        samples                   : health.samples,
+        repetition_count_cutoff   : REPETITION_COUNT_CUTOFF,
+        adaptive_proportion_window: ADAPTIVE_PROPORTION_WINDOW,
+        adaptive_proportion_cutoff: ADAPTIVE_PROPORTION_CUTOFF,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Distinct from its neighbours and never 0, so neither test trips on the filler.
+    fn filler(i: usize) -> u8 {
+        (i % 255) as u8 + 1
+    }
+
+    #[test]
+    fn constant_source_trips_the_repetition_count_test_at_the_cutoff() {
+        let mut health = HealthTests::new();
+        for _ in 1..REPETITION_COUNT_CUTOFF {
+            health.sample(0x42);
+        }
+        assert_ne!(health.status, HealthStatus::Failed);
+        health.sample(0x42);
+        assert_eq!(health.status, HealthStatus::Failed);
+        assert_eq!(health.failed_test, Some(HealthTest::RepetitionCount));
+    }
+
+    #[test]
+    fn cutoff_matches_in_a_window_trip_the_adaptive_proportion_test() {
+        let mut health = HealthTests::new();
+        for i in 0..ADAPTIVE_PROPORTION_CUTOFF as usize - 1 {
+            health.sample(0);
+            health.sample(filler(i));
+        }
+        assert_ne!(health.status, HealthStatus::Failed);
+        health.sample(0);
+        assert_eq!(health.status, HealthStatus::Failed);
+        assert_eq!(health.failed_test, Some(HealthTest::AdaptiveProportion));
+    }
+
+    #[test]
+    fn adaptive_proportion_window_resets() {
+        let mut health = HealthTests::new();
+        // Two windows with one match short of the cutoff each.
+        for _ in 0..2 {
+            let mut window = Vec::with_capacity(ADAPTIVE_PROPORTION_WINDOW as usize);
+            for i in 0..ADAPTIVE_PROPORTION_CUTOFF as usize - 1 {
+                window.push(0);
+                window.push(filler(i));
+            }
+            let i = window.len();
+            window.extend((i..ADAPTIVE_PROPORTION_WINDOW as usize).map(filler));
+            for value in window {
+                health.sample(value);
+            }
+        }
+        assert_eq!(health.samples, 2 * ADAPTIVE_PROPORTION_WINDOW as u64);
+        assert_ne!(health.status, HealthStatus::Failed);
+    }
+
+    #[test]
+    fn failures_are_permanent() {
+        let mut health = HealthTests::new();
+        for _ in 0..REPETITION_COUNT_CUTOFF {
+            health.sample(0x42);
+        }
+        for i in 0..4 * ADAPTIVE_PROPORTION_WINDOW as usize {
+            health.sample(filler(i));
+        }
+        assert_eq!(health.status, HealthStatus::Failed);
+    }
+
+    // The only test that touches the process-wide state: once it fails, key generation keeps
+    // refusing, through later healthy draws and repeated startup tests.
+    #[test]
+    fn failed_source_keeps_refusing_keys() {
+        assert!(crate::ecvrf::generate_sk().is_ok());
+        {
+            let mut health = health();
+            for _ in 0..REPETITION_COUNT_CUTOFF {
+                health.sample(0x42);
+            }
+        }
+        for _ in 0..3 {
+            assert!(crate::ecvrf::generate_sk().is_err());
+            assert!(startup().is_err());
+            HealthTestedRng.fill_bytes(&mut [0u8; STARTUP_SAMPLES]);
+        }
+        assert_eq!(status().status, HealthStatus::Failed);
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Digest};
+use chacha20::ChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+
+use crate::rng::{ChainVersion, SeedChain, SeedInvalid};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Ticket generators, specified in `docs/seed-chain.md`. Each one turns the initial seed
+// into a deterministic stream of 32-byte blocks; tickets are scaled from the blocks the
+// same way for every generator.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize, ToSchema)]
+pub enum GeneratorKind {
+    #[default]
+    #[strum(serialize = "sha512-chain")]
+    #[serde(rename = "sha512-chain")]
+    Sha512Chain,
+    #[strum(serialize = "hmac-drbg-sha256")]
+    #[serde(rename = "hmac-drbg-sha256")]
+    HmacDrbg,
+    #[strum(serialize = "chacha20")]
+    #[serde(rename = "chacha20")]
+    ChaCha20,
+    #[strum(serialize = "blake3-xof")]
+    #[serde(rename = "blake3-xof")]
+    Blake3Xof,
+}
+
+pub struct Block {
+    pub random_result: [u8; 32],
+    // Chain state after this block, for generators whose state is public.
+    pub new_seed     : Option<[u8; 32]>,
+}
+
+pub trait Generator: Send {
+    fn next_block(&mut self) -> Block;
+}
+
+impl Generator for SeedChain {
+    fn next_block(&mut self) -> Block {
+        let step = self.next().expect("the seed chain is infinite");
+        Block{ random_result: step.random_result, new_seed: Some(step.new_seed) }
+    }
+}
+
+// NIST SP 800-90A HMAC_DRBG with HMAC-SHA256, instantiated with the seed as the whole seed
+// material (entropy input and nonce) and no personalization string. The seed must carry the
+// 256-bit security strength, so shorter seeds are rejected. Each block is one 32-byte
+// Generate call without additional input; the DRBG is never reseeded.
+pub const HMAC_DRBG_MIN_SEED_LEN: usize = 32;
+
+pub struct HmacDrbg {
+    k: [u8; 32],
+    v: [u8; 32],
+}
+
+impl HmacDrbg {
+    pub fn new(seed: &[u8]) -> Result<HmacDrbg, SeedInvalid> {
+        if seed.len() < HMAC_DRBG_MIN_SEED_LEN {
+            return Err(SeedInvalid);
+        }
+        let mut drbg = HmacDrbg{ k: [0x00; 32], v: [0x01; 32] };
+        drbg.update(seed);
+        Ok(drbg)
+    }
+
+    // HMAC_DRBG_Generate without additional input.
+    fn generate(&mut self, output: &mut [u8]) {
+        for chunk in output.chunks_mut(32) {
+            self.v = HmacDrbg::hmac(&self.k, &[&self.v]);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+        self.update(&[]);
+    }
+
+    fn hmac(key: &[u8; 32], parts: &[&[u8]]) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize().into_bytes().into()
+    }
+
+    fn update(&mut self, provided_data: &[u8]) {
+        self.k = HmacDrbg::hmac(&self.k, &[&self.v, &[0x00], provided_data]);
+        self.v = HmacDrbg::hmac(&self.k, &[&self.v]);
+        if !provided_data.is_empty() {
+            self.k = HmacDrbg::hmac(&self.k, &[&self.v, &[0x01], provided_data]);
+            self.v = HmacDrbg::hmac(&self.k, &[&self.v]);
+        }
+    }
+}
+
+impl Generator for HmacDrbg {
+    fn next_block(&mut self) -> Block {
+        let mut random_result = [0u8; 32];
+        self.generate(&mut random_result);
+        Block{ random_result, new_seed: None }
+    }
+}
+
+// RFC 8439 ChaCha20 keystream with key = SHA-256(seed), an all-zero nonce and counter 0.
+pub struct ChaCha20Stream {
+    cipher: ChaCha20,
+}
+
+impl ChaCha20Stream {
+    pub fn new(seed: &[u8]) -> ChaCha20Stream {
+        let key: [u8; 32] = Sha256::digest(seed).into();
+        ChaCha20Stream::with_key(&key, &[0u8; 12])
+    }
+
+    fn with_key(key: &[u8; 32], nonce: &[u8; 12]) -> ChaCha20Stream {
+        ChaCha20Stream{ cipher: ChaCha20::new(key.into(), nonce.into()) }
+    }
+}
+
+impl Generator for ChaCha20Stream {
+    fn next_block(&mut self) -> Block {
+        let mut random_result = [0u8; 32];
+        self.cipher.apply_keystream(&mut random_result);
+        Block{ random_result, new_seed: None }
+    }
+}
+
+// BLAKE3 extendable output of the seed, read in consecutive 32-byte blocks.
+pub struct Blake3Xof {
+    reader: blake3::OutputReader,
+}
+
+impl Blake3Xof {
+    pub fn new(seed: &[u8]) -> Blake3Xof {
+        Blake3Xof{ reader: blake3::Hasher::new().update(seed).finalize_xof() }
+    }
+}
+
+impl Generator for Blake3Xof {
+    fn next_block(&mut self) -> Block {
+        let mut random_result = [0u8; 32];
+        self.reader.fill(&mut random_result);
+        Block{ random_result, new_seed: None }
+    }
+}
+
+// `version` only affects the SHA-512 chain; the other generators have a single definition.
+pub fn new_generator(kind: GeneratorKind, seed: &[u8], version: ChainVersion) -> Result<Box<dyn Generator>, SeedInvalid> {
+    match kind {
+        GeneratorKind::Sha512Chain => Ok(Box::new(SeedChain::new(seed, 0, version)?)),
+        GeneratorKind::HmacDrbg => Ok(Box::new(HmacDrbg::new(seed)?)),
+        GeneratorKind::ChaCha20 => Ok(Box::new(ChaCha20Stream::new(seed))),
+        GeneratorKind::Blake3Xof => Ok(Box::new(Blake3Xof::new(seed))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(generator: &mut dyn Generator, count: usize) -> Vec<u8> {
+        (0..count).flat_map(|_| generator.next_block().random_result).collect()
+    }
+
+    // NIST CAVP HMAC_DRBG.rsp, [SHA-256] [PredictionResistance = False], no reseed,
+    // EntropyInputLen = 256, NonceLen = 128, empty personalization and additional input,
+    // COUNT = 0: instantiate with entropy_input || nonce, Generate 1024 bits twice and
+    // compare the second output.
+    #[test]
+    fn hmac_drbg_matches_the_cavp_vector() {
+        let entropy_input = hex::decode("ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488").unwrap();
+        let nonce = hex::decode("659ba96c601dc69fc902940805ec0ca8").unwrap();
+        let mut drbg = HmacDrbg::new(&[entropy_input, nonce].concat()).unwrap();
+
+        let mut returned_bits = [0u8; 128];
+        drbg.generate(&mut returned_bits);
+        drbg.generate(&mut returned_bits);
+        assert_eq!(hex::encode(returned_bits), concat!(
+            "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89",
+            "d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1",
+            "07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668",
+            "961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8",
+        ));
+    }
+
+    #[test]
+    fn hmac_drbg_rejects_short_seeds() {
+        assert!(HmacDrbg::new(&[0u8; HMAC_DRBG_MIN_SEED_LEN - 1]).is_err());
+        assert!(new_generator(GeneratorKind::HmacDrbg, &[0u8; 1], ChainVersion::V1).is_err());
+        assert!(HmacDrbg::new(&[0u8; HMAC_DRBG_MIN_SEED_LEN]).is_ok());
+    }
+
+    // RFC 8439 section 2.4.2: the keystream from block counter 1, i.e. after the first 64 bytes.
+    #[test]
+    fn chacha20_matches_the_rfc_8439_keystream() {
+        let key: [u8; 32] = hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap().try_into().unwrap();
+        let nonce: [u8; 12] = hex::decode("000000000000004a00000000").unwrap().try_into().unwrap();
+        let keystream = blocks(&mut ChaCha20Stream::with_key(&key, &nonce), 6);
+        assert_eq!(hex::encode(&keystream[64..64 + 114]), concat!(
+            "224f51f3401bd9e12fde276fb8631ded8c131f823d2c06e27e4fcaec9ef3cf78",
+            "8a3b0aa372600a92b57974cded2b9334794cba40c63e34cdea212c4cf07d41b7",
+            "69a6749f3f630f4122cafe28ec4dc47e26d4346d70b98c73f3e9c53ac40c5945",
+            "398b6eda1a832c89c167eacd901d7e2bf363",
+        ));
+    }
+
+    // BLAKE3 test_vectors.json, input_len = 1024 (input byte i is i % 251), unkeyed `hash`;
+    // the vector has 131 bytes of extended output, of which the first four blocks are compared.
+    #[test]
+    fn blake3_xof_matches_the_official_vector() {
+        let input: Vec<u8> = (0..1024).map(|i| (i % 251) as u8).collect();
+        let output = blocks(&mut Blake3Xof::new(&input), 4);
+        assert_eq!(hex::encode(output), concat!(
+            "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7",
+            "1cf8107265ecdaf8505b95d8fcec83a98a6a96ea5109d2c179c47a387ffbb404",
+            "756f6eeae7883b446b70ebb144527c2075ab8ab204c0086bb22b7c93d465efc5",
+            "7f8d917f0b385c6df265e77003b85102967486ed57db5c5ca170ba441427ed9a",
+        ));
+    }
+}
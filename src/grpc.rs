@@ -0,0 +1,519 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::Stream;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+use tonic::{Request, Response, Status};
+use tonic::transport::server::Connected;
+
+use crate::{auth, ecvrf, keystore, metrics, ratelimit, rng, v2};
+use crate::config::{LimitsConfig, RouteGroup, RoutesConfig};
+use crate::generator::GeneratorKind;
+use crate::rng::ChainVersion;
+use crate::tls::{ClientCert, PeerAddr};
+
+// gRPC service `truerandom.v1.TrueRandom` (`proto/true_random.proto`) on its own port, next to
+// the warp server. It calls the same `ecvrf`, `keystore` and `rng` functions as the HTTP
+// handlers and shares their config: the TLS acceptor (and so certificate reloads and mTLS),
+// API keys and JWTs from `x-api-key` and `authorization` metadata, rate-limit buckets, limits
+// and route groups. A disabled group answers `UNIMPLEMENTED`.
+
+pub mod pb {
+    tonic::include_proto!("truerandom.v1");
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("true_random_descriptor");
+}
+
+// Connection details, available to every call as a request extension.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    pub peer       : PeerAddr,
+    pub client_cert: Option<ClientCert>,
+}
+
+enum Io {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+pub struct Connection {
+    io  : Io,
+    info: ConnectionInfo,
+}
+
+impl Connected for Connection {
+    type ConnectInfo = ConnectionInfo;
+
+    fn connect_info(&self) -> ConnectionInfo {
+        self.info.clone()
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Io::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Io::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Io::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match &mut self.get_mut().io {
+            Io::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Io::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+// Accepted connections, after their TLS handshake when there is an acceptor. Handshakes run
+// in their own tasks, so a slow client does not hold up the others.
+fn incoming(listener: TcpListener, acceptor: Option<TlsAcceptor>) -> impl Stream<Item = std::io::Result<Connection>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Connection>(64);
+    tokio::spawn(async move {
+        loop {
+            let (stream, remote_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    log::warn!(target: "api", "Connection could not be accepted: {}", err);
+                    continue;
+                }
+            };
+            if sender.is_closed() {
+                return;
+            }
+            let _ = stream.set_nodelay(true);
+            let (acceptor, sender) = (acceptor.clone(), sender.clone());
+            tokio::spawn(async move {
+                let connection = match acceptor {
+                    None => Connection{ io: Io::Plain(stream), info: ConnectionInfo{ peer: PeerAddr(remote_addr), client_cert: None } },
+                    Some(acceptor) => {
+                        let stream = match acceptor.accept(stream).await {
+                            Ok(stream) => stream,
+                            Err(err) => {
+                                log::debug!(target: "api", "TLS handshake with {} failed: {}", remote_addr, err);
+                                return;
+                            }
+                        };
+                        let client_cert = stream.get_ref().1.peer_certificates()
+                            .and_then(|certs| certs.first())
+                            .map(ClientCert::new);
+                        Connection{ io: Io::Tls(Box::new(stream)), info: ConnectionInfo{ peer: PeerAddr(remote_addr), client_cert } }
+                    },
+                };
+                let _ = sender.send(connection).await;
+            });
+        }
+    });
+    futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|connection| (Ok(connection), receiver))
+    })
+}
+
+pub struct GrpcService {
+    keyring      : Arc<keystore::Keyring>,
+    authenticator: Arc<auth::Authenticator>,
+    rate_limiter : Arc<ratelimit::RateLimiter>,
+    routes       : RoutesConfig,
+    limits       : LimitsConfig,
+    client_auth  : bool, // Prove and key calls require a verified client certificate.
+}
+
+impl GrpcService {
+    pub fn new(
+        keyring: Arc<keystore::Keyring>,
+        authenticator: Arc<auth::Authenticator>,
+        rate_limiter: Arc<ratelimit::RateLimiter>,
+        routes: RoutesConfig,
+        limits: LimitsConfig,
+        client_auth: bool
+    ) -> GrpcService {
+        GrpcService{ keyring, authenticator, rate_limiter, routes, limits, client_auth }
+    }
+
+    // The checks of the HTTP routes, in the same order: route group, client certificate,
+    // credentials and scope, then a token from `budget`.
+    fn access<T>(&self, request: &Request<T>, group: RouteGroup, client_cert: bool, scope: auth::Scope, budget: Option<ratelimit::Budget>) -> Result<(), Status> {
+        if !self.routes.enabled(group) {
+            return Err(Status::unimplemented("Method is disabled."));
+        }
+        let connection = request.extensions().get::<ConnectionInfo>();
+        if client_cert && self.client_auth && connection.is_none_or(|connection| connection.client_cert.is_none()) {
+            return Err(Status::permission_denied("A client certificate from the configured CA is required."));
+        }
+        let metadata = |name: &str| request.metadata().get(name).and_then(|value| value.to_str().ok());
+        let principal = match self.authenticator.authorize(metadata("x-api-key"), metadata("authorization"), scope) {
+            Ok(principal) => principal,
+            Err(auth::AuthInputError::CredentialsInvalid(_)) => return Err(Status::unauthenticated("Credentials are missing or invalid.")),
+            Err(auth::AuthInputError::ScopeMissing(_)) => return Err(Status::permission_denied("Credentials do not grant access to this method.")),
+        };
+        metrics::observe_principal(principal.as_ref().map(|principal| principal.id.as_str()));
+        if let Some(budget) = budget {
+            let remote = connection.map(|connection| connection.peer.0.ip());
+            let client = self.rate_limiter.client(principal.as_ref(), remote, metadata("x-forwarded-for"));
+            if let Err(rate_limited) = self.rate_limiter.check(budget, &client, std::time::Instant::now()) {
+                let mut status = Status::resource_exhausted("Rate limit exceeded.");
+                status.metadata_mut().insert("retry-after", rate_limited.retry_after_secs.into());
+                return Err(status);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Hex produced by `ecvrf` and `rng` back to bytes.
+fn decode(hex_string: &str) -> Vec<u8> {
+    hex::decode(hex_string).expect("ecvrf and rng return valid hex")
+}
+
+fn proof(vrf_output: ecvrf::VRFOutput) -> pb::Proof {
+    let pi = decode(&format!("{}{}{}", vrf_output.Gamma, vrf_output.c, vrf_output.s));
+    pb::Proof{ gamma: decode(&vrf_output.Gamma), c: decode(&vrf_output.c), s: decode(&vrf_output.s), pi }
+}
+
+fn chain_version(chain_version: pb::ChainVersion) -> ChainVersion {
+    match chain_version {
+        pb::ChainVersion::Unspecified => ChainVersion::default(),
+        pb::ChainVersion::V1 => ChainVersion::V1,
+        pb::ChainVersion::V2 => ChainVersion::V2,
+    }
+}
+
+fn generator(generator: pb::Generator) -> GeneratorKind {
+    match generator {
+        pb::Generator::Unspecified => GeneratorKind::default(),
+        pb::Generator::Sha512Chain => GeneratorKind::Sha512Chain,
+        pb::Generator::HmacDrbgSha256 => GeneratorKind::HmacDrbg,
+        pb::Generator::Chacha20 => GeneratorKind::ChaCha20,
+        pb::Generator::Blake3Xof => GeneratorKind::Blake3Xof,
+    }
+}
+
+fn ticket(index: usize, rng_result: rng::RngResult) -> pb::Ticket {
+    pb::Ticket {
+        index        : index as u64,
+        ticket       : rng_result.ticket_number,
+        random_result: decode(&rng_result.random_result),
+        new_seed     : rng_result.new_seed.as_deref().map(decode),
+    }
+}
+
+fn rng_status(err: rng::RngInputError) -> Status {
+    match err {
+        rng::RngInputError::SeedInvalid(_) => Status::invalid_argument("Seed is invalid."),
+        rng::RngInputError::IterationsExceeded(_) => Status::invalid_argument("Iterations exceed maximum allowed limit."),
+        rng::RngInputError::DistributionInvalid(_) => Status::invalid_argument("Distribution is invalid."),
+    }
+}
+
+// Counts above `usize::MAX` cannot be below any limit.
+fn count(value: u64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+type TicketStream = Pin<Box<dyn Stream<Item = Result<pb::Ticket, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl pb::true_random_server::TrueRandom for GrpcService {
+    type StreamTicketsStream = TicketStream;
+
+    async fn prove(&self, request: Request<pb::ProveRequest>) -> Result<Response<pb::Proof>, Status> {
+        self.access(&request, RouteGroup::Vrf, true, auth::Scope::VrfProve, Some(ratelimit::Budget::Prove))?;
+        let prove_request = request.into_inner();
+        let sk = ecvrf::decode_sk(hex::encode(prove_request.sk)).map_err(|_err| Status::invalid_argument("VRF secret key is invalid."))?;
+        let vrf_output = metrics::VRF_PROVE_DURATION.observe_closure_duration(|| ecvrf::prove(&sk, &prove_request.alpha));
+        Ok(Response::new(proof(vrf_output)))
+    }
+
+    async fn verify(&self, request: Request<pb::VerifyRequest>) -> Result<Response<pb::VerifyResponse>, Status> {
+        self.access(&request, RouteGroup::Vrf, false, auth::Scope::VrfVerify, Some(ratelimit::Budget::Verify))?;
+        let verify_request = request.into_inner();
+        let verify_result = metrics::VRF_VERIFY_DURATION.observe_closure_duration(|| {
+            ecvrf::verify(hex::encode(verify_request.pk), hex::encode(verify_request.alpha), hex::encode(verify_request.pi))
+        });
+        match verify_result {
+            Ok(beta) => Ok(Response::new(pb::VerifyResponse{ beta: decode(&beta) })),
+            Err(ecvrf::VRFVerifyInputError::PKInvalid(_)) => Err(Status::invalid_argument("VRF public key is invalid.")),
+            Err(ecvrf::VRFVerifyInputError::PiInvalid(_)) => Err(Status::invalid_argument("VRF proof is invalid.")),
+            Err(ecvrf::VRFVerifyInputError::VRFVerifyFailed(_)) => Err(Status::invalid_argument("VRF proof does not verify.")),
+            Err(ecvrf::VRFVerifyInputError::AlphaInvalid(_)) => Err(Status::invalid_argument("Alpha is invalid.")),
+        }
+    }
+
+    async fn get_public_key(&self, request: Request<pb::GetPublicKeyRequest>) -> Result<Response<pb::GetPublicKeyResponse>, Status> {
+        self.access(&request, RouteGroup::Vrf, false, auth::Scope::VrfVerify, None)?;
+        match ecvrf::decode_sk(hex::encode(request.into_inner().sk)) {
+            Ok(sk) => Ok(Response::new(pb::GetPublicKeyResponse{ pk: decode(&ecvrf::get_pk(sk)) })),
+            Err(_err) => Err(Status::invalid_argument("VRF secret key is invalid.")),
+        }
+    }
+
+    async fn generate_secret_key(&self, request: Request<pb::GenerateSecretKeyRequest>) -> Result<Response<pb::GenerateSecretKeyResponse>, Status> {
+        self.access(&request, RouteGroup::Keys, true, auth::Scope::KeysAdmin, None)?;
+        match ecvrf::generate_sk() {
+            Ok(sk) => {
+                metrics::KEYS_GENERATED.inc();
+                Ok(Response::new(pb::GenerateSecretKeyResponse{ sk: decode(&sk) }))
+            },
+            Err(_err) => Err(Status::unavailable("Entropy source failed health tests.")),
+        }
+    }
+
+    async fn list_keys(&self, request: Request<pb::ListKeysRequest>) -> Result<Response<pb::ListKeysResponse>, Status> {
+        self.access(&request, RouteGroup::Keys, true, auth::Scope::KeysAdmin, None)?;
+        let list_request = request.into_inner();
+        let start = count(list_request.start);
+        let page_size = match count(list_request.count) {
+            0 => v2::DEFAULT_PAGE_SIZE,
+            page_size => page_size.min(v2::MAX_PAGE_SIZE),
+        };
+        let keys: Vec<pb::Key> = keystore::api_list_keys(&self.keyring).keys
+            .into_iter()
+            .skip(start)
+            .take(page_size)
+            .map(|key| pb::Key{ pk: decode(&key.pk), id: key.id })
+            .collect();
+        let next_start = start.saturating_add(keys.len());
+        Ok(Response::new(pb::ListKeysResponse {
+            keys,
+            next_start: (next_start < self.keyring.count()).then_some(next_start as u64),
+            total     : self.keyring.count() as u64,
+        }))
+    }
+
+    async fn prove_with_key(&self, request: Request<pb::ProveWithKeyRequest>) -> Result<Response<pb::Proof>, Status> {
+        self.access(&request, RouteGroup::Keys, true, auth::Scope::VrfProve, Some(ratelimit::Budget::Prove))?;
+        let prove_request = request.into_inner();
+        let sk = self.keyring.get(&prove_request.id).ok_or_else(|| Status::not_found("VRF key is not loaded."))?;
+        let vrf_output = metrics::VRF_PROVE_DURATION.observe_closure_duration(|| ecvrf::prove(sk, &prove_request.alpha));
+        metrics::KEY_PROOFS.with_label_values(&[prove_request.id.as_str()]).inc();
+        Ok(Response::new(proof(vrf_output)))
+    }
+
+    async fn draw_tickets(&self, request: Request<pb::DrawTicketsRequest>) -> Result<Response<pb::DrawTicketsResponse>, Status> {
+        self.access(&request, RouteGroup::Random, false, auth::Scope::RngDraw, Some(ratelimit::Budget::Random))?;
+        let draw_request = request.into_inner();
+        let (start, count) = (count(draw_request.start), count(draw_request.count));
+        rng::check_page(start, count, self.limits.max_iterations, self.limits.max_stream_iterations).map_err(rng_status)?;
+        // A page may skip up to `limits.max_stream_iterations` blocks, so it runs on the blocking pool.
+        let (seed, limit) = (hex::encode(&draw_request.seed), draw_request.limit);
+        let (version, generator) = (chain_version(draw_request.chain_version()), generator(draw_request.generator()));
+        let (results, next_seed) = tokio::task::spawn_blocking(move || rng::random_page(&seed, start, count, limit, version, generator))
+            .await
+            .map_err(|_err| Status::internal("Draw failed."))?
+            .map_err(rng_status)?;
+
+        metrics::DRAW_SIZE.with_label_values(&["tickets"]).observe(results.len() as f64);
+        let next_start = (start + results.len()) as u64;
+        Ok(Response::new(pb::DrawTicketsResponse {
+            tickets  : results.into_iter().enumerate().map(|(i, rng_result)| ticket(start + i, rng_result)).collect(),
+            next_start,
+            next_seed: next_seed.as_deref().map(decode),
+        }))
+    }
+
+    // Tickets are generated as the client reads them, like `/api/random/stream`.
+    async fn stream_tickets(&self, request: Request<pb::StreamTicketsRequest>) -> Result<Response<TicketStream>, Status> {
+        self.access(&request, RouteGroup::Random, false, auth::Scope::RngDraw, Some(ratelimit::Budget::Random))?;
+        let stream_request = request.into_inner();
+        let count = count(stream_request.count);
+        if count > self.limits.max_stream_iterations {
+            return Err(rng_status(rng::RngInputError::IterationsExceeded(rng::IterationsExceeded)));
+        }
+        let rng_iter = rng::random_iter(
+            &hex::encode(&stream_request.seed),
+            stream_request.limit,
+            chain_version(stream_request.chain_version()),
+            generator(stream_request.generator()),
+        ).map_err(|err| rng_status(rng::RngInputError::SeedInvalid(err)))?;
+
+        metrics::DRAW_SIZE.with_label_values(&["stream"]).observe(count as f64);
+        let tickets = rng_iter.take(count).enumerate().map(|(index, rng_result)| Ok(ticket(index, rng_result)));
+        Ok(Response::new(Box::pin(futures_util::stream::iter(tickets))))
+    }
+}
+
+// Serves `service` and, with `reflection`, the v1 and v1alpha reflection services on
+// `listener`. The listener is bound by the caller, so that a taken port fails at startup.
+pub async fn serve(service: GrpcService, listener: TcpListener, acceptor: Option<TlsAcceptor>, reflection: bool) -> Result<(), tonic::transport::Error> {
+    let (reflection_v1, reflection_v1alpha) = match reflection {
+        true => {
+            let builder = || tonic_reflection::server::Builder::configure().register_encoded_file_descriptor_set(pb::FILE_DESCRIPTOR_SET);
+            let v1 = builder().build_v1().expect("the descriptor set is valid");
+            let v1alpha = builder().build_v1alpha().expect("the descriptor set is valid");
+            (Some(v1), Some(v1alpha))
+        },
+        false => (None, None),
+    };
+    tonic::transport::Server::builder()
+        .add_service(pb::true_random_server::TrueRandomServer::new(service))
+        .add_optional_service(reflection_v1)
+        .add_optional_service(reflection_v1alpha)
+        .serve_with_incoming(incoming(listener, acceptor))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use futures_util::StreamExt;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use tonic::Code;
+    use tonic::transport::Channel;
+
+    use pb::true_random_client::TrueRandomClient;
+
+    const SEED: &str = "deadbeef";
+    const JWT_SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn authenticator() -> Arc<auth::Authenticator> {
+        let api_keys = [auth::ApiKeyConfig{ id: "ops".to_string(), sha256: hex::encode(auth::key_hash("ops-key")), scopes: vec![auth::Scope::RngDraw] }];
+        let jwt = auth::JwtConfig{ algorithms: vec![auth::JwtAlgorithm::HS256], issuer: None, audience: None, leeway_secs: auth::DEFAULT_LEEWAY_SECS, es_public_key: None };
+        Arc::new(auth::Authenticator::new(&auth::DEFAULT_PUBLIC_SCOPES, &api_keys, Some(&jwt), Some(JWT_SECRET)).unwrap())
+    }
+
+    fn token(scope: &str) -> String {
+        let exp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() + 600;
+        let claims = serde_json::json!({ "sub": "alice", "exp": exp, "scope": scope });
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(JWT_SECRET)).unwrap()
+    }
+
+    // A client of the service on a loopback port of this process.
+    async fn client(routes: RoutesConfig, rate_limiter: ratelimit::RateLimiter) -> TrueRandomClient<Channel> {
+        let service = GrpcService::new(Arc::new(keystore::Keyring::empty()), authenticator(), Arc::new(rate_limiter), routes, LimitsConfig::default(), false);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(service, listener, None, false));
+        TrueRandomClient::connect(format!("http://{}", addr)).await.unwrap()
+    }
+
+    async fn default_client() -> TrueRandomClient<Channel> {
+        client(RoutesConfig::default(), ratelimit::RateLimiter::new(false, false, HashMap::new())).await
+    }
+
+    fn with_metadata<T>(message: T, name: &'static str, value: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert(name, value.parse().unwrap());
+        request
+    }
+
+    fn draw_request(start: u64, count: u64) -> pb::DrawTicketsRequest {
+        pb::DrawTicketsRequest{ seed: hex::decode(SEED).unwrap(), limit: 100, start, count, ..Default::default() }
+    }
+
+    fn draw(start: u64, count: u64) -> Request<pb::DrawTicketsRequest> {
+        with_metadata(draw_request(start, count), "x-api-key", "ops-key")
+    }
+
+    #[tokio::test]
+    async fn credentials_and_scopes_map_to_status_codes() {
+        let mut client = default_client().await;
+
+        let status = client.draw_tickets(draw_request(0, 1)).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        let status = client.draw_tickets(with_metadata(draw_request(0, 1), "x-api-key", "other-key")).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        let status = client.draw_tickets(with_metadata(draw_request(0, 1), "authorization", "Bearer not-a-token")).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        // Invalid credentials are refused on public methods too.
+        let status = client.get_public_key(with_metadata(pb::GetPublicKeyRequest::default(), "x-api-key", "other-key")).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let authorization = format!("Bearer {}", token("vrf:prove"));
+        let status = client.draw_tickets(with_metadata(draw_request(0, 1), "authorization", &authorization)).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = client.list_keys(with_metadata(pb::ListKeysRequest::default(), "x-api-key", "ops-key")).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        let authorization = format!("Bearer {}", token("rng:draw"));
+        client.draw_tickets(with_metadata(draw_request(0, 1), "authorization", &authorization)).await.unwrap();
+        client.draw_tickets(draw(0, 1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn exhausted_budget_answers_resource_exhausted_with_retry_after() {
+        let budgets = HashMap::from([(ratelimit::Budget::Random, ratelimit::BucketConfig{ rate: 0.5, burst: 1 })]);
+        let mut client = client(RoutesConfig::default(), ratelimit::RateLimiter::new(true, false, budgets)).await;
+        client.draw_tickets(draw(0, 1)).await.unwrap();
+        let status = client.draw_tickets(draw(0, 1)).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn disabled_group_answers_unimplemented() {
+        let routes = RoutesConfig{ disabled: vec![RouteGroup::Vrf] };
+        let mut client = client(routes, ratelimit::RateLimiter::new(false, false, HashMap::new())).await;
+        let status = client.verify(pb::VerifyRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unimplemented);
+        let status = client.prove(pb::ProveRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unimplemented);
+        client.draw_tickets(draw(0, 1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn draw_tickets_pages_match_the_http_page() {
+        let mut client = default_client().await;
+        let limits = LimitsConfig::default();
+        for (start, count) in [(0, 5), (3, 4), (7, 1)] {
+            let response = client.draw_tickets(draw(start, count)).await.unwrap().into_inner();
+            let page_inputs = serde_json::from_value(serde_json::json!({ "initial_seed": SEED, "start": start, "count": count, "limit": 100 })).unwrap();
+            let page = serde_json::to_value(rng::api_random_page(page_inputs, limits.max_iterations, limits.max_stream_iterations).unwrap()).unwrap();
+
+            assert_eq!(response.next_start, page["next_start"]);
+            assert_eq!(response.next_seed.map(hex::encode), page["next_seed"].as_str().map(str::to_string));
+            assert_eq!(response.tickets.len(), count as usize);
+            for (i, (ticket, result)) in response.tickets.iter().zip(page["results"].as_array().unwrap()).enumerate() {
+                assert_eq!(ticket.index, start + i as u64);
+                assert_eq!(ticket.ticket, result["ticket_number"]);
+                assert_eq!(hex::encode(&ticket.random_result), result["random_result"]);
+                assert_eq!(ticket.new_seed.as_deref().map(hex::encode), result["new_seed"].as_str().map(str::to_string));
+            }
+        }
+
+        let status = client.draw_tickets(draw(0, limits.max_iterations as u64 + 1)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let status = client.draw_tickets(with_metadata(pb::DrawTicketsRequest{ count: 1, limit: 100, ..Default::default() }, "x-api-key", "ops-key")).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn stream_tickets_follows_the_chain() {
+        let mut client = default_client().await;
+        let stream_request = pb::StreamTicketsRequest{ seed: hex::decode(SEED).unwrap(), limit: 100, count: 12, ..Default::default() };
+        let tickets: Vec<pb::Ticket> = client.stream_tickets(with_metadata(stream_request, "x-api-key", "ops-key")).await.unwrap()
+            .into_inner()
+            .map(|ticket| ticket.unwrap())
+            .collect()
+            .await;
+        let page = client.draw_tickets(draw(0, 12)).await.unwrap().into_inner();
+        assert_eq!(tickets, page.tickets);
+
+        let max_stream_iterations = LimitsConfig::default().max_stream_iterations as u64;
+        let stream_request = pb::StreamTicketsRequest{ seed: hex::decode(SEED).unwrap(), limit: 100, count: max_stream_iterations + 1, ..Default::default() };
+        let status = client.stream_tickets(with_metadata(stream_request, "x-api-key", "ops-key")).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+}
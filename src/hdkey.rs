@@ -0,0 +1,184 @@
+use serde::Serialize;
+use serde::Deserialize;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use curve25519_dalek::scalar::Scalar;
+use vrf_r255::{PublicKey, SecretKey};
+
+type HmacSha512 = Hmac<Sha512>;
+
+// Derivation scheme. This is a custom scheme modelled on SLIP-10's hardened derivation, not
+// SLIP-10 itself: SLIP-10 defines no ristretto255 curve and uses index-based paths, so its
+// published vectors do not apply.
+//
+//   I = HMAC-SHA512(key = "RandomTON VRF seed", data = seed)
+//   for each segment s of the path "a/b/c":
+//     I = HMAC-SHA512(key = I[32..], data = 0x00 || I[..32] || len(s) as u32 BE || s)
+//   x = HMAC-SHA512(key = I[32..], data = "ristretto255" || I[..32]) mod l
+//
+// `x` is the ristretto255 VRF secret scalar for the path.
+const MASTER_KEY: &[u8] = b"RandomTON VRF seed";
+const SCALAR_DOMAIN: &[u8] = b"ristretto255";
+
+pub const MIN_SEED_LEN: usize = 16;
+pub const MAX_SEED_LEN: usize = 64;
+pub const MAX_PATH_DEPTH: usize = 16;
+pub const MAX_SEGMENT_LEN: usize = 255;
+
+#[derive(Debug)]
+pub struct MasterSeedInvalid;
+
+#[derive(Debug)]
+pub struct MasterSeedMissing;
+
+#[derive(Debug)]
+pub struct PathInvalid;
+
+#[derive(Debug)]
+pub struct DerivationFailed;
+
+#[derive(Debug)]
+pub enum DeriveInputError {
+    MasterSeedMissing(MasterSeedMissing),
+    PathInvalid(PathInvalid),
+    DerivationFailed(DerivationFailed),
+}
+
+#[derive(Deserialize)]
+pub struct DerivePKInputs {
+    path: String
+}
+
+#[derive(Serialize)]
+pub struct DerivePKOutput {
+    pub path: String,
+    pub pk  : String,
+}
+
+struct ExtendedKey {
+    key       : [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    fn from_hmac(key: &[u8], parts: &[&[u8]]) -> ExtendedKey {
+        let output = hmac_sha512(key, parts);
+        let mut extended = ExtendedKey{ key: [0u8; 32], chain_code: [0u8; 32] };
+        extended.key.copy_from_slice(&output[..32]);
+        extended.chain_code.copy_from_slice(&output[32..]);
+        extended
+    }
+
+    fn master(seed: &[u8]) -> ExtendedKey {
+        ExtendedKey::from_hmac(MASTER_KEY, &[seed])
+    }
+
+    fn child(&self, segment: &str) -> ExtendedKey {
+        let segment_len = (segment.len() as u32).to_be_bytes();
+        ExtendedKey::from_hmac(&self.chain_code, &[&[0u8], &self.key, &segment_len, segment.as_bytes()])
+    }
+
+    fn secret_key(&self) -> Result<SecretKey, DerivationFailed> {
+        let wide = hmac_sha512(&self.chain_code, &[SCALAR_DOMAIN, &self.key]);
+        let x = Scalar::from_bytes_mod_order_wide(&wide);
+        // Only fails for the zero scalar, which is negligible.
+        let sk = SecretKey::from_bytes(x.to_bytes());
+        if sk.is_some().into() {
+            Ok(sk.unwrap())
+        } else {
+            Err(DerivationFailed)
+        }
+    }
+}
+
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+pub fn decode_master_seed(seed_string: &str) -> Result<Vec<u8>, MasterSeedInvalid> {
+    match hex::decode(seed_string) {
+        Ok(seed) if (MIN_SEED_LEN..=MAX_SEED_LEN).contains(&seed.len()) => Ok(seed),
+        _ => Err(MasterSeedInvalid)
+    }
+}
+
+// `VRF_MASTER_SEED`, when set; checked at startup by `Config::validate`.
+pub fn master_seed_from_env() -> Result<Option<Vec<u8>>, MasterSeedInvalid> {
+    std::env::var("VRF_MASTER_SEED").ok().map(|seed| decode_master_seed(&seed)).transpose()
+}
+
+pub fn parse_path(path: &str) -> Result<Vec<&str>, PathInvalid> {
+    let segments: Vec<&str> = path.split('/').collect();
+    if segments.len() > MAX_PATH_DEPTH {
+        return Err(PathInvalid);
+    }
+    if segments.iter().any(|segment| segment.is_empty() || segment.len() > MAX_SEGMENT_LEN) {
+        return Err(PathInvalid);
+    }
+    Ok(segments)
+}
+
+pub fn derive_sk(seed: &[u8], path: &str) -> Result<SecretKey, DeriveInputError> {
+    let segments = parse_path(path).map_err(DeriveInputError::PathInvalid)?;
+
+    let mut extended = ExtendedKey::master(seed);
+    for segment in segments {
+        extended = extended.child(segment);
+    }
+
+    extended.secret_key().map_err(DeriveInputError::DerivationFailed)
+}
+
+pub fn derive_pk(seed: &[u8], path: &str) -> Result<PublicKey, DeriveInputError> {
+    derive_sk(seed, path).map(PublicKey::from)
+}
+
+pub fn api_derive_pk(derive_inputs: DerivePKInputs, seed: Option<&[u8]>) -> Result<DerivePKOutput, DeriveInputError> {
+    let seed = seed.ok_or(DeriveInputError::MasterSeedMissing(MasterSeedMissing))?;
+    let pk = derive_pk(seed, &derive_inputs.path)?;
+
+    Ok(DerivePKOutput{ path: derive_inputs.path, pk: hex::encode(pk.to_bytes()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str = "000102030405060708090a0b0c0d0e0f";
+
+    fn pk(path: &str) -> String {
+        let seed = decode_master_seed(SEED).unwrap();
+        hex::encode(derive_pk(&seed, path).unwrap().to_bytes())
+    }
+
+    // No external vectors exist for this scheme (see above); this one was produced by this
+    // implementation and is pinned so that any change to the derivation is caught.
+    #[test]
+    fn derivation_matches_the_pinned_vector() {
+        assert_eq!(pk("tenant/game/epoch"), "de6c615fb5881d221b94aa13f2d0e3d00e099c711181e4df3bafa68a2179c836");
+        assert_ne!(pk("tenant/game/epoch"), pk("tenant/game"));
+        assert_ne!(pk("ab/c"), pk("a/bc"));
+    }
+
+    #[test]
+    fn garbage_paths_are_rejected() {
+        let deep = vec!["a"; MAX_PATH_DEPTH + 1].join("/");
+        let long = "a".repeat(MAX_SEGMENT_LEN + 1);
+        for path in ["", "/", "a/", "/a", "a//b", &deep, &long] {
+            assert!(matches!(derive_pk(b"0123456789abcdef", path), Err(DeriveInputError::PathInvalid(_))), "{:?}", path);
+        }
+        assert!(derive_pk(b"0123456789abcdef", &vec!["a"; MAX_PATH_DEPTH].join("/")).is_ok());
+    }
+
+    #[test]
+    fn master_seed_length_is_checked() {
+        assert!(decode_master_seed(&"00".repeat(MIN_SEED_LEN - 1)).is_err());
+        assert!(decode_master_seed(&"00".repeat(MAX_SEED_LEN + 1)).is_err());
+        assert!(decode_master_seed("zz".repeat(MIN_SEED_LEN).as_str()).is_err());
+        assert!(decode_master_seed(&"00".repeat(MIN_SEED_LEN)).is_ok());
+    }
+}
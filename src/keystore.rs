@@ -0,0 +1,388 @@
+use serde::Serialize;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use rand_core::{OsRng, RngCore};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, Payload};
+use vrf_r255::{PublicKey, SecretKey};
+
+use crate::ecvrf;
+
+// Encrypted keystore files, modelled on the Ethereum keystore v3 layout:
+//
+//   key        = scrypt(passphrase, salt, log_n, r, p, dklen = 32)
+//   ciphertext = AES-256-GCM(key, nonce, sk, aad = pk)
+//
+// The public key is stored in clear and bound as associated data, so a keystore
+// can be listed without the passphrase but not re-labelled with another key.
+
+pub const KEYSTORE_VERSION: u32 = 1;
+
+pub const PASSPHRASE_ENV: &str = "VRF_KEYSTORE_PASSPHRASE";
+pub const PASSPHRASE_FILE_ENV: &str = "VRF_KEYSTORE_PASSPHRASE_FILE";
+
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const DKLEN: usize = 32;
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug)]
+pub struct KeystoreInvalid;
+
+#[derive(Debug)]
+pub struct PassphraseInvalid;
+
+#[derive(Debug)]
+pub struct PassphraseEmpty;
+
+#[derive(Debug)]
+pub struct KeyNotFound;
+
+// A keystore whose file is not named `<id>.json`, so `find` could not locate it by id.
+#[derive(Debug)]
+pub struct IdMismatch(pub PathBuf);
+
+#[derive(Debug)]
+pub struct IdDuplicate(pub String);
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    KeystoreInvalid(KeystoreInvalid),
+    PassphraseInvalid(PassphraseInvalid),
+    PassphraseEmpty(PassphraseEmpty),
+    IdMismatch(IdMismatch),
+    IdDuplicate(IdDuplicate),
+    Io(std::io::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r    : u32,
+    pub p    : u32,
+    pub dklen: usize,
+    pub salt : String, // Hex string.
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CipherParams {
+    pub nonce: String, // Hex string.
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Crypto {
+    pub kdf         : String,
+    pub kdfparams   : KdfParams,
+    pub cipher      : String,
+    pub cipherparams: CipherParams,
+    pub ciphertext  : String, // Hex string, includes the GCM tag.
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub id     : String,
+    pub pk     : String, // Hex string.
+    pub crypto : Crypto,
+}
+
+#[derive(Deserialize)]
+pub struct KeyProveInputs {
+    alpha: String
+}
+
+#[derive(Serialize)]
+pub struct KeyOutput {
+    pub id: String,
+    pub pk: String,
+}
+
+#[derive(Serialize)]
+pub struct KeysOutput {
+    pub keys: Vec<KeyOutput>,
+}
+
+pub enum KeyProveInputError {
+    KeyNotFound(KeyNotFound),
+    AlphaInvalid(ecvrf::AlphaInvalid),
+}
+
+// Secret keys unlocked at startup, by keystore id.
+pub struct Keyring {
+    keys: BTreeMap<String, SecretKey>,
+}
+
+fn derive_key(passphrase: &str, kdfparams: &KdfParams) -> Result<[u8; DKLEN], KeystoreError> {
+    if kdfparams.dklen != DKLEN {
+        return Err(KeystoreError::KeystoreInvalid(KeystoreInvalid));
+    }
+    let salt = hex::decode(&kdfparams.salt).map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+    let params = scrypt::Params::new(kdfparams.log_n, kdfparams.r, kdfparams.p, DKLEN)
+        .map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+
+    let mut key = [0u8; DKLEN];
+    scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+        .map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+    Ok(key)
+}
+
+fn seal(sk: &SecretKey, passphrase: &str, id: String) -> Keystore {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let pk = PublicKey::from(*sk).to_bytes();
+    let kdfparams = KdfParams{ log_n: SCRYPT_LOG_N, r: SCRYPT_R, p: SCRYPT_P, dklen: DKLEN, salt: hex::encode(salt) };
+    let key = derive_key(passphrase, &kdfparams).expect("default scrypt parameters are valid");
+
+    let cipher = Aes256Gcm::new(&key.into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload{ msg: &sk.to_bytes(), aad: &pk })
+        .expect("AES-GCM encryption of 32 bytes cannot fail");
+
+    Keystore {
+        version: KEYSTORE_VERSION,
+        id,
+        pk     : hex::encode(pk),
+        crypto : Crypto {
+            kdf         : "scrypt".to_string(),
+            kdfparams,
+            cipher      : "aes-256-gcm".to_string(),
+            cipherparams: CipherParams{ nonce: hex::encode(nonce) },
+            ciphertext  : hex::encode(ciphertext),
+        },
+    }
+}
+
+pub fn encrypt(sk: &SecretKey, passphrase: &str) -> Keystore {
+    seal(sk, passphrase, uuid::Uuid::new_v4().to_string())
+}
+
+pub fn decrypt(keystore: &Keystore, passphrase: &str) -> Result<SecretKey, KeystoreError> {
+    if keystore.version != KEYSTORE_VERSION || keystore.crypto.kdf != "scrypt" || keystore.crypto.cipher != "aes-256-gcm" {
+        return Err(KeystoreError::KeystoreInvalid(KeystoreInvalid));
+    }
+    let pk = hex::decode(&keystore.pk).map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+    let nonce = hex::decode(&keystore.crypto.cipherparams.nonce).map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+    let ciphertext = hex::decode(&keystore.crypto.ciphertext).map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+    if nonce.len() != NONCE_LEN {
+        return Err(KeystoreError::KeystoreInvalid(KeystoreInvalid));
+    }
+
+    let key = derive_key(passphrase, &keystore.crypto.kdfparams)?;
+    let cipher = Aes256Gcm::new(&key.into());
+    let sk_vec = cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload{ msg: &ciphertext, aad: &pk })
+        .map_err(|_err| KeystoreError::PassphraseInvalid(PassphraseInvalid))?;
+
+    let sk_bytes: [u8; 32] = sk_vec.try_into().map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))?;
+    let sk = SecretKey::from_bytes(sk_bytes);
+    if sk.is_some().into() {
+        Ok(sk.unwrap())
+    } else {
+        Err(KeystoreError::KeystoreInvalid(KeystoreInvalid))
+    }
+}
+
+pub fn change_passphrase(keystore: &Keystore, old_passphrase: &str, new_passphrase: &str) -> Result<Keystore, KeystoreError> {
+    let sk = decrypt(keystore, old_passphrase)?;
+    Ok(seal(&sk, new_passphrase, keystore.id.clone()))
+}
+
+pub fn read(path: &Path) -> Result<Keystore, KeystoreError> {
+    let json = fs::read_to_string(path).map_err(KeystoreError::Io)?;
+    serde_json::from_str(&json).map_err(|_err| KeystoreError::KeystoreInvalid(KeystoreInvalid))
+}
+
+pub fn path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+// Writes `keystore` to `path` through a temporary file in the same directory, synced and then
+// renamed over `path`, so a crash or a full disk never leaves a half-written keystore behind.
+pub fn write(path: &Path, keystore: &Keystore) -> Result<(), KeystoreError> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path.file_name().ok_or_else(|| KeystoreError::Io(std::io::ErrorKind::InvalidInput.into()))?;
+    fs::create_dir_all(dir).map_err(KeystoreError::Io)?;
+    let temp_path = dir.join(format!(".{}.tmp", file_name.to_string_lossy()));
+    let json = serde_json::to_string_pretty(keystore).expect("keystore serializes to JSON");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(json.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp_path, path)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(KeystoreError::Io(err));
+    }
+    // The rename itself is only durable once the directory is synced.
+    #[cfg(unix)]
+    fs::File::open(dir).and_then(|dir| dir.sync_all()).map_err(KeystoreError::Io)?;
+    Ok(())
+}
+
+// Keystores in `dir`, each of which must be stored as `<id>.json` so `find` and `list` agree.
+pub fn list(dir: &Path) -> Result<Vec<(PathBuf, Keystore)>, KeystoreError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(KeystoreError::Io)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    paths.into_iter().map(|path| {
+        let keystore = read(&path)?;
+        if path.file_stem().is_none_or(|stem| *stem != *keystore.id) {
+            return Err(KeystoreError::IdMismatch(IdMismatch(path)));
+        }
+        Ok((path, keystore))
+    }).collect()
+}
+
+pub fn find(dir: &Path, id: &str) -> Result<(PathBuf, Keystore), KeystoreError> {
+    let path = path(dir, id);
+    read(&path).map(|keystore| (path, keystore))
+}
+
+// Passphrase from `VRF_KEYSTORE_PASSPHRASE`, or the first line of `VRF_KEYSTORE_PASSPHRASE_FILE`.
+// Empty passphrases are rejected, as at the prompt.
+pub fn passphrase_from_env() -> Result<Option<String>, KeystoreError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return non_empty(passphrase).map(Some);
+    }
+    match std::env::var(PASSPHRASE_FILE_ENV) {
+        Ok(path) => read_passphrase_file(Path::new(&path)).map(Some),
+        Err(_err) => Ok(None)
+    }
+}
+
+pub fn read_passphrase_file(path: &Path) -> Result<String, KeystoreError> {
+    let contents = fs::read_to_string(path).map_err(KeystoreError::Io)?;
+    non_empty(contents.lines().next().unwrap_or_default().to_string())
+}
+
+fn non_empty(passphrase: String) -> Result<String, KeystoreError> {
+    match passphrase.is_empty() {
+        true => Err(KeystoreError::PassphraseEmpty(PassphraseEmpty)),
+        false => Ok(passphrase),
+    }
+}
+
+impl Keyring {
+    pub fn empty() -> Keyring {
+        Keyring{ keys: BTreeMap::new() }
+    }
+
+    // Unlocked keys without keystore files, for tests.
+    #[cfg(test)]
+    pub(crate) fn from_keys(keys: impl IntoIterator<Item = (String, SecretKey)>) -> Keyring {
+        Keyring{ keys: keys.into_iter().collect() }
+    }
+
+    pub fn load(dir: &Path, passphrase: &str) -> Result<Keyring, KeystoreError> {
+        let mut keys = BTreeMap::new();
+        for (_path, keystore) in list(dir)? {
+            let sk = decrypt(&keystore, passphrase)?;
+            if keys.contains_key(&keystore.id) {
+                return Err(KeystoreError::IdDuplicate(IdDuplicate(keystore.id)));
+            }
+            keys.insert(keystore.id, sk);
+        }
+        Ok(Keyring{ keys })
+    }
+
+    pub fn count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&SecretKey> {
+        self.keys.get(id)
+    }
+}
+
+// Api endpoints.
+pub fn api_list_keys(keyring: &Keyring) -> KeysOutput {
+    KeysOutput {
+        keys: keyring.keys
+            .iter()
+            .map(|(id, sk)| KeyOutput{ id: id.clone(), pk: ecvrf::get_pk(*sk) })
+            .collect()
+    }
+}
+
+pub fn api_key_prove(keyring: &Keyring, id: &str, prove_inputs: KeyProveInputs) -> Result<ecvrf::VRFOutput, KeyProveInputError> {
+    let sk = keyring.get(id).ok_or(KeyProveInputError::KeyNotFound(KeyNotFound))?;
+    match hex::decode(prove_inputs.alpha) {
+        Ok(alpha) => Ok(ecvrf::prove(sk, &alpha)),
+        Err(_err) => Err(KeyProveInputError::AlphaInvalid(ecvrf::AlphaInvalid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sk() -> SecretKey {
+        SecretKey::from_bytes([7u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn decrypts_with_the_passphrase_only() {
+        let keystore = encrypt(&sk(), "correct horse");
+        assert_eq!(decrypt(&keystore, "correct horse").unwrap().to_bytes(), sk().to_bytes());
+        assert!(matches!(decrypt(&keystore, "battery staple"), Err(KeystoreError::PassphraseInvalid(_))));
+    }
+
+    #[test]
+    fn change_passphrase_keeps_key_and_id() {
+        let keystore = encrypt(&sk(), "old");
+        let changed = change_passphrase(&keystore, "old", "new").unwrap();
+        assert_eq!(changed.id, keystore.id);
+        assert_eq!(decrypt(&changed, "new").unwrap().to_bytes(), sk().to_bytes());
+        assert!(decrypt(&changed, "old").is_err());
+        assert!(change_passphrase(&keystore, "wrong", "new").is_err());
+    }
+
+    #[test]
+    fn write_replaces_the_file() {
+        let dir = std::env::temp_dir().join(format!("keystore-test-{}", uuid::Uuid::new_v4()));
+        let keystore = encrypt(&sk(), "old");
+        let path = dir.join("selected.json");
+        write(&path, &keystore).unwrap();
+        write(&path, &change_passphrase(&keystore, "old", "new").unwrap()).unwrap();
+
+        let entries: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(entries, vec![path.clone()]);
+        assert_eq!(decrypt(&read(&path).unwrap(), "new").unwrap().to_bytes(), sk().to_bytes());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_requires_file_names_to_match_ids() {
+        let dir = std::env::temp_dir().join(format!("keystore-test-{}", uuid::Uuid::new_v4()));
+        let keystore = encrypt(&sk(), "pass");
+        write(&path(&dir, &keystore.id), &keystore).unwrap();
+        let keyring = Keyring::load(&dir, "pass").unwrap();
+        assert!(keyring.get(&keystore.id).is_some());
+        assert_eq!(find(&dir, &keystore.id).unwrap().1.id, keystore.id);
+
+        // The same keystore copied under another name would shadow or duplicate the original.
+        fs::copy(path(&dir, &keystore.id), dir.join("copy.json")).unwrap();
+        assert!(matches!(Keyring::load(&dir, "pass"), Err(KeystoreError::IdMismatch(_))));
+        assert!(matches!(list(&dir), Err(KeystoreError::IdMismatch(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+pub mod utils;
+pub mod ecvrf;
+pub mod entropy;
+pub mod rng;
+pub mod generator;
+pub mod shuffle;
+pub mod prize;
+pub mod merkle;
+pub mod selftest;
+pub mod hdkey;
+pub mod backup;
+pub mod keystore;
+pub mod beacon;
+pub mod drand;
+pub mod config;
+pub mod tls;
+pub mod auth;
+pub mod ratelimit;
+pub mod metrics;
+pub mod logging;
+pub mod cli;
+pub mod v2;
+pub mod grpc;
+
+// Rejections raised by the warp handlers in `main.rs`.
+impl warp::reject::Reject for ecvrf::AlphaInvalid {}
+impl warp::reject::Reject for ecvrf::SKInvalid {}
+impl warp::reject::Reject for ecvrf::PKInvalid {}
+impl warp::reject::Reject for ecvrf::PiInvalid {}
+impl warp::reject::Reject for ecvrf::VRFVerifyFailed {}
+impl warp::reject::Reject for utils::IntStringInvalid {}
+impl warp::reject::Reject for utils::HexStringInvalid {}
+impl warp::reject::Reject for rng::IterationsExceeded {}
+impl warp::reject::Reject for rng::SeedInvalid {}
+impl warp::reject::Reject for rng::DistributionInvalid {}
+impl warp::reject::Reject for shuffle::ItemInvalid {}
+impl warp::reject::Reject for prize::TiersInvalid {}
+impl warp::reject::Reject for merkle::ParticipantsInvalid {}
+impl warp::reject::Reject for merkle::TicketInvalid {}
+impl warp::reject::Reject for selftest::SamplesInvalid {}
+impl warp::reject::Reject for entropy::EntropyFailed {}
+impl warp::reject::Reject for hdkey::MasterSeedMissing {}
+impl warp::reject::Reject for hdkey::PathInvalid {}
+impl warp::reject::Reject for hdkey::DerivationFailed {}
+impl warp::reject::Reject for backup::MnemonicInvalid {}
+impl warp::reject::Reject for backup::SharesInvalid {}
+impl warp::reject::Reject for backup::ThresholdInvalid {}
+impl warp::reject::Reject for backup::PKMismatch {}
+impl warp::reject::Reject for keystore::KeyNotFound {}
+impl warp::reject::Reject for beacon::BeaconDisabled {}
+impl warp::reject::Reject for beacon::RoundNotFound {}
+impl warp::reject::Reject for drand::DrandDisabled {}
+impl warp::reject::Reject for drand::DrandInvalid {}
+impl warp::reject::Reject for drand::DrandUnavailable {}
+impl warp::reject::Reject for tls::ClientCertRequired {}
+impl warp::reject::Reject for auth::CredentialsInvalid {}
+impl warp::reject::Reject for auth::ScopeMissing {}
+impl warp::reject::Reject for ratelimit::RateLimited {}
@@ -0,0 +1,231 @@
+use std::convert::Infallible;
+use std::io::Write;
+use std::net::SocketAddr;
+
+use log::kv::{self, VisitSource};
+use warp::hyper::{Body, Request, Response};
+use warp::hyper::header::HeaderValue;
+use warp::hyper::service::Service;
+
+use crate::config::{LogConfig, LogFormat};
+use crate::tls::PeerAddr;
+
+// Log output and request correlation. Every request runs with a request id: the incoming
+// `X-Request-Id` when it is a plain token of up to 128 characters, a fresh UUIDv4 otherwise.
+// The id is echoed in the `X-Request-Id` response header and in error bodies, and is added
+// to every line logged while the request is handled.
+//
+// Both formats go through `scrub`, so values of `sk`, `*_sk` and `sk_*` fields never reach
+// the log, whatever logged them.
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+pub const REDACTED: &str = "[redacted]";
+
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    pub id  : String,
+    pub peer: Option<SocketAddr>,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+// The id of the request handled by the current task, if any.
+pub fn request_id() -> Option<String> {
+    REQUEST.try_with(|request| request.id.clone()).ok()
+}
+
+fn request_peer() -> Option<SocketAddr> {
+    REQUEST.try_with(|request| request.peer).ok().flatten()
+}
+
+fn request_id_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"-_.:".contains(&byte))
+}
+
+pub fn request_id_from(header: Option<&HeaderValue>) -> String {
+    match header.and_then(|header| header.to_str().ok()) {
+        Some(id) if request_id_valid(id) => id.to_string(),
+        _ => uuid::Uuid::new_v4().to_string(),
+    }
+}
+
+// Runs `service` for one request within its `RequestContext` and echoes the id. The peer
+// address comes from the `PeerAddr` extension set by `tls::serve`.
+pub async fn serve_request<S>(mut service: S, request: Request<Body>) -> Result<Response<Body>, Infallible>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+{
+    let context = RequestContext {
+        id  : request_id_from(request.headers().get(REQUEST_ID_HEADER)),
+        peer: request.extensions().get::<PeerAddr>().map(|peer| peer.0),
+    };
+    let header = HeaderValue::from_str(&context.id).expect("request ids are visible ASCII");
+    let mut response = REQUEST.scope(context, service.call(request)).await?;
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    Ok(response)
+}
+
+// Passed to `warp::log::custom`: one line per response, in the layout of `warp::log`.
+pub fn log_request(info: warp::log::Info<'_>) {
+    let peer = request_peer().map(|peer| peer.to_string()).unwrap_or_else(|| "-".to_string());
+    let elapsed_ms = info.elapsed().as_secs_f64() * 1000.0;
+    log::info!(
+        target: "api",
+        peer = peer.as_str(), method = info.method().as_str(), path = info.path(), status = info.status().as_u16(), elapsed_ms = elapsed_ms;
+        "{} \"{} {} {:?}\" {} \"{}\" {:.3}ms",
+        peer,
+        info.method(),
+        info.path(),
+        info.version(),
+        info.status().as_u16(),
+        info.user_agent().unwrap_or("-"),
+        elapsed_ms,
+    );
+}
+
+// Secret keys, and the mnemonics and Shamir shares of `backup` that are worth as much.
+fn secret_key_field(name: &str) -> bool {
+    name == "sk" || name.ends_with("_sk") || name.starts_with("sk_") || name == "mnemonic" || name == "shares"
+}
+
+// Length of the value starting at `text`: a quoted string (also with escaped quotes, as in
+// Debug output of a JSON body), a bracketed list, or a bare token.
+fn value_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    if let Some(value) = text.strip_prefix("\\\"") {
+        return value.find("\\\"").map_or(bytes.len(), |i| i + 4);
+    }
+    match bytes.first() {
+        Some(b'"') => {
+            let mut escaped = false;
+            for (i, &byte) in bytes.iter().enumerate().skip(1) {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => return i + 1,
+                    _ => {}
+                }
+            }
+            bytes.len()
+        },
+        Some(b'[') => bytes.iter().position(|&byte| byte == b']').map_or(bytes.len(), |i| i + 1),
+        _ => bytes.iter().position(|&byte| byte.is_ascii_whitespace() || b",;&}])\"".contains(&byte)).unwrap_or(bytes.len()),
+    }
+}
+
+// Replaces the values of `sk`, `*_sk`, `sk_*`, `mnemonic` and `shares` fields written as JSON (`"sk":"..."`), Debug
+// (`sk: [..]`) or key-value pairs (`sk=...`).
+pub fn scrub(text: &str) -> String {
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+        let end = rest[start..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).map_or(rest.len(), |len| start + len);
+        scrubbed.push_str(&rest[..end]);
+        let name = &rest[start..end];
+        rest = &rest[end..];
+        if !secret_key_field(name) {
+            continue;
+        }
+        // Optional closing quote of a JSON key, then the separator.
+        let after_name = rest.strip_prefix("\\\"").or_else(|| rest.strip_prefix('"')).unwrap_or(rest);
+        let after_separator = after_name.trim_start();
+        let Some(value) = after_separator.strip_prefix(':').or_else(|| after_separator.strip_prefix('=')) else {
+            continue;
+        };
+        let value = value.trim_start();
+        let len = value_len(value);
+        if len == 0 {
+            continue;
+        }
+        scrubbed.push_str(&rest[..rest.len() - value.len()]);
+        scrubbed.push_str(match value.as_bytes()[0] {
+            b'"' => "\"[redacted]\"",
+            b'\\' => "\\\"[redacted]\\\"",
+            _ => REDACTED,
+        });
+        rest = &value[len..];
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+// Key-value pairs of a record as JSON fields, scrubbed like the message.
+struct JsonFields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = match serde_json::to_value(value) {
+            _ if secret_key_field(key.as_str()) => serde_json::Value::from(REDACTED),
+            Ok(serde_json::Value::String(text)) => serde_json::Value::String(scrub(&text)),
+            Ok(value) => value,
+            Err(err) => return Err(kv::Error::boxed(err)),
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+// `text` writes `LEVEL target [request id] > message`; `json` writes one object per line.
+pub fn init(log_config: &LogConfig) {
+    let mut builder = pretty_env_logger::formatted_builder();
+    builder.parse_filters(&log_config.filter);
+    match log_config.format {
+        LogFormat::Text => builder.format(|buf, record| {
+            let request_id = request_id().map(|id| format!(" [{}]", id)).unwrap_or_default();
+            writeln!(buf, "{:<5} {}{} > {}", record.level(), record.target(), request_id, scrub(&record.args().to_string()))
+        }),
+        LogFormat::Json => builder.format(|buf, record| {
+            let mut fields = JsonFields(serde_json::Map::new());
+            fields.0.insert("timestamp".to_string(), chrono::Utc::now().to_rfc3339().into());
+            fields.0.insert("level".to_string(), record.level().as_str().into());
+            fields.0.insert("target".to_string(), record.target().into());
+            if let Some(request_id) = request_id() {
+                fields.0.insert("request_id".to_string(), request_id.into());
+            }
+            fields.0.insert("message".to_string(), scrub(&record.args().to_string()).into());
+            let _ = record.key_values().visit(&mut fields);
+            writeln!(buf, "{}", serde_json::Value::Object(fields.0))
+        }),
+    };
+    builder.init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrubs_json() {
+        assert_eq!(scrub(r#"{"sk":"0a1b","alpha":"00"}"#), r#"{"sk":"[redacted]","alpha":"00"}"#);
+        assert_eq!(scrub(r#"{"sk" : "0a\"1b", "alpha": "00"}"#), r#"{"sk" : "[redacted]", "alpha": "00"}"#);
+    }
+
+    #[test]
+    fn scrubs_escaped_json_in_debug_output() {
+        assert_eq!(scrub(r#"Body("{\"sk\":\"0a1b\",\"alpha\":\"00\"}")"#), r#"Body("{\"sk\":\"[redacted]\",\"alpha\":\"00\"}")"#);
+    }
+
+    #[test]
+    fn scrubs_debug_structs_and_key_values() {
+        assert_eq!(scrub("VRFInputs { sk: [10, 27], alpha: [0] }"), "VRFInputs { sk: [redacted], alpha: [0] }");
+        assert_eq!(scrub("Inputs { sk: \"0a1b\" }"), "Inputs { sk: \"[redacted]\" }");
+        assert_eq!(scrub("sk=0a1b&alpha=00"), "sk=[redacted]&alpha=00");
+    }
+
+    #[test]
+    fn scrubs_prefixed_and_suffixed_names_only() {
+        assert_eq!(scrub(r#"{"sk_hex":"0a","master_sk":"1b"}"#), r#"{"sk_hex":"[redacted]","master_sk":"[redacted]"}"#);
+        assert_eq!(scrub(r#"{"task":"0a","risk":1,"skip":true,"pk":"1b"}"#), r#"{"task":"0a","risk":1,"skip":true,"pk":"1b"}"#);
+    }
+
+    #[test]
+    fn scrubs_mnemonics_and_shares() {
+        assert_eq!(scrub(r#"{"mnemonic":"abandon ability able","pk":"1b"}"#), r#"{"mnemonic":"[redacted]","pk":"1b"}"#);
+        assert_eq!(scrub(r#"{"shares":["02010a","02021b"],"pk":"1b"}"#), r#"{"shares":[redacted],"pk":"1b"}"#);
+    }
+}
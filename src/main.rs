@@ -1,129 +1,845 @@
+#![recursion_limit = "256"]
+
 use serde::Serialize;
+use clap::Parser;
+use utoipa::{OpenApi, ToSchema};
 
-use std::error::Error;
 use std::convert::Infallible;
+use std::sync::Arc;
 
 use warp::{Filter, Rejection, Reply};
 use warp::http::StatusCode;
+use futures_util::stream;
 
-mod utils;
-mod ecvrf;
-mod rng;
+use true_random_on_ton::{utils, ecvrf, entropy, rng, shuffle, prize, merkle, selftest, hdkey, backup, keystore, beacon, drand, tls, auth, ratelimit, metrics, logging, cli, v2, grpc};
+use true_random_on_ton::config::{Config, RouteGroup, RoutesConfig};
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct ErrorMessage {
     success: bool,
     code: u16,
-    message: String
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct SuccessMessage<T> {
     success: bool,
     code: u16,
     data: T
 }
 
+// OpenAPI document for the routes built on the `ecvrf`, `utils`, `rng` and `v2` types, served at
+// `/openapi.json`. `tests/openapi.rs` replays every example against the server and checks the
+// responses against the document.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "True Random on TON"),
+    paths(
+        handle_vrf_prove, handle_vrf_verify, handle_get_pk, handle_generate_sk,
+        handle_hex, handle_int, handle_sha256, handle_sha512,
+        handle_random, handle_random_stream, handle_random_page, handle_distribution,
+        handle_v2_vrf_prove, handle_v2_vrf_verify, handle_v2_get_pk, handle_v2_generate_sk, handle_v2_list_keys,
+        handle_v2_int_to_hex, handle_v2_hex_to_int, handle_v2_sha256, handle_v2_sha512,
+        handle_v2_tickets, handle_v2_distribution,
+    ),
+    tags(
+        (name = "vrf", description = "ECVRF-RISTRETTO255-SHA512 keys, proofs and verification."),
+        (name = "utils", description = "Integer, hex and hash conversions."),
+        (name = "random", description = "Draws from a seed, see `docs/seed-chain.md`."),
+        (name = "v2", description = "The `/api/v2` surface: snake_case fields named after their encoding, `{data, page, request_id}` bodies and `{error: {code, message}, request_id}` errors."),
+    ),
+)]
+struct ApiDoc;
+
 #[derive(Debug)]
 struct UnknownError;
 impl warp::reject::Reject for UnknownError {}
 
-impl warp::reject::Reject for ecvrf::AlphaInvalid {}
-impl warp::reject::Reject for ecvrf::SKInvalid {}
-impl warp::reject::Reject for ecvrf::PKInvalid {}
-impl warp::reject::Reject for ecvrf::PiInvalid {}
-impl warp::reject::Reject for utils::IntStringInvalid {}
-impl warp::reject::Reject for utils::HexStringInvalid {}
-impl warp::reject::Reject for rng::IterationsExceeded {}
-
 #[tokio::main]
 async fn main() {
-    if std::env::var_os("RUST_LOG").is_none() {
-        unsafe {
-            std::env::set_var("RUST_LOG", "api=info");
+    let cli = cli::Cli::parse();
+    if let Some(command) = cli.command {
+        if let Err(message) = cli::run(command) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Config file, then environment, then flags; see `config`.
+    let config = match Config::load(&cli.serve) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
+    };
+    if cli.serve.print_config {
+        print!("{}", config.to_toml());
+        return;
     }
-    pretty_env_logger::init();
+
+    logging::init(&config.log);
+
+    // Hex master seed for deterministic VRF key derivation (`hdkey`).
+    let master_seed: Arc<Option<Vec<u8>>> = match hdkey::master_seed_from_env() {
+        Ok(master_seed) => Arc::new(master_seed),
+        Err(_err) => {
+            eprintln!("VRF_MASTER_SEED must be a {}-{} byte hex string", hdkey::MIN_SEED_LEN, hdkey::MAX_SEED_LEN);
+            std::process::exit(1);
+        }
+    };
+
+    // SP 800-90B startup tests; key generation stays disabled if they fail.
+    if entropy::startup().is_err() {
+        log::error!(target: "api", "Entropy source failed its startup health tests, key generation is disabled");
+    }
+
+    // Keystores unlocked at startup, see `keystore create`.
+    let keyring: Arc<keystore::Keyring> = Arc::new(match &config.keystore.dir {
+        Some(dir) => {
+            let passphrase = match keystore::passphrase_from_env() {
+                Ok(Some(passphrase)) => passphrase,
+                Ok(None) => {
+                    eprintln!("VRF_KEYSTORE_PASSPHRASE or VRF_KEYSTORE_PASSPHRASE_FILE must be set with keystore.dir");
+                    std::process::exit(1);
+                },
+                Err(err) => {
+                    eprintln!("VRF_KEYSTORE_PASSPHRASE(_FILE) must be readable and not empty: {}", cli::keystore_error_message(err));
+                    std::process::exit(1);
+                }
+            };
+            let keyring = match keystore::Keyring::load(dir, &passphrase) {
+                Ok(keyring) => keyring,
+                Err(err) => {
+                    eprintln!("Keystores in keystore.dir must unlock with the given passphrase: {}", cli::keystore_error_message(err));
+                    std::process::exit(1);
+                }
+            };
+            log::info!(target: "api", "Loaded {} keystore(s) from {}", keyring.count(), dir.display());
+            keyring
+        },
+        None => keystore::Keyring::empty()
+    });
+
+    // Public beacon rounds proved with the keystore key `beacon.key_id`, see `docs/beacon.md`.
+    let beacon: Arc<Option<beacon::Beacon>> = Arc::new(config.beacon.key_id.as_ref().map(|id| {
+        let Some(sk) = keyring.get(id) else {
+            eprintln!("beacon.key_id must name a keystore loaded from keystore.dir");
+            std::process::exit(1);
+        };
+        match beacon::Beacon::new(*sk, config.beacon.interval_secs, config.beacon.file.clone()) {
+            Ok(beacon) => beacon,
+            Err(err) => {
+                match err {
+                    beacon::BeaconError::ChainInvalid(err) => eprintln!("beacon.file is invalid at round {}: {}", err.round, err.reason),
+                    beacon::BeaconError::Io(err) => eprintln!("beacon.file is not readable: {}", err),
+                }
+                std::process::exit(1);
+            }
+        }
+    }));
+    if beacon.is_some() {
+        let beacon = beacon.clone();
+        tokio::spawn(async move {
+            let beacon = beacon.as_ref().as_ref().expect("beacon is configured");
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(beacon.interval_secs()));
+            loop {
+                interval.tick().await;
+                match beacon.next_round(chrono::Utc::now().timestamp()).await {
+                    Ok(round) => log::info!(target: "api", "Beacon round {} published", round.round),
+                    Err(beacon::BeaconError::Io(err)) => log::error!(target: "api", "Beacon round could not be stored: {}", err),
+                    Err(beacon::BeaconError::ChainInvalid(err)) => log::error!(target: "api", "Beacon round {} is invalid: {}", err.round, err.reason),
+                }
+            }
+        });
+    }
+
+    // External drand chain: `drand.chain_info` (file) or the `/info` of `drand.url`, see `docs/drand.md`.
+    let drand_url = config.drand.url.clone();
+    let drand_info = match (&config.drand.chain_info, &drand_url) {
+        (Some(path), _) => match drand::read_info(path) {
+            Ok(info) => Some(info),
+            Err(_err) => {
+                eprintln!("drand.chain_info must be a readable drand chain info file");
+                std::process::exit(1);
+            }
+        },
+        (None, Some(url)) => {
+            let url = url.clone();
+            match tokio::task::spawn_blocking(move || drand::fetch_info(&url)).await {
+                Ok(Ok(info)) => Some(info),
+                _ => {
+                    eprintln!("drand.url must serve a drand chain info document at /info");
+                    std::process::exit(1);
+                }
+            }
+        },
+        (None, None) => None,
+    };
+    let drand: Arc<Option<drand::Drand>> = Arc::new(drand_info.map(|info| match drand::Drand::new(info, drand_url) {
+        Ok(drand) => {
+            log::info!(target: "api", "Verifying drand rounds for chain {} ({})", drand.verifier().info().hash, drand.verifier().info().scheme);
+            drand
+        },
+        Err(_err) => {
+            eprintln!("drand chain info must hold a valid public key");
+            std::process::exit(1);
+        }
+    }));
+    let with_drand = warp::any().map(move || drand.clone());
+    let with_beacon = warp::any().map(move || beacon.clone());
+    let with_keyring = {
+        let keyring = keyring.clone();
+        warp::any().map(move || keyring.clone())
+    };
+
+    // API keys and JWTs with per-route scopes, see `auth`.
+    let jwt_secret = std::env::var("AUTH_JWT_SECRET").ok();
+    let authenticator = match auth::Authenticator::new(&config.auth.public, &config.auth.api_keys, config.auth.jwt.as_ref(), jwt_secret.as_deref().map(str::as_bytes)) {
+        Ok(authenticator) => Arc::new(authenticator),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if !authenticator.is_enabled() {
+        log::warn!(target: "api", "Authentication is not configured, every route is public");
+    }
+    let rate_limiter = Arc::new(ratelimit::RateLimiter::new(config.rate_limit.enabled, config.rate_limit.trust_forwarded_for, config.rate_limit.budgets()));
+    let access = {
+        let (authenticator, rate_limiter) = (authenticator.clone(), rate_limiter.clone());
+        move |scope, budget| access(authenticator.clone(), rate_limiter.clone(), scope, budget)
+    };
+
+    let limits = config.limits.clone();
+    let (max_iterations, max_stream_iterations) = (limits.max_iterations, limits.max_stream_iterations);
+    let (max_shuffle_items, max_participants) = (limits.max_shuffle_items, limits.max_participants);
+    let body = limits.body;
+    let routes_config = &config.routes;
+    // With mTLS, proving and key management require a verified client certificate.
+    let client_auth = config.server.tls.as_ref().is_some_and(|tls| tls.client_ca.is_some());
+    let grpc_service = grpc::GrpcService::new(keyring, authenticator.clone(), rate_limiter.clone(), config.routes.clone(), config.limits.clone(), client_auth);
 
     let vrf_prove = warp::path!("api" / "vrf" / "prove")
+        .and(route_group(routes_config, RouteGroup::Vrf))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::VrfProve, Some(ratelimit::Budget::Prove)))
+        .and(warp::body::content_length_limit(body.vrf))
         .and(warp::body::json())
         .and_then(handle_vrf_prove);
 
     let generate_sk = warp::path!("api" / "vrf" / "sk" / "new")
+        .and(route_group(routes_config, RouteGroup::Keys))
         .and(warp::get())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
         .and_then(handle_generate_sk);
 
+    let entropy_health = warp::path!("api" / "health" / "entropy") // -> status, failed_test, samples
+        .and(route_group(routes_config, RouteGroup::Health))
+        .and(warp::get())
+        .and(access(auth::Scope::HealthRead, None))
+        .and_then(handle_entropy_health);
+
     let get_pk = warp::path!("api" / "vrf" / "pk")
+        .and(route_group(routes_config, RouteGroup::Vrf))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::VrfVerify, None))
+        .and(warp::body::content_length_limit(body.vrf))
         .and(warp::body::json())
         .and_then(handle_get_pk);
 
+    let derive_pk = warp::path!("api" / "vrf" / "pk" / "derive") // path -> pk
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(warp::body::json())
+        .and(warp::any().map(move || master_seed.clone()))
+        .and_then(handle_derive_pk);
+
+    let sk_mnemonic = warp::path!("api" / "vrf" / "sk" / "mnemonic") // sk -> mnemonic
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(warp::body::json())
+        .and_then(handle_sk_mnemonic);
+
+    let sk_mnemonic_restore = warp::path!("api" / "vrf" / "sk" / "mnemonic" / "restore") // mnemonic, pk? -> sk, pk
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(warp::body::json())
+        .and_then(handle_sk_mnemonic_restore);
+
+    let sk_shares = warp::path!("api" / "vrf" / "sk" / "shares") // sk, threshold, shares -> shares, pk
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(warp::body::json())
+        .and_then(handle_sk_shares);
+
+    let sk_shares_recover = warp::path!("api" / "vrf" / "sk" / "shares" / "recover") // shares, pk? -> sk, pk
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(warp::body::json())
+        .and_then(handle_sk_shares_recover);
+
+    let list_keys = warp::path!("api" / "vrf" / "keys") // -> [ id, pk ]
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::get())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(with_keyring.clone())
+        .and_then(handle_list_keys);
+
+    let key_prove = warp::path!("api" / "vrf" / "keys" / String / "prove") // alpha -> Gamma, c, s
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::VrfProve, Some(ratelimit::Budget::Prove)))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(with_keyring.clone())
+        .and(warp::body::json())
+        .and_then(handle_key_prove);
+
     let vrf_verify = warp::path!("api" / "vrf" / "verify") // -> success, bet
+        .and(route_group(routes_config, RouteGroup::Vrf))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::VrfVerify, Some(ratelimit::Budget::Verify)))
+        .and(warp::body::content_length_limit(body.vrf))
         .and(warp::body::json())
         .and_then(handle_vrf_verify);
 
     let utils_hex = warp::path!("api" / "utils" / "hex") // type: be/le -> value
+        .and(route_group(routes_config, RouteGroup::Utils))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
         .and(warp::body::json())
         .and_then(handle_hex);
 
     let utils_int = warp::path!("api" / "utils" / "int") // type: be/le -> value
+        .and(route_group(routes_config, RouteGroup::Utils))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
         .and(warp::body::json())
         .and_then(handle_int);
 
     let utils_sha256 = warp::path!("api" / "utils" / "sha256") // -> value
+        .and(route_group(routes_config, RouteGroup::Utils))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
         .and(warp::body::json())
         .and_then(handle_sha256);
 
     let utils_sha512 = warp::path!("api" / "utils" / "sha512") // -> value
+        .and(route_group(routes_config, RouteGroup::Utils))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
         .and(warp::body::json())
         .and_then(handle_sha512);
 
     let gen_random = warp::path!("api" / "random") // seed, times, limit -> [ new_seed, ticket ]
+        .and(route_group(routes_config, RouteGroup::Random))
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
         .and(warp::body::json())
+        .and(warp::any().map(move || max_iterations))
         .and_then(handle_random);
 
-    let routes = vrf_prove
+    let gen_random_stream = warp::path!("api" / "random" / "stream") // seed, times, limit -> NDJSON [ new_seed, ticket ]
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_stream_iterations))
+        .and_then(handle_random_stream);
+
+    let gen_random_page = warp::path!("api" / "random" / "page") // seed, start, count, limit -> [ new_seed, ticket ], next_start, next_seed
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_iterations))
+        .and(warp::any().map(move || max_stream_iterations))
+        .and_then(handle_random_page);
+
+    let gen_distribution = warp::path!("api" / "random" / "distribution") // seed, draws -> [ typed results ]
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_iterations))
+        .and_then(handle_distribution);
+
+    let gen_shuffle = warp::path!("api" / "random" / "shuffle") // seed, n -> permutation
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_shuffle_items))
+        .and_then(handle_shuffle);
+
+    let gen_shuffle_rank = warp::path!("api" / "random" / "shuffle" / "rank") // seed, n, item -> position
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_stream_iterations))
+        .and_then(handle_shuffle_rank);
+
+    let gen_prize_draw = warp::path!("api" / "random" / "prizes") // seed, tickets, tiers -> winners per tier
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_iterations))
+        .and_then(handle_prize_draw);
+
+    // Participant lists are larger than the other bodies: up to `limits.max_participants` wallets.
+    let merkle_commit = warp::path!("api" / "merkle" / "commit") // participants, data -> root, alpha
+        .and(route_group(routes_config, RouteGroup::Merkle))
+        .and(warp::post())
+        .and(access(auth::Scope::MerkleCommit, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.merkle))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_participants))
+        .and_then(handle_merkle_commit);
+
+    let merkle_prove = warp::path!("api" / "merkle" / "prove") // sk, participants, data -> root, alpha, pk, proof
+        .and(route_group(routes_config, RouteGroup::Merkle))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::VrfProve, Some(ratelimit::Budget::Prove)))
+        .and(warp::body::content_length_limit(body.merkle))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_participants))
+        .and_then(handle_merkle_prove);
+
+    let merkle_proofs = warp::path!("api" / "merkle" / "proofs") // participants, tickets -> root, [ leaf, path ]
+        .and(route_group(routes_config, RouteGroup::Merkle))
+        .and(warp::post())
+        .and(access(auth::Scope::MerkleCommit, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.merkle))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_participants))
+        .and_then(handle_merkle_proofs);
+
+    let merkle_verify = warp::path!("api" / "merkle" / "verify") // root, ticket, participant, path -> valid
+        .and(route_group(routes_config, RouteGroup::Merkle))
+        .and(warp::post())
+        .and(access(auth::Scope::MerkleVerify, Some(ratelimit::Budget::Verify)))
+        .and(warp::body::content_length_limit(body.merkle))
+        .and(warp::body::json())
+        .and_then(handle_merkle_verify);
+
+    let beacon_info = warp::path!("beacon") // -> pk, interval_secs, rounds
+        .and(route_group(routes_config, RouteGroup::Beacon))
+        .and(warp::get())
+        .and(access(auth::Scope::BeaconRead, None))
+        .and(with_beacon.clone())
+        .and_then(handle_beacon_info);
+
+    let beacon_latest = warp::path!("beacon" / "latest") // -> round, prev_hash, alpha, pi, beta, hash
+        .and(route_group(routes_config, RouteGroup::Beacon))
+        .and(warp::get())
+        .and(access(auth::Scope::BeaconRead, None))
+        .and(with_beacon.clone())
+        .and_then(handle_beacon_latest);
+
+    let beacon_round = warp::path!("beacon" / "round" / u64) // -> round, prev_hash, alpha, pi, beta, hash
+        .and(route_group(routes_config, RouteGroup::Beacon))
+        .and(warp::get())
+        .and(access(auth::Scope::BeaconRead, None))
+        .and(with_beacon.clone())
+        .and_then(handle_beacon_round);
+
+    let drand_verify = warp::path!("api" / "drand" / "verify") // round, randomness, signature, previous_signature -> round, randomness
+        .and(route_group(routes_config, RouteGroup::Drand))
+        .and(warp::post())
+        .and(access(auth::Scope::DrandVerify, Some(ratelimit::Budget::Verify)))
+        .and(warp::body::content_length_limit(body.drand))
+        .and(with_drand.clone())
+        .and(warp::body::json())
+        .and_then(handle_drand_verify);
+
+    let drand_alpha = warp::path!("api" / "drand" / "alpha") // drand? | round?, data -> round, randomness, alpha
+        .and(route_group(routes_config, RouteGroup::Drand))
+        .and(warp::post())
+        .and(access(auth::Scope::DrandVerify, Some(ratelimit::Budget::Verify)))
+        .and(warp::body::content_length_limit(body.drand))
+        .and(with_drand.clone())
+        .and(warp::body::json())
+        .and_then(handle_drand_alpha);
+
+    let admin_self_test = warp::path!("api" / "admin" / "selftest") // source, samples -> [ test, p_value, passed ]
+        .and(route_group(routes_config, RouteGroup::Admin))
+        .and(warp::post())
+        .and(access(auth::Scope::AdminSelfTest, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.admin))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_stream_iterations))
+        .and_then(handle_self_test);
+
+    let admin_rate_limit = warp::path!("api" / "admin" / "ratelimit") // -> enabled, clients, [ budget, allowed, limited ]
+        .and(route_group(routes_config, RouteGroup::Admin))
+        .and(warp::get())
+        .and(access(auth::Scope::AdminRead, None))
+        .and(warp::any().map({
+            let rate_limiter = rate_limiter.clone();
+            move || rate_limiter.clone()
+        }))
+        .and_then(handle_rate_limit);
+
+    let metrics = warp::path!("metrics") // -> Prometheus text format
+        .and(route_group(routes_config, RouteGroup::Metrics))
+        .and(warp::get())
+        .and(access(auth::Scope::MetricsRead, None))
+        .and(warp::any().map(move || rate_limiter.clone()))
+        .and_then(handle_metrics);
+
+    let openapi = warp::path!("openapi.json") // -> OpenAPI 3 document
+        .and(route_group(routes_config, RouteGroup::Docs))
+        .and(warp::get())
+        .and(access(auth::Scope::DocsRead, None))
+        .and_then(handle_openapi);
+
+    // Swagger UI assets are compiled in by `utoipa-swagger-ui`.
+    let swagger_config = Arc::new(utoipa_swagger_ui::Config::from("/openapi.json"));
+    let swagger_ui = warp::path("docs") // -> Swagger UI for /openapi.json
+        .and(route_group(routes_config, RouteGroup::Docs))
+        .and(warp::get())
+        .and(access(auth::Scope::DocsRead, None))
+        .and(warp::path::full())
+        .and(warp::path::tail())
+        .and(warp::any().map(move || swagger_config.clone()))
+        .and_then(handle_swagger_ui);
+
+    // `/api/v2`, see `v2`: the groups, scopes and budgets of the matching v1 routes, with
+    // v2 error bodies. The prefix is matched outside `recover`, so other paths fall through.
+    let v2_vrf_prove = warp::path!("vrf" / "prove") // sk_hex, alpha_hex -> gamma_hex, c_hex, s_hex, pi_hex
+        .and(route_group(routes_config, RouteGroup::Vrf))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::VrfProve, Some(ratelimit::Budget::Prove)))
+        .and(warp::body::content_length_limit(body.vrf))
+        .and(warp::body::json())
+        .and_then(handle_v2_vrf_prove);
+
+    let v2_vrf_verify = warp::path!("vrf" / "verify") // pk_hex, alpha_hex, pi_hex -> beta_hex
+        .and(route_group(routes_config, RouteGroup::Vrf))
+        .and(warp::post())
+        .and(access(auth::Scope::VrfVerify, Some(ratelimit::Budget::Verify)))
+        .and(warp::body::content_length_limit(body.vrf))
+        .and(warp::body::json())
+        .and_then(handle_v2_vrf_verify);
+
+    let v2_get_pk = warp::path!("vrf" / "pk") // sk_hex -> pk_hex
+        .and(route_group(routes_config, RouteGroup::Vrf))
+        .and(warp::post())
+        .and(access(auth::Scope::VrfVerify, None))
+        .and(warp::body::content_length_limit(body.vrf))
+        .and(warp::body::json())
+        .and_then(handle_v2_get_pk);
+
+    let v2_generate_sk = warp::path!("vrf" / "sk") // -> sk_hex
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and_then(handle_v2_generate_sk);
+
+    let v2_list_keys = warp::path!("vrf" / "keys") // ?start, count -> [ id, pk_hex ], page
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::get())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::KeysAdmin, None))
+        .and(warp::query::<v2::PageQuery>())
+        .and(with_keyring.clone())
+        .and_then(handle_v2_list_keys);
+
+    let v2_key_prove = warp::path!("vrf" / "keys" / String / "prove") // alpha_hex -> gamma_hex, c_hex, s_hex, pi_hex
+        .and(route_group(routes_config, RouteGroup::Keys))
+        .and(warp::post())
+        .and(client_cert(client_auth))
+        .and(access(auth::Scope::VrfProve, Some(ratelimit::Budget::Prove)))
+        .and(warp::body::content_length_limit(body.keys))
+        .and(with_keyring.clone())
+        .and(warp::body::json())
+        .and_then(handle_v2_key_prove);
+
+    let v2_int_to_hex = warp::path!("utils" / "int-to-hex") // value_decimal, byte_order -> value_hex
+        .and(route_group(routes_config, RouteGroup::Utils))
+        .and(warp::post())
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
+        .and(warp::body::json())
+        .and_then(handle_v2_int_to_hex);
+
+    let v2_hex_to_int = warp::path!("utils" / "hex-to-int") // value_hex, byte_order -> value_decimal
+        .and(route_group(routes_config, RouteGroup::Utils))
+        .and(warp::post())
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
+        .and(warp::body::json())
+        .and_then(handle_v2_hex_to_int);
+
+    let v2_sha256 = warp::path!("utils" / "sha256") // data_hex -> digest_hex
+        .and(route_group(routes_config, RouteGroup::Utils))
+        .and(warp::post())
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
+        .and(warp::body::json())
+        .and_then(handle_v2_sha256);
+
+    let v2_sha512 = warp::path!("utils" / "sha512") // data_hex -> digest_hex
+        .and(route_group(routes_config, RouteGroup::Utils))
+        .and(warp::post())
+        .and(access(auth::Scope::Utils, None))
+        .and(warp::body::content_length_limit(body.utils))
+        .and(warp::body::json())
+        .and_then(handle_v2_sha512);
+
+    let v2_tickets = warp::path!("random" / "tickets") // seed_hex, limit, start, count -> [ index, ticket, random_result_hex ], next_seed_hex, page
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_iterations))
+        .and(warp::any().map(move || max_stream_iterations))
+        .and_then(handle_v2_tickets);
+
+    let v2_distribution = warp::path!("random" / "distribution") // seed_hex, draws -> [ typed results ]
+        .and(route_group(routes_config, RouteGroup::Random))
+        .and(warp::post())
+        .and(access(auth::Scope::RngDraw, Some(ratelimit::Budget::Random)))
+        .and(warp::body::content_length_limit(body.random))
+        .and(warp::body::json())
+        .and(warp::any().map(move || max_iterations))
+        .and_then(handle_v2_distribution);
+
+    let api_v2 = warp::path!("api" / "v2" / ..).and(
+        v2_vrf_prove
+            .or(v2_vrf_verify)
+            .or(v2_get_pk)
+            .or(v2_generate_sk)
+            .or(v2_list_keys)
+            .or(v2_key_prove)
+            .or(v2_int_to_hex)
+            .or(v2_hex_to_int)
+            .or(v2_sha256)
+            .or(v2_sha512)
+            .or(v2_tickets)
+            .or(v2_distribution)
+            .recover(handle_v2_rejection)
+    );
+
+    let routes = api_v2
+        .or(vrf_prove)
         .or(generate_sk)
+        .or(entropy_health)
         .or(get_pk)
+        .or(derive_pk)
+        .or(sk_mnemonic)
+        .or(sk_mnemonic_restore)
+        .or(sk_shares)
+        .or(sk_shares_recover)
+        .or(list_keys)
+        .or(key_prove)
         .or(vrf_verify)
         .or(utils_hex)
         .or(utils_int)
         .or(utils_sha256)
         .or(utils_sha512)
         .or(gen_random)
-        .recover(handle_rejection)
-        .with(warp::log("api"));
+        .or(gen_random_stream)
+        .or(gen_random_page)
+        .or(gen_distribution)
+        .or(gen_shuffle)
+        .or(gen_shuffle_rank)
+        .or(gen_prize_draw)
+        .or(merkle_commit)
+        .or(merkle_prove)
+        .or(merkle_proofs)
+        .or(merkle_verify)
+        .or(admin_self_test)
+        .or(admin_rate_limit)
+        .or(beacon_info)
+        .or(beacon_latest)
+        .or(beacon_round)
+        .or(drand_verify)
+        .or(drand_alpha)
+        .or(metrics)
+        .or(openapi)
+        .or(swagger_ui)
+        .recover(move |err| handle_rejection(err, max_iterations))
+        .with(warp::log::custom(logging::log_request))
+        .with(warp::log::custom(metrics::observe_request));
+
+    // Request ids and the request log wrap every route, see `logging`.
+    let service = warp::service(routes);
+    let service = warp::hyper::service::service_fn(move |request| logging::serve_request(service.clone(), request));
+
+    let acceptor = match &config.server.tls {
+        Some(tls_config) => {
+            let resolver = match tls::CertResolver::new(&tls_config.cert, &tls_config.key) {
+                Ok(resolver) => Arc::new(resolver),
+                Err(err) => {
+                    eprintln!("server.tls is invalid: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            let acceptor = match tls::acceptor(resolver.clone(), tls_config.client_ca.as_deref()) {
+                Ok(acceptor) => acceptor,
+                Err(err) => {
+                    eprintln!("server.tls.client_ca is invalid: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            let reload_secs = tls_config.reload_secs;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(reload_secs));
+                loop {
+                    interval.tick().await;
+                    match resolver.reload_if_changed() {
+                        Ok(true) => log::info!(target: "api", "TLS certificate reloaded"),
+                        Ok(false) => {},
+                        Err(err) => log::error!(target: "api", "TLS certificate could not be reloaded, keeping the current one: {}", err),
+                    }
+                }
+            });
+            println!("🚀 Server started successfully on https://{}", config.server.listen);
+            Some(acceptor)
+        },
+        None => {
+            println!("🚀 Server started successfully on {}", config.server.listen);
+            None
+        }
+    };
+
+    // The gRPC service shares the acceptor, and so its certificate reloads and mTLS.
+    if let Some(grpc_listen) = config.grpc.listen {
+        let listener = match tokio::net::TcpListener::bind(grpc_listen).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                eprintln!("grpc.listen could not be bound: {}", err);
+                std::process::exit(1);
+            }
+        };
+        let (acceptor, reflection) = (acceptor.clone(), config.grpc.reflection);
+        tokio::spawn(async move {
+            if let Err(err) = grpc::serve(grpc_service, listener, acceptor, reflection).await {
+                log::error!(target: "api", "gRPC server stopped: {}", err);
+            }
+        });
+        let scheme = if config.server.tls.is_some() { "https" } else { "http" };
+        println!("🚀 gRPC server started successfully on {}://{}", scheme, grpc_listen);
+    }
+
+    if let Err(err) = tls::serve(service, config.server.listen, acceptor).await {
+        eprintln!("server.listen could not be bound: {}", err);
+        std::process::exit(1);
+    }
+}
+
+// Requires the `tls::ClientCert` extension set for verified mTLS clients.
+fn client_cert(required: bool) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::ext::optional::<tls::ClientCert>()
+        .and_then(move |client_cert: Option<tls::ClientCert>| async move {
+            if !required || client_cert.is_some() { Ok(()) } else { Err(warp::reject::custom(tls::ClientCertRequired)) }
+        })
+        .untuple_one()
+}
 
-    println!("🚀 Server started successfully");
-    warp::serve(routes).run(([0, 0, 0, 0], 3111)).await;
+// Rejects requests whose `X-Api-Key` or bearer token does not grant `scope`, then takes a
+// token from the client's `budget`, if any.
+fn access(authenticator: Arc<auth::Authenticator>, rate_limiter: Arc<ratelimit::RateLimiter>, scope: auth::Scope, budget: Option<ratelimit::Budget>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and(warp::ext::optional::<tls::PeerAddr>())
+        .and_then(move |api_key: Option<String>, authorization: Option<String>, forwarded_for: Option<String>, peer: Option<tls::PeerAddr>| {
+            let (authenticator, rate_limiter) = (authenticator.clone(), rate_limiter.clone());
+            async move {
+                let principal = match authenticator.authorize(api_key.as_deref(), authorization.as_deref(), scope) {
+                    Ok(principal) => principal,
+                    Err(auth::AuthInputError::CredentialsInvalid(_)) => return Err(warp::reject::custom(auth::CredentialsInvalid)),
+                    Err(auth::AuthInputError::ScopeMissing(_)) => return Err(warp::reject::custom(auth::ScopeMissing)),
+                };
+                metrics::observe_principal(principal.as_ref().map(|principal| principal.id.as_str()));
+                if let Some(budget) = budget {
+                    let remote = peer.map(|peer| peer.0.ip());
+                    let client = rate_limiter.client(principal.as_ref(), remote, forwarded_for.as_deref());
+                    rate_limiter.check(budget, &client, std::time::Instant::now()).map_err(warp::reject::custom)?;
+                }
+                Ok(())
+            }
+        })
+        .untuple_one()
 }
 
+// Routes of a disabled group answer 404 as if they did not exist.
+fn route_group(routes_config: &RoutesConfig, group: RouteGroup) -> impl Filter<Extract = (), Error = Rejection> + Clone + use<> {
+    let enabled = routes_config.enabled(group);
+    warp::any()
+        .and_then(move || async move {
+            if enabled { Ok(()) } else { Err(warp::reject::not_found()) }
+        })
+        .untuple_one()
+}
+
+#[utoipa::path(
+    post, path = "/api/vrf/prove", tag = "vrf",
+    request_body(content = ecvrf::VRFInputs, example = json!({"sk": "0101010101010101010101010101010101010101010101010101010101010101", "alpha": "68656c6c6f"})),
+    responses(
+        (status = 200, description = "Proof of `alpha` under `sk`.", body = SuccessMessage<ecvrf::VRFOutput>),
+        (status = 400, description = "`sk` or `alpha` is invalid.", body = ErrorMessage),
+    ),
+)]
 async fn handle_vrf_prove(vrf_inputs: ecvrf::VRFInputs) -> Result<impl Reply, Rejection> {
-    match ecvrf::api_vrf_prove(vrf_inputs) {
+    match metrics::VRF_PROVE_DURATION.observe_closure_duration(|| ecvrf::api_vrf_prove(vrf_inputs)) {
         Ok(vrf_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: vrf_output })),
         Err(ecvrf::VRFInputError::AlphaInvalid(_)) => Err(warp::reject::custom(ecvrf::AlphaInvalid)),
         Err(ecvrf::VRFInputError::SKInvalid(_)) => Err(warp::reject::custom(ecvrf::SKInvalid)),
-        Err(_err) => Err(warp::reject::custom(UnknownError))
     }
 }
 
+#[utoipa::path(
+    post, path = "/api/vrf/verify", tag = "vrf",
+    request_body(content = ecvrf::VRFVerifyInputs, example = json!({"pk": "3e440469a098036d89ffb2d77a4542928f2f74c2b5769da7480736ace829dc10", "alpha": "68656c6c6f", "Gamma": "008a58b8fb96b282311dd955146e4e87cf7c6632942360deb8955a8dbc0d1672", "c": "b7e236fb0c37e810923059f8c98f0678", "s": "7d8324f7779c9bd1df52d24bfbce44e336a5718db8a301df4cd07fbd4ce0f706"})),
+    responses(
+        (status = 200, description = "The proof is valid; `beta` is its output.", body = SuccessMessage<ecvrf::VRFVerifyOutput>),
+        (status = 400, description = "`pk`, `alpha` or the proof is invalid.", body = ErrorMessage),
+    ),
+)]
 async fn handle_vrf_verify(vrf_verify_inputs: ecvrf::VRFVerifyInputs) -> Result<impl Reply, Rejection> {
-    match ecvrf::api_vrf_verify(vrf_verify_inputs) {
+    match metrics::VRF_VERIFY_DURATION.observe_closure_duration(|| ecvrf::api_vrf_verify(vrf_verify_inputs)) {
         Ok(vrf_verify_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: vrf_verify_output })),
         Err(ecvrf::VRFVerifyInputError::AlphaInvalid(_)) => Err(warp::reject::custom(ecvrf::AlphaInvalid)),
         Err(ecvrf::VRFVerifyInputError::PKInvalid(_)) => Err(warp::reject::custom(ecvrf::PKInvalid)),
@@ -132,10 +848,62 @@ async fn handle_vrf_verify(vrf_verify_inputs: ecvrf::VRFVerifyInputs) -> Result<
     }
 }
 
-async fn handle_generate_sk() -> Result<impl Reply, Infallible> {
-    Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: ecvrf::api_generate_sk() }))
+#[utoipa::path(
+    get, path = "/api/vrf/sk/new", tag = "vrf",
+    responses(
+        (status = 200, description = "A new secret key from the health-tested entropy source.", body = SuccessMessage<ecvrf::SKOutput>),
+        (status = 503, description = "The entropy source failed its health tests.", body = ErrorMessage),
+    ),
+)]
+async fn handle_generate_sk() -> Result<impl Reply, Rejection> {
+    match ecvrf::api_generate_sk() {
+        Ok(sk_output) => {
+            metrics::KEYS_GENERATED.inc();
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: sk_output }))
+        },
+        Err(err) => Err(warp::reject::custom(err))
+    }
+}
+
+async fn handle_rate_limit(rate_limiter: Arc<ratelimit::RateLimiter>) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: rate_limiter.status() }))
+}
+
+async fn handle_metrics(rate_limiter: Arc<ratelimit::RateLimiter>) -> Result<impl Reply, Rejection> {
+    match metrics::api_metrics(&rate_limiter.status()) {
+        Ok(exposition) => Ok(warp::reply::with_header(exposition, "content-type", metrics::CONTENT_TYPE)),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
+}
+
+async fn handle_openapi() -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&ApiDoc::openapi()))
+}
+
+async fn handle_swagger_ui(full_path: warp::path::FullPath, tail: warp::path::Tail, swagger_config: Arc<utoipa_swagger_ui::Config<'static>>) -> Result<warp::reply::Response, Rejection> {
+    // The page loads its assets relative to `/docs/`.
+    if full_path.as_str() == "/docs" {
+        return Ok(warp::redirect::found(warp::http::Uri::from_static("/docs/")).into_response());
+    }
+    match utoipa_swagger_ui::serve(tail.as_str(), swagger_config) {
+        Ok(Some(file)) => Ok(warp::reply::with_header(file.bytes.into_owned(), "content-type", file.content_type).into_response()),
+        Ok(None) => Err(warp::reject::not_found()),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
 }
 
+async fn handle_entropy_health() -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: entropy::status() }))
+}
+
+#[utoipa::path(
+    post, path = "/api/vrf/pk", tag = "vrf",
+    request_body(content = ecvrf::PKInputs, example = json!({"sk": "0101010101010101010101010101010101010101010101010101010101010101"})),
+    responses(
+        (status = 200, description = "Public key of `sk`.", body = SuccessMessage<ecvrf::PKOutput>),
+        (status = 400, description = "`sk` is invalid.", body = ErrorMessage),
+    ),
+)]
 async fn handle_get_pk(pk_inputs: ecvrf::PKInputs) -> Result<impl Reply, Rejection> {
     match ecvrf::api_get_pk(pk_inputs) {
         Ok(vrf_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: vrf_output })),
@@ -144,6 +912,77 @@ async fn handle_get_pk(pk_inputs: ecvrf::PKInputs) -> Result<impl Reply, Rejecti
     }
 }
 
+async fn handle_derive_pk(derive_inputs: hdkey::DerivePKInputs, master_seed: Arc<Option<Vec<u8>>>) -> Result<impl Reply, Rejection> {
+    match hdkey::api_derive_pk(derive_inputs, master_seed.as_deref()) {
+        Ok(derive_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: derive_output })),
+        Err(hdkey::DeriveInputError::MasterSeedMissing(_)) => Err(warp::reject::custom(hdkey::MasterSeedMissing)),
+        Err(hdkey::DeriveInputError::PathInvalid(_)) => Err(warp::reject::custom(hdkey::PathInvalid)),
+        Err(hdkey::DeriveInputError::DerivationFailed(_)) => Err(warp::reject::custom(hdkey::DerivationFailed)),
+    }
+}
+
+fn reject_backup_error(err: backup::BackupInputError) -> Rejection {
+    match err {
+        backup::BackupInputError::SKInvalid(_) => warp::reject::custom(ecvrf::SKInvalid),
+        backup::BackupInputError::PKInvalid(_) => warp::reject::custom(ecvrf::PKInvalid),
+        backup::BackupInputError::MnemonicInvalid(_) => warp::reject::custom(backup::MnemonicInvalid),
+        backup::BackupInputError::SharesInvalid(_) => warp::reject::custom(backup::SharesInvalid),
+        backup::BackupInputError::ThresholdInvalid(_) => warp::reject::custom(backup::ThresholdInvalid),
+        backup::BackupInputError::PKMismatch(_) => warp::reject::custom(backup::PKMismatch),
+    }
+}
+
+async fn handle_sk_mnemonic(mnemonic_inputs: backup::MnemonicInputs) -> Result<impl Reply, Rejection> {
+    match backup::api_sk_to_mnemonic(mnemonic_inputs) {
+        Ok(mnemonic_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: mnemonic_output })),
+        Err(err) => Err(reject_backup_error(err))
+    }
+}
+
+async fn handle_sk_mnemonic_restore(restore_inputs: backup::RestoreInputs) -> Result<impl Reply, Rejection> {
+    match backup::api_sk_from_mnemonic(restore_inputs) {
+        Ok(recovered_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: recovered_output })),
+        Err(err) => Err(reject_backup_error(err))
+    }
+}
+
+async fn handle_sk_shares(split_inputs: backup::SplitInputs) -> Result<impl Reply, Rejection> {
+    match backup::api_split_sk(split_inputs) {
+        Ok(split_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: split_output })),
+        Err(err) => Err(reject_backup_error(err))
+    }
+}
+
+async fn handle_sk_shares_recover(recover_inputs: backup::RecoverInputs) -> Result<impl Reply, Rejection> {
+    match backup::api_recover_sk(recover_inputs) {
+        Ok(recovered_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: recovered_output })),
+        Err(err) => Err(reject_backup_error(err))
+    }
+}
+
+async fn handle_list_keys(keyring: Arc<keystore::Keyring>) -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: keystore::api_list_keys(&keyring) }))
+}
+
+async fn handle_key_prove(id: String, keyring: Arc<keystore::Keyring>, prove_inputs: keystore::KeyProveInputs) -> Result<impl Reply, Rejection> {
+    match metrics::VRF_PROVE_DURATION.observe_closure_duration(|| keystore::api_key_prove(&keyring, &id, prove_inputs)) {
+        Ok(vrf_output) => {
+            metrics::KEY_PROOFS.with_label_values(&[id.as_str()]).inc();
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: vrf_output }))
+        },
+        Err(keystore::KeyProveInputError::KeyNotFound(_)) => Err(warp::reject::custom(keystore::KeyNotFound)),
+        Err(keystore::KeyProveInputError::AlphaInvalid(_)) => Err(warp::reject::custom(ecvrf::AlphaInvalid)),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/utils/hex", tag = "utils",
+    request_body(content = utils::ConvertInputs, example = json!({"value": "1000", "value_type": "Be"})),
+    responses(
+        (status = 200, description = "Hex encoding of the decimal `value`.", body = SuccessMessage<utils::ConvertOutput>),
+        (status = 400, description = "`value` is not a decimal integer.", body = ErrorMessage),
+    ),
+)]
 async fn handle_hex(convert_inputs: utils::ConvertInputs) -> Result<impl Reply, Rejection> {
     match utils::api_convert_to_hex(convert_inputs) {
         Ok(convert_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: convert_output })),
@@ -152,6 +991,14 @@ async fn handle_hex(convert_inputs: utils::ConvertInputs) -> Result<impl Reply,
     }
 }
 
+#[utoipa::path(
+    post, path = "/api/utils/int", tag = "utils",
+    request_body(content = utils::ConvertInputs, example = json!({"value": "03e8", "value_type": "Be"})),
+    responses(
+        (status = 200, description = "Decimal value of the hex `value`.", body = SuccessMessage<utils::ConvertOutput>),
+        (status = 400, description = "`value` is not a hex string.", body = ErrorMessage),
+    ),
+)]
 async fn handle_int(convert_inputs: utils::ConvertInputs) -> Result<impl Reply, Rejection> {
     match utils::api_convert_to_int(convert_inputs) {
         Ok(convert_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: convert_output })),
@@ -160,6 +1007,14 @@ async fn handle_int(convert_inputs: utils::ConvertInputs) -> Result<impl Reply,
     }
 }
 
+#[utoipa::path(
+    post, path = "/api/utils/sha256", tag = "utils",
+    request_body(content = utils::ShaInputs, example = json!({"value": "68656c6c6f"})),
+    responses(
+        (status = 200, description = "SHA-256 of the hex `value`.", body = SuccessMessage<utils::ShaOutput>),
+        (status = 400, description = "`value` is not a hex string.", body = ErrorMessage),
+    ),
+)]
 async fn handle_sha256(sha_inputs: utils::ShaInputs) -> Result<impl Reply, Rejection> {
     match utils::api_sha256(sha_inputs) {
         Ok(sha_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: sha_output })),
@@ -168,6 +1023,14 @@ async fn handle_sha256(sha_inputs: utils::ShaInputs) -> Result<impl Reply, Rejec
     }
 }
 
+#[utoipa::path(
+    post, path = "/api/utils/sha512", tag = "utils",
+    request_body(content = utils::ShaInputs, example = json!({"value": "68656c6c6f"})),
+    responses(
+        (status = 200, description = "SHA-512 of the hex `value`.", body = SuccessMessage<utils::ShaOutput>),
+        (status = 400, description = "`value` is not a hex string.", body = ErrorMessage),
+    ),
+)]
 async fn handle_sha512(sha_inputs: utils::ShaInputs) -> Result<impl Reply, Rejection> {
     match utils::api_sha512(sha_inputs) {
         Ok(sha_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: sha_output })),
@@ -176,61 +1039,829 @@ async fn handle_sha512(sha_inputs: utils::ShaInputs) -> Result<impl Reply, Rejec
     }
 }
 
-async fn handle_random(rng_inputs: rng::RngInputs) -> Result<impl Reply, Rejection> {
-    if rng_inputs.iterations > 3100 {
-        return Err(warp::reject::custom(rng::IterationsExceeded));
+fn reject_rng_error(err: rng::RngInputError) -> Rejection {
+    match err {
+        rng::RngInputError::SeedInvalid(_) => warp::reject::custom(rng::SeedInvalid),
+        rng::RngInputError::IterationsExceeded(_) => warp::reject::custom(rng::IterationsExceeded),
+        rng::RngInputError::DistributionInvalid(_) => warp::reject::custom(rng::DistributionInvalid),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/random", tag = "random",
+    request_body(content = rng::RngInputs, example = json!({"initial_seed": "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d", "iterations": 3, "limit": 100})),
+    responses(
+        (status = 200, description = "`iterations` tickets in [0, `limit`).", body = SuccessMessage<rng::RngOutput>),
+        (status = 400, description = "The seed is invalid or `iterations` exceeds `limits.max_iterations`.", body = ErrorMessage),
+    ),
+)]
+async fn handle_random(rng_inputs: rng::RngInputs, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    match rng::api_random(rng_inputs, max_iterations) {
+        Ok(rng_output) => {
+            metrics::DRAW_SIZE.with_label_values(&["random"]).observe(rng_output.draws() as f64);
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: rng_output }))
+        },
+        Err(err) => Err(reject_rng_error(err))
+    }
+}
+
+// Results are serialized lazily in batches, so hyper only pulls the chain as fast as the client reads.
+const STREAM_BATCH_SIZE: usize = 256;
+
+#[utoipa::path(
+    post, path = "/api/random/stream", tag = "random",
+    request_body(content = rng::RngInputs, example = json!({"initial_seed": "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d", "iterations": 3, "limit": 100, "generator": "chacha20"})),
+    responses(
+        (status = 200, description = "One result per line; the version and generator are in `X-Rng-Version` and `X-Rng-Generator`.", content_type = "application/x-ndjson", body = rng::RngResult),
+        (status = 400, description = "The seed is invalid or `iterations` exceeds `limits.max_stream_iterations`.", body = ErrorMessage),
+    ),
+)]
+async fn handle_random_stream(rng_inputs: rng::RngInputs, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    let rng_stream = match rng::api_random_stream(rng_inputs, max_iterations) {
+        Ok(rng_stream) => rng_stream,
+        Err(err) => return Err(reject_rng_error(err))
+    };
+
+    let (version, generator, mut rng_iter) = (rng_stream.version, rng_stream.generator, rng_stream.results);
+    // `Take` bounds the chain, so the upper size hint is the requested iteration count.
+    let draws = rng_iter.size_hint().1.unwrap_or_default();
+    metrics::DRAW_SIZE.with_label_values(&["stream"]).observe(draws as f64);
+    let batches = stream::iter(std::iter::from_fn(move || {
+        let mut batch = String::new();
+        for rng_result in rng_iter.by_ref().take(STREAM_BATCH_SIZE) {
+            batch.push_str(&serde_json::to_string(&rng_result).expect("RngResult serializes to JSON"));
+            batch.push('\n');
+        }
+        (!batch.is_empty()).then_some(Ok::<String, Infallible>(batch))
+    }));
+
+    let response = warp::reply::with_header(
+        warp::reply::Response::new(warp::hyper::Body::wrap_stream(batches)),
+        "content-type",
+        "application/x-ndjson",
+    );
+    let response = warp::reply::with_header(response, "x-rng-version", version.to_string());
+    Ok(warp::reply::with_header(response, "x-rng-generator", generator.to_string()))
+}
+
+#[utoipa::path(
+    post, path = "/api/random/page", tag = "random",
+    request_body(content = rng::RngPageInputs, example = json!({"initial_seed": "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d", "start": 2, "count": 3, "limit": 100})),
+    responses(
+        (status = 200, description = "Tickets `start` to `start + count` of the chain.", body = SuccessMessage<rng::RngPageOutput>),
+        (status = 400, description = "The seed is invalid or the page exceeds the iteration limits.", body = ErrorMessage),
+    ),
+)]
+// A page may skip up to `limits.max_stream_iterations` blocks, so it runs on the blocking pool.
+async fn handle_random_page(page_inputs: rng::RngPageInputs, max_count: usize, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    let page_result = tokio::task::spawn_blocking(move || rng::api_random_page(page_inputs, max_count, max_iterations))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match page_result {
+        Ok(page_output) => {
+            metrics::DRAW_SIZE.with_label_values(&["page"]).observe(page_output.draws() as f64);
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: page_output }))
+        },
+        Err(err) => Err(reject_rng_error(err))
+    }
+}
+
+fn reject_shuffle_error(err: shuffle::ShuffleInputError) -> Rejection {
+    match err {
+        shuffle::ShuffleInputError::SeedInvalid(_) => warp::reject::custom(rng::SeedInvalid),
+        shuffle::ShuffleInputError::IterationsExceeded(_) => warp::reject::custom(rng::IterationsExceeded),
+        shuffle::ShuffleInputError::ItemInvalid(_) => warp::reject::custom(shuffle::ItemInvalid),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/random/distribution", tag = "random",
+    request_body(content = rng::DistributionInputs, example = json!({"initial_seed": "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d", "draws": [{"type": "range", "min": -5, "max": 5}, {"type": "float", "decimals": 4}, {"type": "dice", "sides": 6, "count": 2}, {"type": "coin", "p": "0.25"}, {"type": "table", "outcomes": [{"value": "gold", "weight": 1}, {"value": "silver", "weight": 3}]}]})),
+    responses(
+        (status = 200, description = "One typed result per draw, in request order.", body = SuccessMessage<rng::DistributionOutput>),
+        (status = 400, description = "The seed or a draw is invalid, or the draws exceed `limits.max_iterations` blocks.", body = ErrorMessage),
+    ),
+)]
+async fn handle_distribution(distribution_inputs: rng::DistributionInputs, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    match rng::api_distribution(distribution_inputs, max_iterations) {
+        Ok(distribution_output) => {
+            metrics::DRAW_SIZE.with_label_values(&["distribution"]).observe(distribution_output.draws() as f64);
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: distribution_output }))
+        },
+        Err(err) => Err(reject_rng_error(err))
+    }
+}
+
+// Shuffles walk up to `n` generator blocks, so they run on the blocking pool.
+async fn handle_shuffle(shuffle_inputs: shuffle::ShuffleInputs, max_items: usize) -> Result<impl Reply, Rejection> {
+    let shuffle_result = tokio::task::spawn_blocking(move || shuffle::api_shuffle(shuffle_inputs, max_items))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match shuffle_result {
+        Ok(shuffle_output) => {
+            metrics::DRAW_SIZE.with_label_values(&["shuffle"]).observe(shuffle_output.draws() as f64);
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: shuffle_output }))
+        },
+        Err(err) => Err(reject_shuffle_error(err))
+    }
+}
+
+async fn handle_shuffle_rank(rank_inputs: shuffle::RankInputs, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    let rank_result = tokio::task::spawn_blocking(move || shuffle::api_rank(rank_inputs, max_iterations))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match rank_result {
+        Ok(rank_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: rank_output })),
+        Err(err) => Err(reject_shuffle_error(err))
+    }
+}
+
+async fn handle_prize_draw(prize_inputs: prize::PrizeInputs, max_winners: usize) -> Result<impl Reply, Rejection> {
+    match prize::api_prize_draw(prize_inputs, max_winners) {
+        Ok(prize_output) => {
+            metrics::DRAW_SIZE.with_label_values(&["prizes"]).observe(prize_output.draws() as f64);
+            Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: prize_output }))
+        },
+        Err(prize::PrizeInputError::SeedInvalid(_)) => Err(warp::reject::custom(rng::SeedInvalid)),
+        Err(prize::PrizeInputError::IterationsExceeded(_)) => Err(warp::reject::custom(rng::IterationsExceeded)),
+        Err(prize::PrizeInputError::TiersInvalid(_)) => Err(warp::reject::custom(prize::TiersInvalid)),
+    }
+}
+
+fn reject_merkle_error(err: merkle::MerkleInputError) -> Rejection {
+    match err {
+        merkle::MerkleInputError::ParticipantsInvalid(_) => warp::reject::custom(merkle::ParticipantsInvalid),
+        merkle::MerkleInputError::TicketInvalid(_) => warp::reject::custom(merkle::TicketInvalid),
+        merkle::MerkleInputError::HexStringInvalid(_) => warp::reject::custom(utils::HexStringInvalid),
+        merkle::MerkleInputError::SKInvalid(_) => warp::reject::custom(ecvrf::SKInvalid),
+    }
+}
+
+// Building a tree hashes up to `max_participants` leaves, so the tree routes run on the blocking pool.
+async fn handle_merkle_commit(commit_inputs: merkle::CommitInputs, max_participants: usize) -> Result<impl Reply, Rejection> {
+    let commit_result = tokio::task::spawn_blocking(move || merkle::api_commit(commit_inputs, max_participants))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match commit_result {
+        Ok(commit_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: commit_output })),
+        Err(err) => Err(reject_merkle_error(err))
+    }
+}
+
+async fn handle_merkle_prove(prove_inputs: merkle::ProveInputs, max_participants: usize) -> Result<impl Reply, Rejection> {
+    let prove_result = tokio::task::spawn_blocking(move || merkle::api_prove(prove_inputs, max_participants))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match prove_result {
+        Ok(prove_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: prove_output })),
+        Err(err) => Err(reject_merkle_error(err))
+    }
+}
+
+async fn handle_merkle_proofs(proofs_inputs: merkle::ProofsInputs, max_participants: usize) -> Result<impl Reply, Rejection> {
+    let proofs_result = tokio::task::spawn_blocking(move || merkle::api_proofs(proofs_inputs, max_participants))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match proofs_result {
+        Ok(proofs_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: proofs_output })),
+        Err(err) => Err(reject_merkle_error(err))
+    }
+}
+
+async fn handle_merkle_verify(verify_inputs: merkle::VerifyInputs) -> Result<impl Reply, Rejection> {
+    match merkle::api_verify(verify_inputs) {
+        Ok(verify_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: verify_output })),
+        Err(err) => Err(reject_merkle_error(err))
+    }
+}
+
+// The battery generates up to `max_samples` blocks or VRF proofs, so it runs on the blocking pool.
+async fn handle_self_test(self_test_inputs: selftest::SelfTestInputs, max_samples: usize) -> Result<impl Reply, Rejection> {
+    let self_test_result = tokio::task::spawn_blocking(move || selftest::api_self_test(self_test_inputs, max_samples))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match self_test_result {
+        Ok(report) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: report })),
+        Err(selftest::SelfTestInputError::SeedInvalid(_)) => Err(warp::reject::custom(rng::SeedInvalid)),
+        Err(selftest::SelfTestInputError::IterationsExceeded(_)) => Err(warp::reject::custom(rng::IterationsExceeded)),
+        Err(selftest::SelfTestInputError::SamplesInvalid(_)) => Err(warp::reject::custom(selftest::SamplesInvalid)),
+    }
+}
+
+fn reject_beacon_error(err: beacon::BeaconInputError) -> Rejection {
+    match err {
+        beacon::BeaconInputError::BeaconDisabled(_) => warp::reject::custom(beacon::BeaconDisabled),
+        beacon::BeaconInputError::RoundNotFound(_) => warp::reject::custom(beacon::RoundNotFound),
+    }
+}
+
+async fn handle_beacon_info(beacon: Arc<Option<beacon::Beacon>>) -> Result<impl Reply, Rejection> {
+    match beacon::api_info(beacon.as_ref().as_ref()) {
+        Ok(info) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: info })),
+        Err(err) => Err(reject_beacon_error(err))
+    }
+}
+
+async fn handle_beacon_latest(beacon: Arc<Option<beacon::Beacon>>) -> Result<impl Reply, Rejection> {
+    match beacon::api_latest(beacon.as_ref().as_ref()) {
+        Ok(round) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: round })),
+        Err(err) => Err(reject_beacon_error(err))
+    }
+}
+
+// Stored rounds are read from `beacon.file`, so lookups run on the blocking pool.
+async fn handle_beacon_round(round: u64, beacon: Arc<Option<beacon::Beacon>>) -> Result<impl Reply, Rejection> {
+    let round_result = tokio::task::spawn_blocking(move || beacon::api_round(beacon.as_ref().as_ref(), round))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match round_result {
+        Ok(round) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: round })),
+        Err(err) => Err(reject_beacon_error(err))
+    }
+}
+
+fn reject_drand_error(err: drand::DrandInputError) -> Rejection {
+    match err {
+        drand::DrandInputError::DrandDisabled(_) => warp::reject::custom(drand::DrandDisabled),
+        drand::DrandInputError::DrandInvalid(_) => warp::reject::custom(drand::DrandInvalid),
+        drand::DrandInputError::DrandUnavailable(_) => warp::reject::custom(drand::DrandUnavailable),
+        drand::DrandInputError::HexStringInvalid(_) => warp::reject::custom(utils::HexStringInvalid),
+    }
+}
+
+// Pairing checks and round fetches block, so they run on the blocking pool.
+async fn handle_drand_verify(drand: Arc<Option<drand::Drand>>, round: drand::DrandRound) -> Result<impl Reply, Rejection> {
+    let verify_result = tokio::task::spawn_blocking(move || drand::api_verify(drand.as_ref().as_ref(), round))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match verify_result {
+        Ok(verify_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: verify_output })),
+        Err(err) => Err(reject_drand_error(err))
+    }
+}
+
+async fn handle_drand_alpha(drand: Arc<Option<drand::Drand>>, alpha_inputs: drand::AlphaInputs) -> Result<impl Reply, Rejection> {
+    let alpha_result = tokio::task::spawn_blocking(move || drand::api_alpha(drand.as_ref().as_ref(), alpha_inputs))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match alpha_result {
+        Ok(alpha_output) => Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: alpha_output })),
+        Err(err) => Err(reject_drand_error(err))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/vrf/prove", tag = "v2",
+    request_body(content = v2::ProveInputs, example = json!({"sk_hex": "0101010101010101010101010101010101010101010101010101010101010101", "alpha_hex": "68656c6c6f"})),
+    responses(
+        (status = 200, description = "Proof of `alpha_hex` under `sk_hex`.", body = v2::Envelope<v2::ProofOutput>),
+        (status = 400, description = "`sk_invalid`, `alpha_invalid` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_vrf_prove(prove_inputs: v2::ProveInputs) -> Result<impl Reply, Rejection> {
+    match metrics::VRF_PROVE_DURATION.observe_closure_duration(|| v2::api_vrf_prove(prove_inputs)) {
+        Ok(proof_output) => Ok(warp::reply::json(&v2::Envelope::new(proof_output))),
+        Err(ecvrf::VRFInputError::AlphaInvalid(_)) => Err(warp::reject::custom(ecvrf::AlphaInvalid)),
+        Err(ecvrf::VRFInputError::SKInvalid(_)) => Err(warp::reject::custom(ecvrf::SKInvalid)),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/vrf/verify", tag = "v2",
+    request_body(content = v2::VerifyInputs, example = json!({"pk_hex": "3e440469a098036d89ffb2d77a4542928f2f74c2b5769da7480736ace829dc10", "alpha_hex": "68656c6c6f", "pi_hex": "008a58b8fb96b282311dd955146e4e87cf7c6632942360deb8955a8dbc0d1672b7e236fb0c37e810923059f8c98f06787d8324f7779c9bd1df52d24bfbce44e336a5718db8a301df4cd07fbd4ce0f706"})),
+    responses(
+        (status = 200, description = "The proof is valid; `beta_hex` is its output.", body = v2::Envelope<v2::VerifyOutput>),
+        (status = 400, description = "`pk_invalid`, `alpha_invalid`, `pi_invalid`, `vrf_verify_failed` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_vrf_verify(verify_inputs: v2::VerifyInputs) -> Result<impl Reply, Rejection> {
+    match metrics::VRF_VERIFY_DURATION.observe_closure_duration(|| v2::api_vrf_verify(verify_inputs)) {
+        Ok(verify_output) => Ok(warp::reply::json(&v2::Envelope::new(verify_output))),
+        Err(ecvrf::VRFVerifyInputError::AlphaInvalid(_)) => Err(warp::reject::custom(ecvrf::AlphaInvalid)),
+        Err(ecvrf::VRFVerifyInputError::PKInvalid(_)) => Err(warp::reject::custom(ecvrf::PKInvalid)),
+        Err(ecvrf::VRFVerifyInputError::PiInvalid(_)) => Err(warp::reject::custom(ecvrf::PiInvalid)),
+        Err(ecvrf::VRFVerifyInputError::VRFVerifyFailed(_)) => Err(warp::reject::custom(ecvrf::VRFVerifyFailed)),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/vrf/pk", tag = "v2",
+    request_body(content = v2::PKInputs, example = json!({"sk_hex": "0101010101010101010101010101010101010101010101010101010101010101"})),
+    responses(
+        (status = 200, description = "Public key of `sk_hex`.", body = v2::Envelope<v2::PKOutput>),
+        (status = 400, description = "`sk_invalid` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_get_pk(pk_inputs: v2::PKInputs) -> Result<impl Reply, Rejection> {
+    match v2::api_get_pk(pk_inputs) {
+        Ok(pk_output) => Ok(warp::reply::json(&v2::Envelope::new(pk_output))),
+        Err(ecvrf::VRFInputError::SKInvalid(_)) => Err(warp::reject::custom(ecvrf::SKInvalid)),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/vrf/sk", tag = "v2",
+    responses(
+        (status = 200, description = "A new secret key from the health-tested entropy source.", body = v2::Envelope<v2::SKOutput>),
+        (status = 503, description = "`entropy_failed`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_generate_sk() -> Result<impl Reply, Rejection> {
+    match v2::api_generate_sk() {
+        Ok(sk_output) => {
+            metrics::KEYS_GENERATED.inc();
+            Ok(warp::reply::json(&v2::Envelope::new(sk_output)))
+        },
+        Err(err) => Err(warp::reject::custom(err))
+    }
+}
+
+#[utoipa::path(
+    get, path = "/api/v2/vrf/keys", tag = "v2",
+    params(v2::PageQuery),
+    responses(
+        (status = 200, description = "Keystore keys in id order.", body = v2::Envelope<Vec<v2::KeyOutput>>),
+        (status = 400, description = "`invalid_query`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_list_keys(page_query: v2::PageQuery, keyring: Arc<keystore::Keyring>) -> Result<impl Reply, Infallible> {
+    let (keys, page) = v2::api_list_keys(&keyring, page_query);
+    Ok(warp::reply::json(&v2::Envelope::paged(keys, page)))
+}
+
+async fn handle_v2_key_prove(id: String, keyring: Arc<keystore::Keyring>, prove_inputs: v2::KeyProveInputs) -> Result<impl Reply, Rejection> {
+    match metrics::VRF_PROVE_DURATION.observe_closure_duration(|| v2::api_key_prove(&keyring, &id, prove_inputs)) {
+        Ok(proof_output) => {
+            metrics::KEY_PROOFS.with_label_values(&[id.as_str()]).inc();
+            Ok(warp::reply::json(&v2::Envelope::new(proof_output)))
+        },
+        Err(keystore::KeyProveInputError::KeyNotFound(_)) => Err(warp::reject::custom(keystore::KeyNotFound)),
+        Err(keystore::KeyProveInputError::AlphaInvalid(_)) => Err(warp::reject::custom(ecvrf::AlphaInvalid)),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/utils/int-to-hex", tag = "v2",
+    request_body(content = v2::IntToHexInputs, example = json!({"value_decimal": "1000", "byte_order": "big_endian"})),
+    responses(
+        (status = 200, description = "Hex encoding of `value_decimal`.", body = v2::Envelope<v2::HexOutput>),
+        (status = 400, description = "`int_string_invalid` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_int_to_hex(convert_inputs: v2::IntToHexInputs) -> Result<impl Reply, Rejection> {
+    match v2::api_int_to_hex(convert_inputs) {
+        Ok(hex_output) => Ok(warp::reply::json(&v2::Envelope::new(hex_output))),
+        Err(utils::ConvertInputError::IntStringInvalid(_)) => Err(warp::reject::custom(utils::IntStringInvalid)),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/utils/hex-to-int", tag = "v2",
+    request_body(content = v2::HexToIntInputs, example = json!({"value_hex": "03e8", "byte_order": "big_endian"})),
+    responses(
+        (status = 200, description = "Decimal value of `value_hex`.", body = v2::Envelope<v2::DecimalOutput>),
+        (status = 400, description = "`hex_string_invalid` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_hex_to_int(convert_inputs: v2::HexToIntInputs) -> Result<impl Reply, Rejection> {
+    match v2::api_hex_to_int(convert_inputs) {
+        Ok(decimal_output) => Ok(warp::reply::json(&v2::Envelope::new(decimal_output))),
+        Err(utils::ConvertInputError::HexStringInvalid(_)) => Err(warp::reject::custom(utils::HexStringInvalid)),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/utils/sha256", tag = "v2",
+    request_body(content = v2::DigestInputs, example = json!({"data_hex": "68656c6c6f"})),
+    responses(
+        (status = 200, description = "SHA-256 of `data_hex`.", body = v2::Envelope<v2::DigestOutput>),
+        (status = 400, description = "`hex_string_invalid` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_sha256(digest_inputs: v2::DigestInputs) -> Result<impl Reply, Rejection> {
+    match v2::api_sha256(digest_inputs) {
+        Ok(digest_output) => Ok(warp::reply::json(&v2::Envelope::new(digest_output))),
+        Err(utils::ConvertInputError::HexStringInvalid(_)) => Err(warp::reject::custom(utils::HexStringInvalid)),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/utils/sha512", tag = "v2",
+    request_body(content = v2::DigestInputs, example = json!({"data_hex": "68656c6c6f"})),
+    responses(
+        (status = 200, description = "SHA-512 of `data_hex`.", body = v2::Envelope<v2::DigestOutput>),
+        (status = 400, description = "`hex_string_invalid` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_sha512(digest_inputs: v2::DigestInputs) -> Result<impl Reply, Rejection> {
+    match v2::api_sha512(digest_inputs) {
+        Ok(digest_output) => Ok(warp::reply::json(&v2::Envelope::new(digest_output))),
+        Err(utils::ConvertInputError::HexStringInvalid(_)) => Err(warp::reject::custom(utils::HexStringInvalid)),
+        Err(_err) => Err(warp::reject::custom(UnknownError))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/random/tickets", tag = "v2",
+    request_body(content = v2::TicketsInputs, example = json!({"seed_hex": "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d", "limit": 100, "start": 2, "count": 3})),
+    responses(
+        (status = 200, description = "Tickets `start` to `start + count` of the chain, each in [0, `limit`).", body = v2::Envelope<v2::TicketsOutput>),
+        (status = 400, description = "`seed_invalid`, `iterations_exceeded` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+// Like `/api/random/page`, a page may skip up to `limits.max_stream_iterations` blocks.
+async fn handle_v2_tickets(tickets_inputs: v2::TicketsInputs, max_count: usize, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    let tickets_result = tokio::task::spawn_blocking(move || v2::api_tickets(tickets_inputs, max_count, max_iterations))
+        .await
+        .map_err(|_err| warp::reject::custom(UnknownError))?;
+    match tickets_result {
+        Ok((tickets_output, page)) => {
+            metrics::DRAW_SIZE.with_label_values(&["tickets"]).observe(tickets_output.draws() as f64);
+            Ok(warp::reply::json(&v2::Envelope::paged(tickets_output, page)))
+        },
+        Err(err) => Err(reject_rng_error(err))
+    }
+}
+
+#[utoipa::path(
+    post, path = "/api/v2/random/distribution", tag = "v2",
+    request_body(content = v2::DistributionInputs, example = json!({"seed_hex": "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d", "draws": [{"type": "range", "min": -5, "max": 5}, {"type": "float", "decimals": 4}, {"type": "dice", "sides": 6, "count": 2}, {"type": "coin", "p": "0.25"}, {"type": "table", "outcomes": [{"value": "gold", "weight": 1}, {"value": "silver", "weight": 3}]}]})),
+    responses(
+        (status = 200, description = "One typed result per draw, in request order.", body = v2::Envelope<v2::DistributionOutput>),
+        (status = 400, description = "`seed_invalid`, `distribution_invalid`, `iterations_exceeded` or `body_deserialize_error`.", body = v2::ErrorEnvelope),
+    ),
+)]
+async fn handle_v2_distribution(distribution_inputs: v2::DistributionInputs, max_iterations: usize) -> Result<impl Reply, Rejection> {
+    match v2::api_distribution(distribution_inputs, max_iterations) {
+        Ok(distribution_output) => {
+            metrics::DRAW_SIZE.with_label_values(&["distribution"]).observe(distribution_output.draws() as f64);
+            Ok(warp::reply::json(&v2::Envelope::new(distribution_output)))
+        },
+        Err(err) => Err(reject_rng_error(err))
     }
-    Ok(warp::reply::json(&SuccessMessage{ success: true, code: StatusCode::OK.as_u16(), data: rng::api_random(rng_inputs) }))
 }
 
 // fn handle_json_body() -> impl Filter<Extract = (HashMap<String, String>,), Error = Rejection> + Clone {
 //     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 // }
 
-async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+struct Rejected {
+    kind       : &'static str,
+    code       : StatusCode,
+    message    : &'static str,
+    retry_after: Option<u64>,
+}
+
+// Status and message of a rejection, shared by the v1 and v2 error bodies. `kind` labels
+// `rejections_total` and is the v2 error code in snake case.
+fn classify_rejection(err: &Rejection) -> Rejected {
     let code;
     let message;
+    let kind;
+    let mut retry_after = None;
 
     if err.is_not_found() {
+        kind = "NotFound";
         code = StatusCode::NOT_FOUND;
         message = "NOT_FOUND";
     } else if let Some(ecvrf::AlphaInvalid) = err.find() {
+        kind = "AlphaInvalid";
         code = StatusCode::BAD_REQUEST;
         message = "Alpha is invalid.";
     } else if let Some(ecvrf::SKInvalid) = err.find() {
+        kind = "SKInvalid";
         code = StatusCode::BAD_REQUEST;
         message = "VRF secret key is invalid.";
     } else if let Some(ecvrf::PiInvalid) = err.find() {
+        kind = "PiInvalid";
         code = StatusCode::BAD_REQUEST;
         message = "VRF proof is invalid.";
+    } else if let Some(ecvrf::VRFVerifyFailed) = err.find() {
+        kind = "VRFVerifyFailed";
+        code = StatusCode::BAD_REQUEST;
+        message = "VRF proof does not verify.";
     } else if let Some(utils::IntStringInvalid) = err.find() {
+        kind = "IntStringInvalid";
         code = StatusCode::BAD_REQUEST;
         message = "Integer string is invalid.";
     } else if let Some(utils::HexStringInvalid) = err.find() {
+        kind = "HexStringInvalid";
         code = StatusCode::BAD_REQUEST;
         message = "Hex string is invalid.";
+    } else if let Some(rng::SeedInvalid) = err.find() {
+        kind = "SeedInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Seed is invalid.";
+    } else if let Some(rng::DistributionInvalid) = err.find() {
+        kind = "DistributionInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Distribution is invalid.";
+    } else if let Some(shuffle::ItemInvalid) = err.find() {
+        kind = "ItemInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Item is out of range.";
+    } else if let Some(prize::TiersInvalid) = err.find() {
+        kind = "TiersInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Prize tiers are invalid.";
+    } else if let Some(merkle::ParticipantsInvalid) = err.find() {
+        kind = "ParticipantsInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Participant list is invalid.";
+    } else if let Some(merkle::TicketInvalid) = err.find() {
+        kind = "TicketInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Ticket is out of range.";
+    } else if let Some(selftest::SamplesInvalid) = err.find() {
+        kind = "SamplesInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Sample count is too small for the requested buckets.";
     } else if let Some(rng::IterationsExceeded) = err.find() {
+        kind = "IterationsExceeded";
+        code = StatusCode::BAD_REQUEST;
+        message = "Iterations exceed maximum allowed limit.";
+    } else if let Some(entropy::EntropyFailed) = err.find() {
+        kind = "EntropyFailed";
+        code = StatusCode::SERVICE_UNAVAILABLE;
+        message = "Entropy source failed health tests.";
+    } else if let Some(hdkey::MasterSeedMissing) = err.find() {
+        kind = "MasterSeedMissing";
+        code = StatusCode::SERVICE_UNAVAILABLE;
+        message = "Master seed is not configured.";
+    } else if let Some(hdkey::PathInvalid) = err.find() {
+        kind = "PathInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Derivation path is invalid.";
+    } else if let Some(hdkey::DerivationFailed) = err.find() {
+        kind = "DerivationFailed";
+        code = StatusCode::INTERNAL_SERVER_ERROR;
+        message = "Key derivation failed.";
+    } else if let Some(ecvrf::PKInvalid) = err.find() {
+        kind = "PKInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "VRF public key is invalid.";
+    } else if let Some(backup::MnemonicInvalid) = err.find() {
+        kind = "MnemonicInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Mnemonic is invalid.";
+    } else if let Some(backup::SharesInvalid) = err.find() {
+        kind = "SharesInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "Secret shares are invalid.";
+    } else if let Some(backup::ThresholdInvalid) = err.find() {
+        kind = "ThresholdInvalid";
         code = StatusCode::BAD_REQUEST;
-        message = "Iterations exceed maximum allowed limit of 3100.";
-    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        message = "Share threshold is invalid.";
+    } else if let Some(backup::PKMismatch) = err.find() {
+        kind = "PKMismatch";
+        code = StatusCode::BAD_REQUEST;
+        message = "Recovered key does not match the VRF public key.";
+    } else if let Some(beacon::BeaconDisabled) = err.find() {
+        kind = "BeaconDisabled";
+        code = StatusCode::SERVICE_UNAVAILABLE;
+        message = "Beacon is not configured.";
+    } else if let Some(beacon::RoundNotFound) = err.find() {
+        kind = "RoundNotFound";
+        code = StatusCode::NOT_FOUND;
+        message = "Beacon round not found.";
+    } else if let Some(drand::DrandDisabled) = err.find() {
+        kind = "DrandDisabled";
+        code = StatusCode::SERVICE_UNAVAILABLE;
+        message = "drand is not configured.";
+    } else if let Some(drand::DrandInvalid) = err.find() {
+        kind = "DrandInvalid";
+        code = StatusCode::BAD_REQUEST;
+        message = "drand round is invalid.";
+    } else if let Some(drand::DrandUnavailable) = err.find() {
+        kind = "DrandUnavailable";
+        code = StatusCode::BAD_GATEWAY;
+        message = "drand endpoint is unavailable.";
+    } else if let Some(keystore::KeyNotFound) = err.find() {
+        kind = "KeyNotFound";
+        code = StatusCode::NOT_FOUND;
+        message = "VRF key is not loaded.";
+    } else if let Some(auth::CredentialsInvalid) = err.find() {
+        kind = "CredentialsInvalid";
+        code = StatusCode::UNAUTHORIZED;
+        message = "Credentials are missing or invalid.";
+    } else if let Some(auth::ScopeMissing) = err.find() {
+        kind = "ScopeMissing";
+        code = StatusCode::FORBIDDEN;
+        message = "Credentials do not grant access to this route.";
+    } else if let Some(ratelimit::RateLimited{ retry_after_secs }) = err.find() {
+        kind = "RateLimited";
+        code = StatusCode::TOO_MANY_REQUESTS;
+        message = "Rate limit exceeded.";
+        retry_after = Some(*retry_after_secs);
+    } else if let Some(tls::ClientCertRequired) = err.find() {
+        kind = "ClientCertRequired";
+        code = StatusCode::FORBIDDEN;
+        message = "A client certificate from the configured CA is required.";
+    } else if let Some(_e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        kind = "BodyDeserializeError";
         // This error happens if the body could not be deserialized correctly
         message = "BAD_REQUEST";
         code = StatusCode::BAD_REQUEST;
-    } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
+    } else if err.find::<warp::reject::InvalidQuery>().is_some() {
+        kind = "InvalidQuery";
+        code = StatusCode::BAD_REQUEST;
+        message = "Query string is invalid.";
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        kind = "MethodNotAllowed";
         code = StatusCode::METHOD_NOT_ALLOWED;
         message = "METHOD_NOT_ALLOWED";
     } else {
         // We should have expected this... Just log and say its a 500
-        eprintln!("unhandled rejection: {:?}", err);
+        log::error!(target: "api", "Unhandled rejection: {:?}", err);
+        kind = "Unhandled";
         code = StatusCode::INTERNAL_SERVER_ERROR;
         message = "UNHANDLED_REJECTION";
     }
 
+    metrics::REJECTIONS.with_label_values(&[kind]).inc();
+    Rejected{ kind, code, message, retry_after }
+}
+
+fn with_retry_after(reply: impl Reply, retry_after: Option<u64>) -> warp::reply::Response {
+    match retry_after {
+        Some(secs) => warp::reply::with_header(reply, "retry-after", secs.to_string()).into_response(),
+        None => reply.into_response(),
+    }
+}
+
+// v1 messages are frozen. The iterations message names `limits.max_iterations`, which is the
+// original "... limit of 3100." with the default limits.
+async fn handle_rejection(err: Rejection, max_iterations: usize) -> Result<impl Reply, Infallible> {
+    let rejected = classify_rejection(&err);
+    let message = match rejected.kind {
+        "IterationsExceeded" => format!("Iterations exceed maximum allowed limit of {}.", max_iterations),
+        _ => rejected.message.to_string(),
+    };
+
     let json = warp::reply::json(&ErrorMessage {
         success: false,
-        code: code.as_u16(),
-        message: message.into(),
+        code: rejected.code.as_u16(),
+        message,
+        request_id: logging::request_id(),
     });
 
-    Ok(warp::reply::with_status(json, code))
+    Ok(with_retry_after(warp::reply::with_status(json, rejected.code), rejected.retry_after))
+}
+
+// v2 messages are sentences; body errors say which field is wrong.
+async fn handle_v2_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let rejected = classify_rejection(&err);
+    let message = match err.find::<warp::filters::body::BodyDeserializeError>() {
+        Some(body_err) => body_err.to_string(),
+        None if matches!(rejected.kind, "NotFound" | "MethodNotAllowed" | "Unhandled") => rejected.code.canonical_reason().unwrap_or_default().to_string() + ".",
+        None => rejected.message.to_string(),
+    };
+
+    let json = warp::reply::json(&v2::ErrorEnvelope {
+        error     : v2::ErrorBody{ code: v2::error_code(rejected.kind), message },
+        request_id: logging::request_id(),
+    });
+
+    Ok(with_retry_after(warp::reply::with_status(json, rejected.code), rejected.retry_after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn authenticator() -> Arc<auth::Authenticator> {
+        let api_keys = [auth::ApiKeyConfig{ id: "ops".to_string(), sha256: hex::encode(auth::key_hash("ops-key")), scopes: vec![auth::Scope::KeysAdmin] }];
+        Arc::new(auth::Authenticator::new(&auth::DEFAULT_PUBLIC_SCOPES, &api_keys, None, None).unwrap())
+    }
+
+    // `access` for `scope` behind the v1 error handler, as the routes in `main` use it.
+    async fn respond(scope: auth::Scope, api_key: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let rate_limiter = Arc::new(ratelimit::RateLimiter::new(false, false, HashMap::new()));
+        let filter = access(authenticator(), rate_limiter, scope, None)
+            .map(warp::reply)
+            .recover(|err| handle_rejection(err, rng::MAX_ITERATIONS));
+        let mut request = warp::test::request();
+        if let Some(api_key) = api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        let response = request.reply(&filter).await;
+        (response.status(), serde_json::from_slice(response.body()).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn missing_or_invalid_credentials_answer_401() {
+        for (scope, api_key) in [(auth::Scope::KeysAdmin, None), (auth::Scope::KeysAdmin, Some("other-key")), (auth::Scope::VrfVerify, Some("other-key"))] {
+            let (status, body) = respond(scope, api_key).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["code"], 401);
+            assert_eq!(body["message"], "Credentials are missing or invalid.");
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_scope_answers_403() {
+        let (status, body) = respond(auth::Scope::VrfProve, Some("ops-key")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], 403);
+        assert_eq!(body["message"], "Credentials do not grant access to this route.");
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_answer_429_with_retry_after() {
+        let budgets = HashMap::from([(ratelimit::Budget::Prove, ratelimit::BucketConfig{ rate: 0.5, burst: 1 })]);
+        let rate_limiter = Arc::new(ratelimit::RateLimiter::new(true, false, budgets));
+        let filter = access(authenticator(), rate_limiter, auth::Scope::KeysAdmin, Some(ratelimit::Budget::Prove))
+            .map(warp::reply)
+            .recover(|err| handle_rejection(err, rng::MAX_ITERATIONS));
+        let request = || warp::test::request().header("x-api-key", "ops-key");
+        assert_eq!(request().reply(&filter).await.status(), StatusCode::OK);
+        let response = request().reply(&filter).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "2");
+    }
+
+    #[tokio::test]
+    async fn granted_scope_and_public_routes_pass() {
+        assert_eq!(respond(auth::Scope::KeysAdmin, Some("ops-key")).await.0, StatusCode::OK);
+        assert_eq!(respond(auth::Scope::VrfVerify, None).await.0, StatusCode::OK);
+    }
+
+    // A wrong method is rejected before credentials are checked or a token is taken.
+    #[tokio::test]
+    async fn wrong_method_answers_405_without_spending_a_token() {
+        let budgets = HashMap::from([(ratelimit::Budget::Prove, ratelimit::BucketConfig{ rate: 0.001, burst: 1 })]);
+        let rate_limiter = Arc::new(ratelimit::RateLimiter::new(true, false, budgets));
+        let filter = warp::path!("api" / "vrf" / "prove")
+            .and(route_group(&RoutesConfig::default(), RouteGroup::Vrf))
+            .and(warp::post())
+            .and(access(authenticator(), rate_limiter, auth::Scope::KeysAdmin, Some(ratelimit::Budget::Prove)))
+            .map(warp::reply)
+            .recover(|err| handle_rejection(err, rng::MAX_ITERATIONS));
+        let request = |method: &str, api_key: Option<&str>| {
+            let request = warp::test::request().method(method).path("/api/vrf/prove");
+            match api_key {
+                Some(api_key) => request.header("x-api-key", api_key),
+                None => request,
+            }
+        };
+        assert_eq!(request("GET", None).reply(&filter).await.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(request("GET", Some("ops-key")).reply(&filter).await.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(request("POST", None).reply(&filter).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(request("POST", Some("ops-key")).reply(&filter).await.status(), StatusCode::OK);
+        assert_eq!(request("POST", Some("ops-key")).reply(&filter).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn every_route_checks_the_method_before_access() {
+        let source = include_str!("main.rs");
+        let main = &source[source.find("async fn main()").unwrap()..source.find("#[cfg(test)]").unwrap()];
+        let filters: Vec<&str> = main.split("let ").filter(|filter| filter.contains(".and(access(")).collect();
+        assert_eq!(filters.len(), metrics::ROUTES.len() - 1); // `/docs` and `/docs/{file}` share a filter.
+        for filter in filters {
+            let method = filter.find(".and(warp::post())").or_else(|| filter.find(".and(warp::get())"));
+            assert!(method.is_some_and(|method| method < filter.find(".and(access(").unwrap()), "{}", filter.lines().next().unwrap());
+        }
+    }
+
+    // The routes declared in `main`, read from its `warp::path!` filters: typed segments become
+    // `{}` and the `v2_*` filters are nested under `/api/v2`. `/docs` also serves one file level.
+    fn declared_routes() -> Vec<String> {
+        let source = include_str!("main.rs");
+        let main = &source[source.find("async fn main()").unwrap()..source.find("#[cfg(test)]").unwrap()];
+        let mut routes = vec!["/docs".to_string(), "/docs/{}".to_string()];
+        for line in main.lines().filter(|line| line.contains("= warp::path!(") && !line.contains("..")) {
+            let name = line.trim().trim_start_matches("let ").split(' ').next().unwrap();
+            let segments = &line[line.find("path!(").unwrap() + "path!(".len()..];
+            let segments = &segments[..segments.find(')').unwrap()];
+            let mut route = if name.starts_with("v2_") { "/api/v2".to_string() } else { String::new() };
+            for segment in segments.split('/').map(str::trim) {
+                route.push('/');
+                route.push_str(segment.strip_prefix('"').and_then(|segment| segment.strip_suffix('"')).unwrap_or("{}"));
+            }
+            routes.push(route);
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_has_a_metrics_template() {
+        let declared = declared_routes();
+        let templates: Vec<String> = metrics::ROUTES.iter()
+            .map(|route| route.split('/').map(|segment| if segment.starts_with('{') { "{}" } else { segment }).collect::<Vec<_>>().join("/"))
+            .collect();
+        for route in &declared {
+            assert!(templates.contains(route), "{} is missing from metrics::ROUTES", route);
+            assert_ne!(metrics::route_template(&route.replace("{}", "x")), "unmatched", "{}", route);
+        }
+        for template in &templates {
+            assert!(declared.contains(template), "{} in metrics::ROUTES is not served", template);
+        }
+        assert_eq!(declared.len(), metrics::ROUTES.len());
+    }
 }
@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+
+use crate::ecvrf;
+use crate::metrics;
+use crate::utils::HexStringInvalid;
+
+// SHA-256 Merkle commitment to the participant list, specified in `docs/seed-chain.md`.
+//
+//   leaf(i) = SHA-256(0x00 || u64_be(i) || utf8(participants[i]))
+//   node    = SHA-256(0x01 || left || right)
+//   root    = SHA-256(0x02 || u64_be(n) || top node)
+//
+// A level with an odd number of nodes carries its last node up unchanged. The ticket index
+// is part of the leaf and the participant count n is part of the root, so the shape of the
+// tree, and with it every step of a ticket's path, follows from the root and the ticket.
+
+pub const MAX_PARTICIPANTS: usize = 100_000; // Default for `limits.max_participants`.
+
+#[derive(Debug)]
+pub struct ParticipantsInvalid;
+
+#[derive(Debug)]
+pub struct TicketInvalid;
+
+#[derive(Debug)]
+pub enum MerkleInputError {
+    ParticipantsInvalid(ParticipantsInvalid),
+    TicketInvalid(TicketInvalid),
+    HexStringInvalid(HexStringInvalid),
+    SKInvalid(ecvrf::SKInvalid),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ProofStep {
+    pub sibling: String, // Hex, 32 bytes.
+    pub side   : Side,   // Side of the sibling; checked against the ticket's position.
+}
+
+#[derive(Deserialize)]
+pub struct CommitInputs {
+    participants: Vec<String>, // participants[ticket] = wallet.
+    #[serde(default)]
+    data: String, // Hex string, e.g. hex(rng) || hex(time).
+}
+
+#[derive(Serialize)]
+pub struct CommitOutput {
+    pub root        : String,
+    pub participants: usize,
+    pub alpha       : String, // SHA-256(root || data), to be passed to the VRF.
+}
+
+#[derive(Deserialize)]
+pub struct ProveInputs {
+    sk          : String,
+    participants: Vec<String>,
+    #[serde(default)]
+    data: String,
+}
+
+// A commitment and the VRF proof of its alpha, so the proof is bound to the participant list.
+#[derive(Serialize)]
+pub struct ProveOutput {
+    pub root        : String,
+    pub participants: usize,
+    pub alpha       : String,
+    pub pk          : String,
+    #[serde(flatten)]
+    pub proof       : ecvrf::VRFOutput,
+}
+
+#[derive(Deserialize)]
+pub struct ProofsInputs {
+    participants: Vec<String>,
+    tickets: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct InclusionProof {
+    pub ticket     : u64,
+    pub participant: String,
+    pub leaf       : String,
+    pub path       : Vec<ProofStep>,
+}
+
+#[derive(Serialize)]
+pub struct ProofsOutput {
+    pub root  : String,
+    pub proofs: Vec<InclusionProof>,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyInputs {
+    root: String,
+    participants: u64, // Participant count of the commitment.
+    ticket: u64,
+    participant: String,
+    path: Vec<ProofStep>,
+}
+
+#[derive(Serialize)]
+pub struct VerifyOutput {
+    pub valid: bool,
+}
+
+pub fn leaf_hash(ticket: u64, participant: &str) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([0x00])
+        .chain_update(ticket.to_be_bytes())
+        .chain_update(participant.as_bytes())
+        .finalize()
+        .into()
+}
+
+pub fn root_hash(participants: u64, top: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([0x02])
+        .chain_update(participants.to_be_bytes())
+        .chain_update(top)
+        .finalize()
+        .into()
+}
+
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([0x01])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+// All levels of the tree, leaves first and the root last.
+pub struct MerkleTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub fn new(participants: &[String], max_participants: usize) -> Result<MerkleTree, ParticipantsInvalid> {
+        if participants.is_empty() || participants.len() > max_participants {
+            return Err(ParticipantsInvalid);
+        }
+        let mut levels = vec![participants.iter().enumerate().map(|(ticket, participant)| leaf_hash(ticket as u64, participant)).collect::<Vec<_>>()];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next = level.chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [last] => *last,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Ok(MerkleTree{ levels })
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        root_hash(self.len() as u64, &self.levels[self.levels.len() - 1][0])
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    pub fn leaf(&self, ticket: u64) -> Option<[u8; 32]> {
+        self.levels[0].get(usize::try_from(ticket).ok()?).copied()
+    }
+
+    pub fn proof(&self, ticket: u64) -> Option<Vec<ProofStep>> {
+        let mut index = usize::try_from(ticket).ok().filter(|&index| index < self.len())?;
+        let mut path = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < index { Side::Left } else { Side::Right };
+                path.push(ProofStep{ sibling: hex::encode(level[sibling]), side });
+            }
+            index /= 2;
+        }
+        Some(path)
+    }
+}
+
+// Folds `path` from the ticket's leaf. Each step's side follows from the ticket's position in
+// its level, so a path only verifies for the ticket and participant count it was built for.
+pub fn verify(root: &[u8], participants: u64, ticket: u64, participant: &str, path: &[ProofStep]) -> Result<bool, HexStringInvalid> {
+    if ticket >= participants {
+        return Ok(false);
+    }
+    let (mut index, mut level_len) = (ticket, participants);
+    let mut steps = path.iter();
+    let mut node = leaf_hash(ticket, participant);
+    while level_len > 1 {
+        let sibling_index = index ^ 1;
+        if sibling_index < level_len {
+            let Some(step) = steps.next() else {
+                return Ok(false);
+            };
+            let sibling: [u8; 32] = hex::decode(&step.sibling).ok().and_then(|bytes| bytes.try_into().ok()).ok_or(HexStringInvalid)?;
+            let side = if sibling_index < index { Side::Left } else { Side::Right };
+            if step.side != side {
+                return Ok(false);
+            }
+            node = match side {
+                Side::Left => node_hash(&sibling, &node),
+                Side::Right => node_hash(&node, &sibling),
+            };
+        }
+        index /= 2;
+        level_len = level_len.div_ceil(2);
+    }
+    Ok(steps.next().is_none() && root_hash(participants, &node).as_slice() == root)
+}
+
+pub fn commit_alpha(root: &[u8; 32], data: &[u8]) -> [u8; 32] {
+    Sha256::new().chain_update(root).chain_update(data).finalize().into()
+}
+
+// Api endpoints.
+pub fn api_commit(commit_inputs: CommitInputs, max_participants: usize) -> Result<CommitOutput, MerkleInputError> {
+    let data = match hex::decode(&commit_inputs.data) {
+        Ok(data) => data,
+        Err(_err) => return Err(MerkleInputError::HexStringInvalid(HexStringInvalid))
+    };
+    match MerkleTree::new(&commit_inputs.participants, max_participants) {
+        Ok(tree) => Ok(CommitOutput {
+            root        : hex::encode(tree.root()),
+            participants: tree.len(),
+            alpha       : hex::encode(commit_alpha(&tree.root(), &data)),
+        }),
+        Err(err) => Err(MerkleInputError::ParticipantsInvalid(err))
+    }
+}
+
+// Commits to the participants and proves the resulting alpha in one step: unlike passing the
+// `api_commit` alpha to `/api/vrf/prove`, the server computes the alpha it proves.
+pub fn api_prove(prove_inputs: ProveInputs, max_participants: usize) -> Result<ProveOutput, MerkleInputError> {
+    let sk = ecvrf::decode_sk(prove_inputs.sk).map_err(MerkleInputError::SKInvalid)?;
+    let data = hex::decode(&prove_inputs.data).map_err(|_err| MerkleInputError::HexStringInvalid(HexStringInvalid))?;
+    let tree = MerkleTree::new(&prove_inputs.participants, max_participants).map_err(MerkleInputError::ParticipantsInvalid)?;
+    let alpha = commit_alpha(&tree.root(), &data);
+    let proof = metrics::VRF_PROVE_DURATION.observe_closure_duration(|| ecvrf::prove(&sk, &alpha));
+    Ok(ProveOutput {
+        root        : hex::encode(tree.root()),
+        participants: tree.len(),
+        alpha       : hex::encode(alpha),
+        pk          : ecvrf::get_pk(sk),
+        proof,
+    })
+}
+
+pub fn api_proofs(proofs_inputs: ProofsInputs, max_participants: usize) -> Result<ProofsOutput, MerkleInputError> {
+    let tree = match MerkleTree::new(&proofs_inputs.participants, max_participants) {
+        Ok(tree) => tree,
+        Err(err) => return Err(MerkleInputError::ParticipantsInvalid(err))
+    };
+    let mut proofs = Vec::with_capacity(proofs_inputs.tickets.len());
+    for ticket in proofs_inputs.tickets {
+        match (tree.leaf(ticket), tree.proof(ticket)) {
+            (Some(leaf), Some(path)) => proofs.push(InclusionProof {
+                ticket,
+                participant: proofs_inputs.participants[ticket as usize].clone(),
+                leaf       : hex::encode(leaf),
+                path,
+            }),
+            _ => return Err(MerkleInputError::TicketInvalid(TicketInvalid))
+        }
+    }
+    Ok(ProofsOutput{ root: hex::encode(tree.root()), proofs })
+}
+
+pub fn api_verify(verify_inputs: VerifyInputs) -> Result<VerifyOutput, MerkleInputError> {
+    let root = match hex::decode(&verify_inputs.root) {
+        Ok(root) => root,
+        Err(_err) => return Err(MerkleInputError::HexStringInvalid(HexStringInvalid))
+    };
+    match verify(&root, verify_inputs.participants, verify_inputs.ticket, &verify_inputs.participant, &verify_inputs.path) {
+        Ok(valid) => Ok(VerifyOutput{ valid }),
+        Err(err) => Err(MerkleInputError::HexStringInvalid(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn participants(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("wallet-{}", i)).collect()
+    }
+
+    #[test]
+    fn every_proof_verifies() {
+        for n in 1..=9 {
+            let participants = participants(n);
+            let tree = MerkleTree::new(&participants, MAX_PARTICIPANTS).unwrap();
+            for ticket in 0..n as u64 {
+                let path = tree.proof(ticket).unwrap();
+                assert!(verify(&tree.root(), n as u64, ticket, &participants[ticket as usize], &path).unwrap(), "n {} ticket {}", n, ticket);
+            }
+        }
+    }
+
+    #[test]
+    fn flipped_side_fails() {
+        let participants = participants(7);
+        let tree = MerkleTree::new(&participants, MAX_PARTICIPANTS).unwrap();
+        let mut path = tree.proof(2).unwrap();
+        path[0].side = match path[0].side { Side::Left => Side::Right, Side::Right => Side::Left };
+        assert!(!verify(&tree.root(), 7, 2, &participants[2], &path).unwrap());
+    }
+
+    #[test]
+    fn proof_for_another_ticket_fails() {
+        let participants = participants(7);
+        let tree = MerkleTree::new(&participants, MAX_PARTICIPANTS).unwrap();
+        let path = tree.proof(2).unwrap();
+        assert!(!verify(&tree.root(), 7, 3, &participants[2], &path).unwrap());
+        assert!(!verify(&tree.root(), 7, 3, &participants[3], &path).unwrap());
+    }
+
+    #[test]
+    fn participant_count_is_bound() {
+        let participants = participants(8);
+        let tree = MerkleTree::new(&participants, MAX_PARTICIPANTS).unwrap();
+        let path = tree.proof(5).unwrap();
+        assert!(verify(&tree.root(), 8, 5, &participants[5], &path).unwrap());
+        assert!(!verify(&tree.root(), 6, 5, &participants[5], &path).unwrap());
+        assert!(!verify(&tree.root(), 9, 5, &participants[5], &path).unwrap());
+        assert!(!verify(&tree.root(), 8, 8, &participants[5], &path).unwrap());
+    }
+
+    #[test]
+    fn prove_binds_the_commitment() {
+        let sk = "0101010101010101010101010101010101010101010101010101010101010101";
+        let participants = participants(5);
+        let prove_inputs = ProveInputs{ sk: sk.to_string(), participants: participants.clone(), data: "abcd".to_string() };
+        let prove_output = api_prove(prove_inputs, MAX_PARTICIPANTS).unwrap();
+
+        let tree = MerkleTree::new(&participants, MAX_PARTICIPANTS).unwrap();
+        let alpha = commit_alpha(&tree.root(), &[0xab, 0xcd]);
+        assert_eq!(prove_output.root, hex::encode(tree.root()));
+        assert_eq!(prove_output.alpha, hex::encode(alpha));
+        let proof = ecvrf::prove(&ecvrf::decode_sk(sk.to_string()).unwrap(), &alpha);
+        assert_eq!((prove_output.proof.Gamma, prove_output.proof.c, prove_output.proof.s), (proof.Gamma, proof.c, proof.s));
+    }
+}
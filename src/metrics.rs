@@ -0,0 +1,242 @@
+use std::sync::LazyLock;
+
+use prometheus::{Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts};
+use prometheus::core::Collector;
+use prometheus::proto::MetricFamily;
+use warp::http::Method;
+
+use crate::ratelimit::RateLimitOutput;
+
+// Prometheus metrics in the default registry, exported in the text format at `/metrics`.
+// Requests are labelled with their route template (`/beacon/round/{round}`), never the raw
+// path, so label cardinality stays bounded; paths that match no route share `unmatched`.
+// Rate limiter counters are kept by `ratelimit` and copied in at scrape time.
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+// Every route served by `main.rs`, `{...}` matching any single segment.
+pub const ROUTES: [&str; 50] = [
+    "/api/vrf/prove",
+    "/api/vrf/verify",
+    "/api/vrf/pk",
+    "/api/vrf/pk/derive",
+    "/api/vrf/sk/new",
+    "/api/vrf/sk/mnemonic",
+    "/api/vrf/sk/mnemonic/restore",
+    "/api/vrf/sk/shares",
+    "/api/vrf/sk/shares/recover",
+    "/api/vrf/keys",
+    "/api/vrf/keys/{id}/prove",
+    "/api/utils/hex",
+    "/api/utils/int",
+    "/api/utils/sha256",
+    "/api/utils/sha512",
+    "/api/random",
+    "/api/random/stream",
+    "/api/random/page",
+    "/api/random/distribution",
+    "/api/random/shuffle",
+    "/api/random/shuffle/rank",
+    "/api/random/prizes",
+    "/api/merkle/commit",
+    "/api/merkle/prove",
+    "/api/merkle/proofs",
+    "/api/merkle/verify",
+    "/api/drand/verify",
+    "/api/drand/alpha",
+    "/api/admin/selftest",
+    "/api/admin/ratelimit",
+    "/api/health/entropy",
+    "/api/v2/vrf/prove",
+    "/api/v2/vrf/verify",
+    "/api/v2/vrf/pk",
+    "/api/v2/vrf/sk",
+    "/api/v2/vrf/keys",
+    "/api/v2/vrf/keys/{id}/prove",
+    "/api/v2/utils/int-to-hex",
+    "/api/v2/utils/hex-to-int",
+    "/api/v2/utils/sha256",
+    "/api/v2/utils/sha512",
+    "/api/v2/random/tickets",
+    "/api/v2/random/distribution",
+    "/beacon",
+    "/beacon/latest",
+    "/beacon/round/{round}",
+    "/metrics",
+    "/openapi.json",
+    "/docs",
+    "/docs/{file}",
+];
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("http_requests_total", "Requests by route template, method and status."), &["route", "method", "status"]))
+});
+
+// Time until the response head is ready; streamed bodies are not included.
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(HistogramOpts::new("http_request_duration_seconds", "Request latency by route template."), &["route"]))
+});
+
+pub static REJECTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("rejections_total", "Rejected requests by rejection type."), &["kind"]))
+});
+
+pub static VRF_PROVE_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    register(Histogram::with_opts(HistogramOpts::new("vrf_prove_duration_seconds", "ECVRF proof generation time.").buckets(vrf_buckets())))
+});
+
+pub static VRF_VERIFY_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    register(Histogram::with_opts(HistogramOpts::new("vrf_verify_duration_seconds", "ECVRF proof verification time.").buckets(vrf_buckets())))
+});
+
+// Values drawn per request: iterations, distribution draws, shuffled items or prize winners.
+pub static DRAW_SIZE: LazyLock<HistogramVec> = LazyLock::new(|| {
+    let buckets = prometheus::exponential_buckets(1.0, 4.0, 11).expect("bucket parameters are valid");
+    register(HistogramVec::new(HistogramOpts::new("draw_size", "Values drawn per request by draw kind.").buckets(buckets), &["kind"]))
+});
+
+pub static KEY_PROOFS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("vrf_key_proofs_total", "Proofs made with each keystore key."), &["key"]))
+});
+
+pub static KEYS_GENERATED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("vrf_keys_generated_total", "Secret keys generated by /api/vrf/sk/new."))
+});
+
+// `key:<id>` per API key; JWTs and anonymous callers are aggregated, as their ids are unbounded.
+pub static PRINCIPAL_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("auth_requests_total", "Authorized requests by API key, jwt or anonymous."), &["principal"]))
+});
+
+fn register<C: Collector + Clone + 'static>(collector: prometheus::Result<C>) -> C {
+    let collector = collector.expect("metric options are valid");
+    prometheus::register(Box::new(collector.clone())).expect("metric is registered once");
+    collector
+}
+
+// 50µs to about 0.4s.
+fn vrf_buckets() -> Vec<f64> {
+    prometheus::exponential_buckets(0.00005, 2.0, 14).expect("bucket parameters are valid")
+}
+
+pub fn route_template(path: &str) -> &'static str {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    ROUTES.iter()
+        .find(|route| {
+            let template: Vec<&str> = route.trim_matches('/').split('/').collect();
+            template.len() == segments.len()
+                && template.iter().zip(&segments).all(|(part, segment)| part.starts_with('{') || part == segment)
+        })
+        .copied()
+        .unwrap_or("unmatched")
+}
+
+// Passed to `warp::log::custom` for every response, rejections included.
+pub fn observe_request(info: warp::log::Info<'_>) {
+    let route = route_template(info.path());
+    let method = match *info.method() {
+        Method::GET | Method::POST | Method::PUT | Method::DELETE | Method::HEAD | Method::OPTIONS | Method::PATCH => info.method().as_str(),
+        _ => "other",
+    };
+    HTTP_REQUESTS.with_label_values(&[route, method, info.status().as_str()]).inc();
+    HTTP_REQUEST_DURATION.with_label_values(&[route]).observe(info.elapsed().as_secs_f64());
+}
+
+pub fn observe_principal(principal_id: Option<&str>) {
+    let principal = match principal_id {
+        Some(id) if id.starts_with("key:") => id,
+        Some(_id) => "jwt",
+        None => "anonymous",
+    };
+    PRINCIPAL_REQUESTS.with_label_values(&[principal]).inc();
+}
+
+fn rate_limit_families(status: &RateLimitOutput) -> prometheus::Result<Vec<MetricFamily>> {
+    let requests = IntCounterVec::new(Opts::new("rate_limit_requests_total", "Rate-limited route requests by budget and outcome."), &["budget", "outcome"])?;
+    let clients = IntGauge::new("rate_limit_clients", "Token buckets currently tracked.")?;
+    for budget in &status.budgets {
+        let name = budget.budget.to_string();
+        requests.with_label_values(&[name.as_str(), "allowed"]).inc_by(budget.allowed);
+        requests.with_label_values(&[name.as_str(), "limited"]).inc_by(budget.limited);
+    }
+    clients.set(status.clients as i64);
+    Ok(requests.collect().into_iter().chain(clients.collect()).collect())
+}
+
+pub fn api_metrics(rate_limit: &RateLimitOutput) -> prometheus::Result<String> {
+    let mut families = prometheus::gather();
+    families.extend(rate_limit_families(rate_limit)?);
+    families.sort_by(|a, b| a.name().cmp(b.name()));
+    prometheus::TextEncoder::new().encode_to_string(&families)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use warp::Filter;
+
+    use crate::ratelimit::{Budget, BudgetOutput};
+
+    #[test]
+    fn paths_resolve_to_their_route_template() {
+        for route in ROUTES {
+            assert_eq!(route_template(route), route);
+        }
+        assert_eq!(route_template("/beacon/round/42"), "/beacon/round/{round}");
+        assert_eq!(route_template("/api/vrf/keys/main/prove"), "/api/vrf/keys/{id}/prove");
+        assert_eq!(route_template("/api/v2/vrf/keys/main/prove"), "/api/v2/vrf/keys/{id}/prove");
+        assert_eq!(route_template("/docs/index.html"), "/docs/{file}");
+        assert_eq!(route_template("/api/random/"), "/api/random");
+        assert_eq!(route_template("/api/vrf/keys/main"), "unmatched");
+        assert_eq!(route_template("/api/vrf/keys/main/verify"), "unmatched");
+        assert_eq!(route_template("/docs/swagger/index.html"), "unmatched");
+        assert_eq!(route_template("/"), "unmatched");
+    }
+
+    #[tokio::test]
+    async fn requests_are_counted_by_route_template() {
+        let filter = warp::path!("beacon" / "round" / u64)
+            .map(|round: u64| round.to_string())
+            .with(warp::log::custom(observe_request));
+        let count = |method: &str, status: &str| HTTP_REQUESTS.with_label_values(&["/beacon/round/{round}", method, status]).get();
+        let (ok, not_found, other) = (count("GET", "200"), count("GET", "404"), count("other", "200"));
+
+        for path in ["/beacon/round/1", "/beacon/round/2"] {
+            assert_eq!(warp::test::request().path(path).reply(&filter).await.status(), 200);
+        }
+        assert_eq!(warp::test::request().path("/beacon/round/x").reply(&filter).await.status(), 404);
+        warp::test::request().method("PROPFIND").path("/beacon/round/3").reply(&filter).await;
+
+        assert_eq!(count("GET", "200") - ok, 2);
+        assert_eq!(count("GET", "404") - not_found, 1);
+        assert_eq!(count("other", "200") - other, 1);
+        assert!(HTTP_REQUEST_DURATION.with_label_values(&["/beacon/round/{round}"]).get_sample_count() >= 4);
+    }
+
+    #[test]
+    fn exposition_includes_rate_limits_and_request_counters() {
+        HTTP_REQUESTS.with_label_values(&["/metrics", "GET", "200"]).inc();
+        observe_principal(Some("key:ops"));
+        observe_principal(Some("subject@example.com"));
+        let status = RateLimitOutput {
+            enabled: true,
+            clients: 3,
+            budgets: vec![BudgetOutput{ budget: Budget::Prove, rate: 5.0, burst: 10, allowed: 7, limited: 2 }],
+        };
+        let exposition = api_metrics(&status).unwrap();
+
+        assert!(exposition.contains("# TYPE http_requests_total counter"));
+        assert!(exposition.contains(r#"http_requests_total{method="GET",route="/metrics",status="200"}"#));
+        assert!(exposition.contains(r#"auth_requests_total{principal="key:ops"}"#));
+        assert!(exposition.contains(r#"auth_requests_total{principal="jwt"}"#));
+        assert!(exposition.contains(&format!(r#"rate_limit_requests_total{{budget="{}",outcome="allowed"}} 7"#, Budget::Prove)));
+        assert!(exposition.contains(&format!(r#"rate_limit_requests_total{{budget="{}",outcome="limited"}} 2"#, Budget::Prove)));
+        assert!(exposition.contains("rate_limit_clients 3"));
+        assert!(!exposition.contains("subject@example.com"));
+
+        // Families are sorted by name.
+        let names: Vec<&str> = exposition.lines().filter_map(|line| line.strip_prefix("# TYPE ")).map(|line| line.split(' ').next().unwrap()).collect();
+        assert!(names.is_sorted());
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::generator::GeneratorKind;
+use crate::rng::{ChainVersion, IterationsExceeded, SeedInvalid};
+use crate::shuffle;
+
+// Multi-prize draws, specified in `docs/seed-chain.md`. Winners are the first steps of the
+// Fisher-Yates shuffle of tickets `0..tickets`: step `i` moves its pick to position `i` and
+// removes it from the pool, so a ticket wins at most once. Tiers take consecutive winners
+// in request order, which excludes higher-tier winners from the lower tiers.
+
+#[derive(Debug)]
+pub struct TiersInvalid;
+
+#[derive(Debug)]
+pub enum PrizeInputError {
+    SeedInvalid(SeedInvalid),
+    IterationsExceeded(IterationsExceeded),
+    TiersInvalid(TiersInvalid),
+}
+
+#[derive(Deserialize)]
+pub struct TierInputs {
+    #[serde(default)]
+    name: Option<String>,
+    winners: u64,
+}
+
+#[derive(Deserialize)]
+pub struct PrizeInputs {
+    initial_seed: String, // Hex string.
+    tickets: u64,
+    tiers: Vec<TierInputs>, // Highest tier first.
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
+}
+
+#[derive(Serialize)]
+pub struct TierOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name   : Option<String>,
+    pub winners: Vec<u64>, // Ticket numbers in draw order.
+}
+
+#[derive(Serialize)]
+pub struct PrizeOutput {
+    version  : ChainVersion,
+    generator: GeneratorKind,
+    tickets  : u64,
+    tiers    : Vec<TierOutput>,
+}
+
+impl PrizeOutput {
+    pub fn draws(&self) -> usize {
+        self.tiers.iter().map(|tier| tier.winners.len()).sum()
+    }
+}
+
+// The first `count` picks of the shuffle of `0..tickets`, without materializing the ticket list.
+pub fn draw_winners(initial_seed: &str, tickets: u64, count: u64, version: ChainVersion, generator: GeneratorKind) -> Result<Vec<u64>, SeedInvalid> {
+    let mut moved: HashMap<u64, u64> = HashMap::new();
+    let mut winners = Vec::with_capacity(count as usize);
+    // The shuffle stops at `i = 1`; the ticket left at position 0 is the last pick.
+    for (i, j) in shuffle::swaps(initial_seed, tickets, version, generator)?.chain(std::iter::once((0, 0))).take(count as usize) {
+        let picked = moved.get(&j).copied().unwrap_or(j);
+        moved.insert(j, moved.get(&i).copied().unwrap_or(i));
+        winners.push(picked);
+    }
+    Ok(winners)
+}
+
+// Api endpoints.
+pub fn api_prize_draw(prize_inputs: PrizeInputs, max_winners: usize) -> Result<PrizeOutput, PrizeInputError> {
+    if prize_inputs.tiers.is_empty() || prize_inputs.tiers.iter().any(|tier| tier.winners == 0) {
+        return Err(PrizeInputError::TiersInvalid(TiersInvalid));
+    }
+    let total = prize_inputs.tiers.iter().fold(0u64, |total, tier| total.saturating_add(tier.winners));
+    if total > max_winners as u64 {
+        return Err(PrizeInputError::IterationsExceeded(IterationsExceeded));
+    }
+    if total > prize_inputs.tickets {
+        return Err(PrizeInputError::TiersInvalid(TiersInvalid));
+    }
+
+    let winners = match draw_winners(&prize_inputs.initial_seed, prize_inputs.tickets, total, prize_inputs.version, prize_inputs.generator) {
+        Ok(winners) => winners,
+        Err(err) => return Err(PrizeInputError::SeedInvalid(err))
+    };
+    let mut winners = winners.into_iter();
+    let tiers = prize_inputs.tiers.into_iter()
+        .map(|tier| TierOutput{ name: tier.name, winners: winners.by_ref().take(tier.winners as usize).collect() })
+        .collect();
+
+    Ok(PrizeOutput {
+        version  : prize_inputs.version,
+        generator: prize_inputs.generator,
+        tickets  : prize_inputs.tickets,
+        tiers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str = "0123456789abcdef";
+
+    #[test]
+    fn winners_are_the_tail_of_the_shuffle() {
+        let permutation = shuffle::shuffle(SEED, 40, ChainVersion::V2, GeneratorKind::default()).unwrap();
+        let winners = draw_winners(SEED, 40, 40, ChainVersion::V2, GeneratorKind::default()).unwrap();
+        assert_eq!(winners, permutation.into_iter().rev().collect::<Vec<u64>>());
+        assert_eq!(draw_winners(SEED, 40, 5, ChainVersion::V2, GeneratorKind::default()).unwrap(), winners[..5]);
+    }
+
+    #[test]
+    fn winners_are_distinct() {
+        for tickets in [1, 2, 7, 1000] {
+            let mut winners = draw_winners(SEED, tickets, tickets.min(100), ChainVersion::V2, GeneratorKind::default()).unwrap();
+            assert_eq!(winners.len() as u64, tickets.min(100));
+            assert!(winners.iter().all(|&winner| winner < tickets));
+            winners.sort();
+            winners.dedup();
+            assert_eq!(winners.len() as u64, tickets.min(100));
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::auth::Principal;
+
+// Token-bucket rate limiting per client and budget. A client is its principal (`key:<id>`,
+// `jwt:<sub>`) when authenticated, its IP address otherwise. Each bucket holds up to `burst`
+// tokens and refills at `rate` tokens per second; a request takes one token or is answered
+// with 429 and the seconds until the next token in `Retry-After`.
+
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Budget {
+    Prove,  // VRF proofs.
+    Verify, // VRF, Merkle and drand verification.
+    Random, // Draws, shuffles, commitments and self-tests.
+}
+
+pub const BUDGETS: [Budget; 3] = [Budget::Prove, Budget::Verify, Budget::Random];
+
+pub const DEFAULT_PROVE : BucketConfig = BucketConfig{ rate: 5.0, burst: 10 };
+pub const DEFAULT_VERIFY: BucketConfig = BucketConfig{ rate: 50.0, burst: 100 };
+pub const DEFAULT_RANDOM: BucketConfig = BucketConfig{ rate: 10.0, burst: 20 };
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BucketConfig {
+    pub rate : f64, // Tokens per second.
+    pub burst: u32,
+}
+
+#[derive(Serialize)]
+pub struct BudgetOutput {
+    pub budget : Budget,
+    pub rate   : f64,
+    pub burst  : u32,
+    pub allowed: u64,
+    pub limited: u64,
+}
+
+#[derive(Serialize)]
+pub struct RateLimitOutput {
+    pub enabled: bool,
+    pub clients: usize, // Buckets currently tracked.
+    pub budgets: Vec<BudgetOutput>,
+}
+
+struct Bucket {
+    tokens : f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    buckets    : HashMap<(Budget, String), Bucket>,
+    last_pruned: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    allowed: AtomicU64,
+    limited: AtomicU64,
+}
+
+pub struct RateLimiter {
+    enabled            : bool,
+    trust_forwarded_for: bool,
+    configs            : HashMap<Budget, BucketConfig>,
+    counters           : HashMap<Budget, Counters>,
+    buckets            : Mutex<Buckets>,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.rate).min(config.burst as f64);
+        self.updated = now;
+    }
+}
+
+impl RateLimiter {
+    pub fn new(enabled: bool, trust_forwarded_for: bool, configs: HashMap<Budget, BucketConfig>) -> RateLimiter {
+        RateLimiter {
+            enabled,
+            trust_forwarded_for,
+            configs,
+            counters: BUDGETS.iter().map(|&budget| (budget, Counters::default())).collect(),
+            buckets : Mutex::new(Buckets{ buckets: HashMap::new(), last_pruned: Instant::now() }),
+        }
+    }
+
+    // Principal id, else the last `X-Forwarded-For` address (when trusted), else the peer
+    // address. The last entry is the one the proxy appended; earlier ones come from the client.
+    pub fn client(&self, principal: Option<&Principal>, remote: Option<IpAddr>, forwarded_for: Option<&str>) -> String {
+        if let Some(principal) = principal {
+            return principal.id.clone();
+        }
+        let forwarded = forwarded_for
+            .filter(|_forwarded_for| self.trust_forwarded_for)
+            .and_then(|forwarded_for| forwarded_for.rsplit(',').next()?.trim().parse::<IpAddr>().ok());
+        match forwarded.or(remote) {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_string(),
+        }
+    }
+
+    pub fn check(&self, budget: Budget, client: &str, now: Instant) -> Result<(), RateLimited> {
+        let config = match (self.enabled, self.configs.get(&budget)) {
+            (true, Some(config)) => config,
+            _ => return Ok(()),
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        if now.saturating_duration_since(buckets.last_pruned) >= PRUNE_INTERVAL {
+            self.prune(&mut buckets, now);
+        }
+        let bucket = buckets.buckets.entry((budget, client.to_string()))
+            .or_insert(Bucket{ tokens: config.burst as f64, updated: now });
+        bucket.refill(config, now);
+
+        let counters = &self.counters[&budget];
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            counters.allowed.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        } else {
+            counters.limited.fetch_add(1, Ordering::Relaxed);
+            Err(RateLimited{ retry_after_secs: ((1.0 - bucket.tokens) / config.rate).ceil().max(1.0) as u64 })
+        }
+    }
+
+    // Full buckets carry no state, so they can be dropped.
+    fn prune(&self, buckets: &mut Buckets, now: Instant) {
+        buckets.buckets.retain(|(budget, _client), bucket| {
+            bucket.refill(&self.configs[budget], now);
+            bucket.tokens < self.configs[budget].burst as f64
+        });
+        buckets.last_pruned = now;
+    }
+
+    pub fn status(&self) -> RateLimitOutput {
+        RateLimitOutput {
+            enabled: self.enabled,
+            clients: self.buckets.lock().unwrap_or_else(|err| err.into_inner()).buckets.len(),
+            budgets: BUDGETS.iter()
+                .filter_map(|budget| {
+                    let config = self.configs.get(budget)?;
+                    let counters = &self.counters[budget];
+                    Some(BudgetOutput {
+                        budget : *budget,
+                        rate   : config.rate,
+                        burst  : config.burst,
+                        allowed: counters.allowed.load(Ordering::Relaxed),
+                        limited: counters.limited.load(Ordering::Relaxed),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: BucketConfig = BucketConfig{ rate: 0.5, burst: 3 };
+
+    fn rate_limiter(trust_forwarded_for: bool) -> RateLimiter {
+        RateLimiter::new(true, trust_forwarded_for, HashMap::from([(Budget::Prove, CONFIG)]))
+    }
+
+    fn retry_after_secs(result: Result<(), RateLimited>) -> u64 {
+        result.unwrap_err().retry_after_secs
+    }
+
+    #[test]
+    fn burst_is_exhausted_then_limited() {
+        let rate_limiter = rate_limiter(false);
+        let now = Instant::now();
+        for _ in 0..CONFIG.burst {
+            rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now).unwrap();
+        }
+        // One token takes 1 / 0.5 = 2 seconds.
+        assert_eq!(retry_after_secs(rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now)), 2);
+        assert_eq!(retry_after_secs(rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now + Duration::from_millis(500))), 2);
+        assert_eq!(retry_after_secs(rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now + Duration::from_millis(1500))), 1);
+
+        let status = rate_limiter.status();
+        assert_eq!((status.budgets[0].allowed, status.budgets[0].limited), (3, 3));
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let rate_limiter = rate_limiter(false);
+        let now = Instant::now();
+        for _ in 0..CONFIG.burst {
+            rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now).unwrap();
+        }
+        assert!(rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now).is_err());
+        let later = now + Duration::from_secs(2);
+        rate_limiter.check(Budget::Prove, "ip:192.0.2.1", later).unwrap();
+        assert!(rate_limiter.check(Budget::Prove, "ip:192.0.2.1", later).is_err());
+        // Refills stop at the burst.
+        let much_later = later + Duration::from_secs(30);
+        for _ in 0..CONFIG.burst {
+            rate_limiter.check(Budget::Prove, "ip:192.0.2.1", much_later).unwrap();
+        }
+        assert!(rate_limiter.check(Budget::Prove, "ip:192.0.2.1", much_later).is_err());
+    }
+
+    #[test]
+    fn clients_and_budgets_have_separate_buckets() {
+        let rate_limiter = RateLimiter::new(true, false, HashMap::from([(Budget::Prove, CONFIG), (Budget::Verify, CONFIG)]));
+        let now = Instant::now();
+        for _ in 0..CONFIG.burst {
+            rate_limiter.check(Budget::Prove, "key:ops", now).unwrap();
+        }
+        assert!(rate_limiter.check(Budget::Prove, "key:ops", now).is_err());
+        rate_limiter.check(Budget::Prove, "key:other", now).unwrap();
+        rate_limiter.check(Budget::Verify, "key:ops", now).unwrap();
+        // Budgets without a config are not limited.
+        for _ in 0..2 * CONFIG.burst {
+            rate_limiter.check(Budget::Random, "key:ops", now).unwrap();
+        }
+    }
+
+    #[test]
+    fn disabled_limiter_allows_everything() {
+        let rate_limiter = RateLimiter::new(false, false, HashMap::from([(Budget::Prove, CONFIG)]));
+        let now = Instant::now();
+        for _ in 0..2 * CONFIG.burst {
+            rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now).unwrap();
+        }
+        assert_eq!(rate_limiter.status().clients, 0);
+    }
+
+    #[test]
+    fn full_buckets_are_pruned() {
+        let rate_limiter = rate_limiter(false);
+        let now = Instant::now();
+        rate_limiter.check(Budget::Prove, "ip:192.0.2.1", now).unwrap();
+        // Drained just before the interval ends, so still short of the burst when pruned.
+        let drained = now + PRUNE_INTERVAL - Duration::from_secs(1);
+        for _ in 0..CONFIG.burst {
+            rate_limiter.check(Budget::Prove, "ip:192.0.2.2", drained).unwrap();
+        }
+        assert_eq!(rate_limiter.status().clients, 2);
+
+        rate_limiter.check(Budget::Prove, "ip:192.0.2.3", now + PRUNE_INTERVAL).unwrap();
+        let buckets = rate_limiter.buckets.lock().unwrap();
+        let mut clients = buckets.buckets.keys().map(|(_budget, client)| client.as_str()).collect::<Vec<_>>();
+        clients.sort();
+        assert_eq!(clients, ["ip:192.0.2.2", "ip:192.0.2.3"]);
+    }
+
+    #[test]
+    fn forwarded_for_is_only_used_when_trusted() {
+        let remote = Some("198.51.100.7".parse().unwrap());
+        // The client sent the first entry, the proxy appended the second.
+        let forwarded_for = Some("203.0.113.9, 192.0.2.44");
+        assert_eq!(rate_limiter(false).client(None, remote, forwarded_for), "ip:198.51.100.7");
+        assert_eq!(rate_limiter(true).client(None, remote, forwarded_for), "ip:192.0.2.44");
+        assert_eq!(rate_limiter(true).client(None, remote, Some("203.0.113.10, 192.0.2.44")), "ip:192.0.2.44");
+        assert_eq!(rate_limiter(true).client(None, remote, Some("not-an-ip")), "ip:198.51.100.7");
+        assert_eq!(rate_limiter(true).client(None, None, None), "ip:unknown");
+
+        let principal = Principal{ id: "key:ops".to_string(), scopes: Vec::new() };
+        assert_eq!(rate_limiter(true).client(Some(&principal), remote, forwarded_for), "key:ops");
+    }
+}
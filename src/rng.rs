@@ -1,74 +1,752 @@
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
 use num_bigint::BigUint;
-use std::str::FromStr;
-use hex;
+use sha2::{Sha512, Digest};
 
-use crate::utils;
+use crate::generator::{self, Generator, GeneratorKind};
+
+// Defaults for `limits.max_iterations` and `limits.max_stream_iterations`, see `config`.
+pub const MAX_ITERATIONS: usize = 3100;
+pub const MAX_SEED_LEN: usize = 64; // Bytes.
+pub const V2_SEED_LEN: usize = 32; // Bytes.
+pub const DEFAULT_MAX_STREAM_ITERATIONS: usize = 1_000_000;
 
 #[derive(Debug)]
 pub struct IterationsExceeded;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug)]
+pub struct SeedInvalid;
+
+#[derive(Debug)]
+pub struct DistributionInvalid;
+
+#[derive(Debug)]
+pub enum RngInputError {
+    SeedInvalid(SeedInvalid),
+    IterationsExceeded(IterationsExceeded),
+    DistributionInvalid(DistributionInvalid),
+}
+
+// Seed-chain versions, specified in `docs/seed-chain.md`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize, ToSchema)]
+pub enum ChainVersion {
+    // Hashes the chain number without leading zero bytes.
+    #[default]
+    #[strum(serialize = "v1")]
+    #[serde(rename = "v1")]
+    V1,
+    // Hashes the chain number as exactly 32 big-endian bytes.
+    #[strum(serialize = "v2")]
+    #[serde(rename = "v2")]
+    V2,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct RngResult {
     pub ticket_number: u64,
-    pub new_seed: String, // Hex string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_seed: Option<String>, // Hex string, SHA-512 chain only.
     pub random_result: String, // Hex string.
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct RngInputs {
     initial_seed: String, // Hex string.
-    pub iterations: usize,
-    limit: u64
+    iterations: usize,
+    limit: u64,
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct RngOutput {
-    results: Vec<RngResult>
+    version  : ChainVersion,
+    generator: GeneratorKind,
+    results  : Vec<RngResult>
+}
+
+impl RngOutput {
+    pub fn draws(&self) -> usize {
+        self.results.len()
+    }
+}
+
+pub struct RngStream {
+    pub version  : ChainVersion,
+    pub generator: GeneratorKind,
+    pub results  : std::iter::Take<RngIter>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RngPageInputs {
+    initial_seed: String, // Hex string.
+    #[serde(default)]
+    start: usize,
+    count: usize,
+    limit: u64,
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RngPageOutput {
+    version   : ChainVersion,
+    generator : GeneratorKind,
+    results   : Vec<RngResult>,
+    start     : usize,
+    next_start: usize,
+    // Hex string, resumes the chain at `next_start` with `start: 0`. SHA-512 chain only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_seed : Option<String>,
+}
+
+impl RngPageOutput {
+    pub fn draws(&self) -> usize {
+        self.results.len()
+    }
+}
+
+// Typed draws, specified in `docs/seed-chain.md`. Each draw consumes generator blocks
+// in request order; `scale(block, k)` is the same multiply-shift used for tickets.
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Distribution {
+    // Signed integer in [min, max].
+    Range { min: i64, max: i64 },
+    // Fixed-point decimal in [0, 1) with `decimals` digits.
+    Float { #[serde(default = "default_decimals")] decimals: u32 },
+    // `count` rolls of a die with faces 1..=sides.
+    Dice { sides: u64, #[serde(default = "default_dice_count")] count: usize },
+    // `true` with probability `p`, a decimal string such as "0.25".
+    Coin { p: String },
+    // One outcome, picked with probability weight / sum(weights).
+    Table { outcomes: Vec<TableOutcome> },
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TableOutcome {
+    value : String,
+    weight: u64,
+}
+
+fn default_decimals() -> u32 {
+    MAX_DECIMALS
+}
+
+fn default_dice_count() -> usize {
+    1
+}
+
+pub const MAX_DECIMALS: u32 = 18;
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DistributionResult {
+    Range { value: i64, random_result: String },
+    Float { value: String, random_result: String },
+    Dice { rolls: Vec<u64>, total: u64, random_results: Vec<String> },
+    Coin { value: bool, random_result: String },
+    Table { index: usize, value: String, random_result: String },
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DistributionInputs {
+    initial_seed: String, // Hex string.
+    draws: Vec<Distribution>,
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DistributionOutput {
+    version  : ChainVersion,
+    generator: GeneratorKind,
+    results  : Vec<DistributionResult>
+}
+
+impl DistributionOutput {
+    pub fn draws(&self) -> usize {
+        self.results.len()
+    }
+}
+
+// Byte-level SHA-512 seed chain. The current number is kept right-aligned in a 64-byte
+// buffer; `start` marks the bytes that get hashed for the chain version:
+//   v1: without leading zero bytes (a zero number hashes as a single 0x00), which is
+//       exactly what `BigUint::to_bytes_be` produced in the original implementation.
+//   v2: the last 32 bytes, i.e. the number as fixed-width big-endian.
+pub struct SeedChain {
+    buf    : [u8; MAX_SEED_LEN],
+    start  : usize,
+    limit  : u64,
+    version: ChainVersion,
+}
+
+pub struct ChainStep {
+    pub ticket_number: u64,
+    pub new_seed     : [u8; 32],
+    pub random_result: [u8; 32],
+}
+
+impl SeedChain {
+    pub fn new(seed: &[u8], limit: u64, version: ChainVersion) -> Result<SeedChain, SeedInvalid> {
+        let max_seed_len = match version {
+            ChainVersion::V1 => MAX_SEED_LEN,
+            ChainVersion::V2 => V2_SEED_LEN,
+        };
+        if seed.is_empty() || seed.len() > max_seed_len {
+            return Err(SeedInvalid);
+        }
+        let mut chain = SeedChain{ buf: [0u8; MAX_SEED_LEN], start: 0, limit, version };
+        chain.set(seed);
+        Ok(chain)
+    }
+
+    fn set(&mut self, number: &[u8]) {
+        let offset = MAX_SEED_LEN - number.len();
+        self.buf[..offset].fill(0);
+        self.buf[offset..].copy_from_slice(number);
+        self.start = match self.version {
+            ChainVersion::V1 => self.buf[..MAX_SEED_LEN - 1]
+                .iter()
+                .position(|byte| *byte != 0)
+                .unwrap_or(MAX_SEED_LEN - 1),
+            ChainVersion::V2 => MAX_SEED_LEN - V2_SEED_LEN,
+        };
+    }
+}
+
+impl Iterator for SeedChain {
+    type Item = ChainStep;
+
+    fn next(&mut self) -> Option<ChainStep> {
+        let hash: [u8; 64] = Sha512::digest(&self.buf[self.start..]).into();
+
+        let mut step = ChainStep{ ticket_number: 0, new_seed: [0u8; 32], random_result: [0u8; 32] };
+        step.new_seed.copy_from_slice(&hash[..32]);
+        step.random_result.copy_from_slice(&hash[32..]);
+        step.ticket_number = scale_ticket(&step.random_result, self.limit);
+
+        self.set(&step.new_seed);
+        Some(step)
+    }
+}
+
+// (random_result * limit) >> 256, i.e. the top 64 bits of a 256x64-bit product.
+pub fn scale_ticket(random_result: &[u8; 32], limit: u64) -> u64 {
+    let mut carry: u128 = 0;
+    for limb in random_result.chunks_exact(8).rev() {
+        let limb = u64::from_be_bytes(limb.try_into().expect("8-byte limb"));
+        carry = (limb as u128 * limit as u128 + carry) >> 64;
+    }
+    carry as u64
+}
+
+// Top 64 bits of block * 2^64, for spans that do not fit in a u64.
+fn scale_full(random_result: &[u8; 32]) -> u64 {
+    u64::from_be_bytes(random_result[..8].try_into().expect("8-byte prefix"))
+}
+
+// "0.25" -> (25, 100). Accepts 0 <= p <= 1 with at most `MAX_DECIMALS` fractional digits; a
+// point must be followed by at least one digit.
+fn parse_probability(p: &str) -> Result<(u64, u64), DistributionInvalid> {
+    let (whole, fraction) = p.split_once('.').unwrap_or((p, ""));
+    if p.ends_with('.') || fraction.len() > MAX_DECIMALS as usize || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(DistributionInvalid);
+    }
+    let denominator = 10u64.pow(fraction.len() as u32);
+    let numerator = if fraction.is_empty() { 0 } else { fraction.parse::<u64>().map_err(|_err| DistributionInvalid)? };
+    match whole {
+        "0" => Ok((numerator, denominator)),
+        "1" if numerator == 0 => Ok((denominator, denominator)),
+        _ => Err(DistributionInvalid)
+    }
+}
+
+impl Distribution {
+    fn blocks(&self) -> usize {
+        match self {
+            Distribution::Dice { count, .. } => *count,
+            _ => 1,
+        }
+    }
+
+    fn validate(&self) -> Result<(), DistributionInvalid> {
+        let valid = match self {
+            Distribution::Range { min, max } => min <= max,
+            Distribution::Float { decimals } => (1..=MAX_DECIMALS).contains(decimals),
+            Distribution::Dice { sides, count } => *sides > 0 && *count > 0,
+            Distribution::Coin { p } => parse_probability(p).is_ok(),
+            Distribution::Table { outcomes } => !outcomes.is_empty() && outcomes
+                .iter()
+                .try_fold(0u64, |total, outcome| total.checked_add(outcome.weight))
+                .is_some_and(|total| total > 0),
+        };
+        if valid { Ok(()) } else { Err(DistributionInvalid) }
+    }
+
+    fn draw(&self, generator: &mut dyn Generator) -> DistributionResult {
+        match self {
+            Distribution::Range { min, max } => {
+                let block = generator.next_block().random_result;
+                let span = (*max as i128 - *min as i128) as u64;
+                let offset = match span.checked_add(1) {
+                    Some(span) => scale_ticket(&block, span),
+                    None => scale_full(&block),
+                };
+                DistributionResult::Range{ value: (*min as i128 + offset as i128) as i64, random_result: hex::encode(block) }
+            },
+            Distribution::Float { decimals } => {
+                let block = generator.next_block().random_result;
+                let numerator = scale_ticket(&block, 10u64.pow(*decimals));
+                let value = format!("0.{:0width$}", numerator, width = *decimals as usize);
+                DistributionResult::Float{ value, random_result: hex::encode(block) }
+            },
+            Distribution::Dice { sides, count } => {
+                let blocks: Vec<[u8; 32]> = (0..*count).map(|_| generator.next_block().random_result).collect();
+                let rolls: Vec<u64> = blocks.iter().map(|block| 1 + scale_ticket(block, *sides)).collect();
+                DistributionResult::Dice {
+                    total         : rolls.iter().fold(0u64, |total, roll| total.saturating_add(*roll)),
+                    rolls,
+                    random_results: blocks.iter().map(hex::encode).collect(),
+                }
+            },
+            Distribution::Coin { p } => {
+                let block = generator.next_block().random_result;
+                let (numerator, denominator) = parse_probability(p).expect("validated probability");
+                DistributionResult::Coin{ value: scale_ticket(&block, denominator) < numerator, random_result: hex::encode(block) }
+            },
+            Distribution::Table { outcomes } => {
+                let block = generator.next_block().random_result;
+                let total: u64 = outcomes.iter().map(|outcome| outcome.weight).sum();
+                let mut remaining = scale_ticket(&block, total);
+                let index = outcomes
+                    .iter()
+                    .position(|outcome| {
+                        let hit = remaining < outcome.weight;
+                        remaining = remaining.saturating_sub(outcome.weight);
+                        hit
+                    })
+                    .expect("ticket is below the total weight");
+                DistributionResult::Table{ index, value: outcomes[index].value.clone(), random_result: hex::encode(block) }
+            },
+        }
+    }
+}
+
+pub fn distribution(initial_seed: &str, draws: &[Distribution], version: ChainVersion, generator: GeneratorKind) -> Result<Vec<DistributionResult>, RngInputError> {
+    for draw in draws {
+        draw.validate().map_err(RngInputError::DistributionInvalid)?;
+    }
+    let mut generator = generator::new_generator(generator, &decode_seed(initial_seed).map_err(RngInputError::SeedInvalid)?, version)
+        .map_err(RngInputError::SeedInvalid)?;
+
+    Ok(draws.iter().map(|draw| draw.draw(generator.as_mut())).collect())
+}
+
+pub struct RngIter {
+    generator: Box<dyn Generator>,
+    limit    : u64,
+}
+
+impl Iterator for RngIter {
+    type Item = RngResult;
+
+    fn next(&mut self) -> Option<RngResult> {
+        let block = self.generator.next_block();
+        Some(RngResult {
+            ticket_number: scale_ticket(&block.random_result, self.limit),
+            new_seed     : block.new_seed.map(hex::encode),
+            random_result: hex::encode(block.random_result),
+        })
+    }
+}
+
+// Seeds are non-empty, even-length hex strings of at most `MAX_SEED_LEN` bytes.
+pub fn decode_seed(seed: &str) -> Result<Vec<u8>, SeedInvalid> {
+    if seed.is_empty() || !seed.len().is_multiple_of(2) || seed.len() > MAX_SEED_LEN * 2 {
+        return Err(SeedInvalid);
+    }
+    hex::decode(seed).map_err(|_err| SeedInvalid)
+}
+
+pub fn random_iter(initial_seed: &str, limit: u64, version: ChainVersion, generator: GeneratorKind) -> Result<RngIter, SeedInvalid> {
+    Ok(RngIter{ generator: generator::new_generator(generator, &decode_seed(initial_seed)?, version)?, limit })
 }
 
 pub fn random(
     initial_seed: &str,
     iterations: usize,
-    limit: u64
-) -> Vec<RngResult> {
-    let mut current_number = BigUint::from_str(&utils::convert_to_int_string(initial_seed.to_string(), utils::ConvertInputValueType::Be).unwrap())
-        .expect("Invalid initial seed");
+    limit: u64,
+    version: ChainVersion,
+    generator: GeneratorKind
+) -> Result<Vec<RngResult>, SeedInvalid> {
+    Ok(random_iter(initial_seed, limit, version, generator)?.take(iterations).collect())
+}
+
+// Straightforward transcription of `docs/seed-chain.md`, for verifiers and cross-checks.
+// Slow on purpose: use `random` or `SeedChain` for real draws.
+pub fn reference_random(initial_seed: &[u8], iterations: usize, limit: u64, version: ChainVersion) -> Vec<RngResult> {
+    let mut number = BigUint::from_bytes_be(initial_seed);
     let mut results = Vec::with_capacity(iterations);
 
     for _ in 0..iterations {
-        let hash = utils::sha512(utils::convert_to_hex_string(current_number.to_string(), utils::ConvertInputValueType::Be).unwrap()).unwrap();
-
-        // Split hash into two halves
-        let (first_half_hex, second_half_hex) = hash.split_at(64);
-        
-        // Convert hex halves to bytes
-        let first_half_bytes = hex::decode(first_half_hex).expect("Invalid hex");
-        let second_half_bytes = hex::decode(second_half_hex).expect("Invalid hex");
-        
-        // Update current number for next iteration
-        current_number = BigUint::from_bytes_be(&first_half_bytes);
-        
-        // Calculate ticket number
-        let random_int = BigUint::from_bytes_be(&second_half_bytes);
-        let ticket_number = (random_int * BigUint::from(limit)) >> 256usize;
-        let ticket_number = ticket_number.to_u64_digits()
-            .first()
-            .copied()
-            .unwrap_or(0);
-
-        results.push(
-            RngResult {
-                ticket_number: ticket_number,
-                new_seed     : first_half_hex.to_string(),
-                random_result: second_half_hex.to_string(),
-            }
-        );
+        let encoded = match version {
+            ChainVersion::V1 => number.to_bytes_be(),
+            ChainVersion::V2 => {
+                let bytes = number.to_bytes_be();
+                let mut padded = vec![0u8; V2_SEED_LEN.saturating_sub(bytes.len())];
+                padded.extend(bytes);
+                padded
+            },
+        };
+        let hash = Sha512::digest(&encoded);
+        let (h1, h2) = hash.split_at(32);
+
+        number = BigUint::from_bytes_be(h1);
+        let ticket_number = (BigUint::from_bytes_be(h2) * BigUint::from(limit)) >> 256usize;
+
+        results.push(RngResult {
+            ticket_number: ticket_number.to_u64_digits().first().copied().unwrap_or(0),
+            new_seed     : Some(hex::encode(h1)),
+            random_result: hex::encode(h2),
+        });
     }
 
     results
 }
 
-pub fn api_random(rng_inputs: RngInputs) -> RngOutput {
-    return RngOutput{ results: random(&rng_inputs.initial_seed, rng_inputs.iterations, rng_inputs.limit) };
+pub fn api_random(rng_inputs: RngInputs, max_iterations: usize) -> Result<RngOutput, RngInputError> {
+    if rng_inputs.iterations > max_iterations {
+        return Err(RngInputError::IterationsExceeded(IterationsExceeded));
+    }
+    match random(&rng_inputs.initial_seed, rng_inputs.iterations, rng_inputs.limit, rng_inputs.version, rng_inputs.generator) {
+        Ok(results) => Ok(RngOutput{ version: rng_inputs.version, generator: rng_inputs.generator, results }),
+        Err(_err) => Err(RngInputError::SeedInvalid(SeedInvalid))
+    }
+}
+
+pub fn api_random_stream(rng_inputs: RngInputs, max_iterations: usize) -> Result<RngStream, RngInputError> {
+    if rng_inputs.iterations > max_iterations {
+        return Err(RngInputError::IterationsExceeded(IterationsExceeded));
+    }
+    match random_iter(&rng_inputs.initial_seed, rng_inputs.limit, rng_inputs.version, rng_inputs.generator) {
+        Ok(rng_iter) => Ok(RngStream {
+            version  : rng_inputs.version,
+            generator: rng_inputs.generator,
+            results  : rng_iter.take(rng_inputs.iterations),
+        }),
+        Err(_err) => Err(RngInputError::SeedInvalid(SeedInvalid))
+    }
+}
+
+// Pages hold at most `max_count` results and end within the first `max_iterations`.
+pub fn check_page(start: usize, count: usize, max_count: usize, max_iterations: usize) -> Result<(), RngInputError> {
+    if count > max_count || start.saturating_add(count) > max_iterations {
+        return Err(RngInputError::IterationsExceeded(IterationsExceeded));
+    }
+    Ok(())
+}
+
+// Results `start` to `start + count` of the chain, and the seed that resumes it after them
+// (SHA-512 chain only). Shared by `/api/random/page` and `/api/v2/random/tickets`.
+pub fn random_page(
+    initial_seed: &str,
+    start: usize,
+    count: usize,
+    limit: u64,
+    version: ChainVersion,
+    generator: GeneratorKind
+) -> Result<(Vec<RngResult>, Option<String>), RngInputError> {
+    let mut rng_iter = random_iter(initial_seed, limit, version, generator).map_err(RngInputError::SeedInvalid)?;
+
+    // Generators have no random access; skipping still costs one block per iteration.
+    let mut next_seed = (generator == GeneratorKind::Sha512Chain).then(|| initial_seed.to_string());
+    if start > 0 && let Some(skipped) = rng_iter.nth(start - 1) {
+        next_seed = skipped.new_seed;
+    }
+    let results: Vec<RngResult> = rng_iter.take(count).collect();
+    if let Some(last) = results.last() {
+        next_seed = last.new_seed.clone();
+    }
+
+    Ok((results, next_seed))
+}
+
+pub fn api_random_page(page_inputs: RngPageInputs, max_count: usize, max_iterations: usize) -> Result<RngPageOutput, RngInputError> {
+    check_page(page_inputs.start, page_inputs.count, max_count, max_iterations)?;
+    let (results, next_seed) = random_page(&page_inputs.initial_seed, page_inputs.start, page_inputs.count, page_inputs.limit, page_inputs.version, page_inputs.generator)?;
+
+    Ok(RngPageOutput {
+        version   : page_inputs.version,
+        generator : page_inputs.generator,
+        start     : page_inputs.start,
+        next_start: page_inputs.start + results.len(),
+        results,
+        next_seed,
+    })
+}
+
+// Generator blocks consumed by `draws`, or `IterationsExceeded` above `max_iterations`.
+pub fn distribution_blocks(draws: &[Distribution], max_iterations: usize) -> Result<usize, RngInputError> {
+    let blocks = draws
+        .iter()
+        .try_fold(0usize, |total, draw| total.checked_add(draw.blocks()));
+    match blocks {
+        Some(blocks) if blocks <= max_iterations => Ok(blocks),
+        _ => Err(RngInputError::IterationsExceeded(IterationsExceeded))
+    }
+}
+
+pub fn api_distribution(distribution_inputs: DistributionInputs, max_iterations: usize) -> Result<DistributionOutput, RngInputError> {
+    distribution_blocks(&distribution_inputs.draws, max_iterations)?;
+    let results = distribution(&distribution_inputs.initial_seed, &distribution_inputs.draws, distribution_inputs.version, distribution_inputs.generator)?;
+
+    Ok(DistributionOutput{ version: distribution_inputs.version, generator: distribution_inputs.generator, results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use crate::utils;
+
+    const LIMIT: u64 = 1_000_000;
+
+    // The original string round-trip implementation, which v1 must reproduce bit for bit.
+    fn legacy_random(initial_seed: &str, iterations: usize, limit: u64) -> Vec<RngResult> {
+        let mut current_number = BigUint::from_str(&utils::convert_to_int_string(initial_seed.to_string(), utils::ConvertInputValueType::Be).unwrap()).unwrap();
+        let mut results = Vec::with_capacity(iterations);
+
+        for _ in 0..iterations {
+            let hash = utils::sha512(utils::convert_to_hex_string(current_number.to_string(), utils::ConvertInputValueType::Be).unwrap()).unwrap();
+            let (first_half_hex, second_half_hex) = hash.split_at(64);
+            current_number = BigUint::from_bytes_be(&hex::decode(first_half_hex).unwrap());
+            let ticket_number = (BigUint::from_bytes_be(&hex::decode(second_half_hex).unwrap()) * BigUint::from(limit)) >> 256usize;
+
+            results.push(RngResult {
+                ticket_number: ticket_number.to_u64_digits().first().copied().unwrap_or(0),
+                new_seed     : Some(first_half_hex.to_string()),
+                random_result: second_half_hex.to_string(),
+            });
+        }
+
+        results
+    }
+
+    fn assert_same(expected: &[RngResult], actual: &[RngResult], seed: &str) {
+        assert_eq!(expected.len(), actual.len(), "seed {}", seed);
+        for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+            assert_eq!(expected.ticket_number, actual.ticket_number, "seed {} step {}", seed, i);
+            assert_eq!(expected.new_seed, actual.new_seed, "seed {} step {}", seed, i);
+            assert_eq!(expected.random_result, actual.random_result, "seed {} step {}", seed, i);
+        }
+    }
+
+    // Leading zero bytes are dropped by v1 and kept by v2; an all-zero seed is the number 0,
+    // which v1 hashes as the single byte 0x00, the same as an all-zero `new_seed` mid-chain.
+    const SEEDS: [&str; 5] = [
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "00ff",
+        "00",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+    ];
+
+    #[test]
+    fn v1_matches_the_legacy_implementation() {
+        let long_seed = "00".repeat(3) + &"ab".repeat(61);
+        for seed in SEEDS.iter().copied().chain([long_seed.as_str()]) {
+            let actual = random(seed, 1000, LIMIT, ChainVersion::V1, GeneratorKind::Sha512Chain).unwrap();
+            assert_same(&legacy_random(seed, 1000, LIMIT), &actual, seed);
+        }
+    }
+
+    #[test]
+    fn both_versions_match_the_reference() {
+        for version in [ChainVersion::V1, ChainVersion::V2] {
+            for seed in SEEDS {
+                let actual = random(seed, 1000, LIMIT, version, GeneratorKind::Sha512Chain).unwrap();
+                assert_same(&reference_random(&hex::decode(seed).unwrap(), 1000, LIMIT, version), &actual, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn v1_hashes_zero_as_one_byte() {
+        let zero = random(&"00".repeat(32), 1, LIMIT, ChainVersion::V1, GeneratorKind::Sha512Chain).unwrap();
+        let expected = hex::encode(&Sha512::digest([0u8])[32..]);
+        assert_eq!(zero[0].random_result, expected);
+    }
+
+    #[test]
+    fn invalid_seeds_are_rejected() {
+        let too_long = "ab".repeat(MAX_SEED_LEN + 1);
+        for seed in ["", "0", "abc", "zz", "0x00", "00 ", "éé", &too_long] {
+            assert!(decode_seed(seed).is_err(), "{:?}", seed);
+            assert!(matches!(random_page(seed, 0, 1, LIMIT, ChainVersion::V2, GeneratorKind::Sha512Chain), Err(RngInputError::SeedInvalid(_))), "{:?}", seed);
+        }
+        assert_eq!(decode_seed(&"AB".repeat(MAX_SEED_LEN)).unwrap(), vec![0xab; MAX_SEED_LEN]);
+    }
+
+    #[test]
+    fn pages_resume_the_same_chain() {
+        let generators = [GeneratorKind::Sha512Chain, GeneratorKind::HmacDrbg, GeneratorKind::ChaCha20, GeneratorKind::Blake3Xof];
+        for (version, generator) in [ChainVersion::V1, ChainVersion::V2].into_iter().flat_map(|version| generators.map(|generator| (version, generator))) {
+            let chain: Vec<RngResult> = random_iter(SEEDS[0], LIMIT, version, generator).unwrap().take(20).collect();
+            for (start, count) in [(0, 0), (0, 1), (0, 20), (1, 5), (7, 13), (19, 1), (20, 0)] {
+                let (page, next_seed) = random_page(SEEDS[0], start, count, LIMIT, version, generator).unwrap();
+                assert_same(&chain[start..start + count], &page, SEEDS[0]);
+
+                // The SHA-512 chain resumes from `next_seed`; the other generators keep their state private.
+                if generator != GeneratorKind::Sha512Chain {
+                    assert_eq!(next_seed, None);
+                    continue;
+                }
+                let next_seed = next_seed.unwrap();
+                let expected = match start + count {
+                    0 => SEEDS[0].to_string(),
+                    end => chain[end - 1].new_seed.clone().unwrap(),
+                };
+                assert_eq!(next_seed, expected);
+                let (resumed, _) = random_page(&next_seed, 0, 20 - start - count, LIMIT, version, generator).unwrap();
+                assert_same(&chain[start + count..], &resumed, &next_seed);
+            }
+        }
+    }
+
+    #[test]
+    fn page_limits_are_inclusive() {
+        assert!(check_page(0, 10, 10, 10).is_ok());
+        assert!(check_page(5, 5, 10, 10).is_ok());
+        assert!(check_page(10, 0, 10, 10).is_ok());
+        assert!(check_page(0, 11, 10, 20).is_err());
+        assert!(check_page(6, 5, 10, 10).is_err());
+        assert!(check_page(11, 0, 10, 10).is_err());
+        assert!(matches!(check_page(usize::MAX, 1, 10, usize::MAX - 1), Err(RngInputError::IterationsExceeded(_))));
+    }
+
+    fn parse_draws(draws: serde_json::Value) -> Vec<Distribution> {
+        serde_json::from_value(draws).unwrap()
+    }
+
+    #[test]
+    fn probabilities_parse_as_exact_fractions() {
+        assert_eq!(parse_probability("0").unwrap(), (0, 1));
+        assert_eq!(parse_probability("1").unwrap(), (1, 1));
+        assert_eq!(parse_probability("1.000").unwrap(), (1000, 1000));
+        assert_eq!(parse_probability("0.25").unwrap(), (25, 100));
+        assert_eq!(parse_probability(&format!("0.{}", "9".repeat(18))).unwrap(), (10u64.pow(18) - 1, 10u64.pow(18)));
+        for p in ["1.5", "1.001", "2", ".5", "0.", "1.", "", "00.5", "-0", "+0.5", "0.-5", "0.5e1", " 0.5"] {
+            assert!(parse_probability(p).is_err(), "{:?}", p);
+        }
+        assert!(parse_probability(&format!("0.{}", "1".repeat(19))).is_err());
+        assert!(parse_probability(&format!("1.{}", "0".repeat(19))).is_err());
+    }
+
+    #[test]
+    fn invalid_draws_are_rejected() {
+        let draws = parse_draws(serde_json::json!([
+            { "type": "range", "min": 1, "max": 0 },
+            { "type": "float", "decimals": 0 },
+            { "type": "float", "decimals": 19 },
+            { "type": "dice", "sides": 0 },
+            { "type": "dice", "sides": 6, "count": 0 },
+            { "type": "coin", "p": "1.5" },
+            { "type": "table", "outcomes": [] },
+            { "type": "table", "outcomes": [{ "value": "a", "weight": 0 }, { "value": "b", "weight": 0 }] },
+            { "type": "table", "outcomes": [{ "value": "a", "weight": u64::MAX }, { "value": "b", "weight": 1 }] },
+        ]));
+        for draw in &draws {
+            assert!(draw.validate().is_err());
+            assert!(matches!(distribution("00", std::slice::from_ref(draw), ChainVersion::V2, GeneratorKind::default()), Err(RngInputError::DistributionInvalid(_))));
+        }
+        // The largest total that fits is accepted.
+        let draws = parse_draws(serde_json::json!([{ "type": "table", "outcomes": [{ "value": "a", "weight": u64::MAX - 1 }, { "value": "b", "weight": 1 }] }]));
+        assert!(draws[0].validate().is_ok());
+    }
+
+    #[test]
+    fn full_i64_range_uses_the_top_64_bits() {
+        let draws = parse_draws(serde_json::json!([{ "type": "range", "min": i64::MIN, "max": i64::MAX }]));
+        let blocks = random(SEEDS[0], 1, LIMIT, ChainVersion::V2, GeneratorKind::default()).unwrap();
+        let block: [u8; 32] = hex::decode(&blocks[0].random_result).unwrap().try_into().unwrap();
+        let DistributionResult::Range{ value, random_result } = &distribution(SEEDS[0], &draws, ChainVersion::V2, GeneratorKind::default()).unwrap()[0] else {
+            panic!("range draw");
+        };
+        assert_eq!(random_result, &blocks[0].random_result);
+        assert_eq!(*value, (scale_full(&block) as i64).wrapping_add(i64::MIN));
+
+        assert_eq!(scale_full(&[0; 32]), 0);
+        assert_eq!(scale_full(&[0xff; 32]), u64::MAX);
+        let mut block = [0u8; 32];
+        block[0] = 0x80;
+        assert_eq!((scale_full(&block) as i64).wrapping_add(i64::MIN), 0);
+    }
+
+    #[test]
+    fn dice_take_one_block_per_roll() {
+        let draws = parse_draws(serde_json::json!([
+            { "type": "dice", "sides": 6, "count": 3 },
+            { "type": "coin", "p": "0.5" },
+            { "type": "dice", "sides": 20 },
+        ]));
+        assert_eq!(distribution_blocks(&draws, 5).unwrap(), 5);
+        assert!(matches!(distribution_blocks(&draws, 4), Err(RngInputError::IterationsExceeded(_))));
+        let huge = parse_draws(serde_json::json!([{ "type": "dice", "sides": 6, "count": usize::MAX }, { "type": "coin", "p": "1" }]));
+        assert!(matches!(distribution_blocks(&huge, usize::MAX), Err(RngInputError::IterationsExceeded(_))));
+
+        // The results consume the chain in request order.
+        let blocks: Vec<String> = random(SEEDS[0], 5, LIMIT, ChainVersion::V2, GeneratorKind::default()).unwrap()
+            .into_iter()
+            .map(|result| result.random_result)
+            .collect();
+        let results = distribution(SEEDS[0], &draws, ChainVersion::V2, GeneratorKind::default()).unwrap();
+        let DistributionResult::Dice{ rolls, total, random_results } = &results[0] else { panic!("dice draw") };
+        assert_eq!(random_results[..], blocks[..3]);
+        assert_eq!(rolls.len(), 3);
+        assert!(rolls.iter().all(|roll| (1..=6).contains(roll)));
+        assert_eq!(*total, rolls.iter().sum::<u64>());
+        let DistributionResult::Coin{ random_result, .. } = &results[1] else { panic!("coin draw") };
+        assert_eq!(random_result, &blocks[3]);
+        let DistributionResult::Dice{ random_results, .. } = &results[2] else { panic!("dice draw") };
+        assert_eq!(random_results[..], blocks[4..]);
+    }
+
+    // Pinned outputs: a change here changes published draws.
+    #[test]
+    fn distribution_outputs_are_pinned() {
+        let draws = parse_draws(serde_json::json!([
+            { "type": "range", "min": -10, "max": 10 },
+            { "type": "float", "decimals": 6 },
+            { "type": "dice", "sides": 6, "count": 2 },
+            { "type": "coin", "p": "0.25" },
+            { "type": "table", "outcomes": [{ "value": "gold", "weight": 1 }, { "value": "silver", "weight": 3 }, { "value": "bronze", "weight": 6 }] },
+        ]));
+        let results = serde_json::to_value(distribution(SEEDS[0], &draws, ChainVersion::V2, GeneratorKind::default()).unwrap()).unwrap();
+        let values: Vec<serde_json::Value> = results.as_array().unwrap().iter()
+            .map(|result| {
+                let mut result = result.clone();
+                let result = result.as_object_mut().unwrap();
+                result.remove("random_result");
+                result.remove("random_results");
+                serde_json::Value::Object(result.clone())
+            })
+            .collect();
+        assert_eq!(serde_json::Value::Array(values), serde_json::json!([
+            { "type": "range", "value": -9 },
+            { "type": "float", "value": "0.196153" },
+            { "type": "dice", "rolls": [2, 5], "total": 7 },
+            { "type": "coin", "value": false },
+            { "type": "table", "index": 2, "value": "bronze" },
+        ]));
+    }
 }
@@ -0,0 +1,321 @@
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use statrs::function::erf::erfc;
+use statrs::function::gamma::gamma_ur;
+use strum::{Display, EnumString};
+use vrf_r255::{PublicKey, SecretKey};
+
+use crate::generator::{self, GeneratorKind};
+use crate::rng::{self, ChainVersion, IterationsExceeded, SeedInvalid};
+
+// Statistical self-test battery over generated output. Every test reports a p-value and
+// passes when it is at least `TEST_SIGNIFICANCE`: the level recommended by NIST SP 800-22,
+// Bonferroni-corrected over the `TESTS` tests so the whole battery falsely fails about 1% of
+// the time rather than 6%.
+// The bit tests run over the concatenated sample bytes (most significant bit first); the
+// ticket tests use one ticket or one fraction per sample.
+
+pub const SIGNIFICANCE: f64 = 0.01;
+pub const TESTS: usize = 6;
+pub const TEST_SIGNIFICANCE: f64 = SIGNIFICANCE / TESTS as f64;
+pub const BLOCK_FREQUENCY_BITS: usize = 128;
+pub const DEFAULT_SAMPLES: usize = 10_000;
+pub const DEFAULT_BUCKETS: u64 = 16;
+pub const MAX_BUCKETS: u64 = 1024;
+pub const MIN_SAMPLES_PER_BUCKET: usize = 5;
+
+#[derive(Debug)]
+pub struct SamplesInvalid;
+
+#[derive(Debug)]
+pub enum SelfTestInputError {
+    SeedInvalid(SeedInvalid),
+    IterationsExceeded(IterationsExceeded),
+    SamplesInvalid(SamplesInvalid),
+}
+
+// What to sample: generator blocks (`rng::random`) or VRF betas under a throwaway key.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Display, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    Rng,
+    Vrf,
+}
+
+#[derive(Deserialize)]
+pub struct SelfTestInputs {
+    #[serde(default)]
+    source: Source,
+    #[serde(default = "default_samples")]
+    samples: usize,
+    #[serde(default = "default_buckets")]
+    buckets: u64,
+    #[serde(default)]
+    seed: Option<String>, // Hex string, random when omitted.
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
+}
+
+fn default_samples() -> usize {
+    DEFAULT_SAMPLES
+}
+
+fn default_buckets() -> u64 {
+    DEFAULT_BUCKETS
+}
+
+#[derive(Serialize)]
+pub struct TestResult {
+    pub name     : &'static str,
+    pub statistic: f64,
+    pub p_value  : f64,
+    pub passed   : bool,
+}
+
+#[derive(Serialize)]
+pub struct SelfTestReport {
+    pub source      : Source,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generator   : Option<GeneratorKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version     : Option<ChainVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pk          : Option<String>, // VRF key the betas were proved with.
+    pub seed        : String,
+    pub samples     : usize,
+    pub bits        : usize,
+    pub significance: f64, // Per-test level, `SIGNIFICANCE / TESTS`.
+    pub passed      : bool,
+    pub tests       : Vec<TestResult>,
+}
+
+fn result(name: &'static str, statistic: f64, p_value: f64) -> TestResult {
+    TestResult{ name, statistic, p_value, passed: p_value >= TEST_SIGNIFICANCE }
+}
+
+fn bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes.iter().flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1 == 1))
+}
+
+// Upper regularized gamma Q(a, x); `gamma_ur` panics at x = 0, where Q is 1.
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    if x > 0.0 { gamma_ur(a, x) } else { 1.0 }
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+// SP 800-22 2.1: Frequency (monobit) test.
+pub fn frequency(bytes: &[u8]) -> TestResult {
+    let n = (bytes.len() * 8) as f64;
+    let sum: i64 = bits(bytes).map(|bit| if bit { 1 } else { -1 }).sum();
+    let s_obs = (sum as f64).abs() / n.sqrt();
+    result("frequency", s_obs, erfc(s_obs / std::f64::consts::SQRT_2))
+}
+
+// SP 800-22 2.2: Frequency test within blocks of `BLOCK_FREQUENCY_BITS` bits.
+pub fn block_frequency(bytes: &[u8]) -> TestResult {
+    let block_bytes = BLOCK_FREQUENCY_BITS / 8;
+    let blocks = bytes.len() / block_bytes;
+    let chi_square = 4.0 * BLOCK_FREQUENCY_BITS as f64 * bytes.chunks_exact(block_bytes)
+        .map(|block| {
+            let ones = block.iter().map(|byte| byte.count_ones()).sum::<u32>();
+            let pi = ones as f64 / BLOCK_FREQUENCY_BITS as f64 - 0.5;
+            pi * pi
+        })
+        .sum::<f64>();
+    result("block_frequency", chi_square, upper_gamma(blocks as f64 / 2.0, chi_square / 2.0))
+}
+
+// SP 800-22 2.3: Runs test, with the frequency prerequisite.
+pub fn runs(bytes: &[u8]) -> TestResult {
+    let n = (bytes.len() * 8) as f64;
+    let ones = bytes.iter().map(|byte| byte.count_ones() as u64).sum::<u64>();
+    let pi = ones as f64 / n;
+    if (pi - 0.5).abs() >= 2.0 / n.sqrt() {
+        return result("runs", 0.0, 0.0);
+    }
+    let mut previous = None;
+    let mut v_obs = 0u64;
+    for bit in bits(bytes) {
+        if previous != Some(bit) {
+            v_obs += 1;
+        }
+        previous = Some(bit);
+    }
+    let v_obs = v_obs as f64;
+    let p_value = erfc((v_obs - 2.0 * n * pi * (1.0 - pi)).abs() / (2.0 * (2.0 * n).sqrt() * pi * (1.0 - pi)));
+    result("runs", v_obs, p_value)
+}
+
+// SP 800-22 2.13: Cumulative sums test, forward mode.
+pub fn cumulative_sums(bytes: &[u8]) -> TestResult {
+    let n = (bytes.len() * 8) as f64;
+    let mut sum = 0i64;
+    let mut z = 0i64;
+    for bit in bits(bytes) {
+        sum += if bit { 1 } else { -1 };
+        z = z.max(sum.abs());
+    }
+    let z = z as f64;
+    let sqrt_n = n.sqrt();
+    let mut p_value = 1.0;
+    for k in ((-n / z + 1.0) / 4.0) as i64..=((n / z - 1.0) / 4.0) as i64 {
+        let k = k as f64;
+        p_value -= normal_cdf((4.0 * k + 1.0) * z / sqrt_n) - normal_cdf((4.0 * k - 1.0) * z / sqrt_n);
+    }
+    for k in ((-n / z - 3.0) / 4.0) as i64..=((n / z - 1.0) / 4.0) as i64 {
+        let k = k as f64;
+        p_value += normal_cdf((4.0 * k + 3.0) * z / sqrt_n) - normal_cdf((4.0 * k + 1.0) * z / sqrt_n);
+    }
+    result("cumulative_sums", z, p_value.clamp(0.0, 1.0))
+}
+
+// Pearson chi-square of tickets over `buckets` equally likely values.
+pub fn chi_square(tickets: &[u64], buckets: u64) -> TestResult {
+    let mut counts = vec![0u64; buckets as usize];
+    for &ticket in tickets {
+        counts[ticket as usize] += 1;
+    }
+    let expected = tickets.len() as f64 / buckets as f64;
+    let chi_square = counts.iter().map(|&count| (count as f64 - expected).powi(2) / expected).sum::<f64>();
+    result("chi_square", chi_square, upper_gamma((buckets - 1) as f64 / 2.0, chi_square / 2.0))
+}
+
+// Knuth's lag-1 (circular) serial correlation coefficient, compared with its normal approximation
+// (mean -1/(n-1), variance n^2/((n-1)^2 (n-2))).
+pub fn serial_correlation(fractions: &[f64]) -> TestResult {
+    let n = fractions.len() as f64;
+    let sum = fractions.iter().sum::<f64>();
+    let sum_squares = fractions.iter().map(|x| x * x).sum::<f64>();
+    let sum_products = fractions.iter().zip(fractions.iter().cycle().skip(1)).map(|(x, y)| x * y).sum::<f64>();
+    let r = (n * sum_products - sum * sum) / (n * sum_squares - sum * sum);
+    let mean = -1.0 / (n - 1.0);
+    let deviation = n / ((n - 1.0) * (n - 2.0).sqrt());
+    let z = (r - mean) / deviation;
+    result("serial_correlation", r, erfc(z.abs() / std::f64::consts::SQRT_2))
+}
+
+pub fn run_tests(samples: &[Vec<u8>], buckets: u64) -> Vec<TestResult> {
+    let bytes: Vec<u8> = samples.concat();
+    let tickets: Vec<u64> = samples.iter().map(|sample| rng::scale_ticket(sample[..32].try_into().unwrap(), buckets)).collect();
+    let fractions: Vec<f64> = samples.iter().map(|sample| u64::from_be_bytes(sample[..8].try_into().unwrap()) as f64 / 2f64.powi(64)).collect();
+    // `TESTS` entries.
+    vec![
+        frequency(&bytes),
+        block_frequency(&bytes),
+        runs(&bytes),
+        cumulative_sums(&bytes),
+        chi_square(&tickets, buckets),
+        serial_correlation(&fractions),
+    ]
+}
+
+// 32-byte generator blocks.
+pub fn rng_samples(seed: &[u8], samples: usize, version: ChainVersion, generator: GeneratorKind) -> Result<Vec<Vec<u8>>, SeedInvalid> {
+    let mut generator = generator::new_generator(generator, seed, version)?;
+    Ok((0..samples).map(|_| generator.next_block().random_result.to_vec()).collect())
+}
+
+// 64-byte betas for `alpha_i = seed || u64_be(i)`.
+pub fn vrf_samples(sk: &SecretKey, seed: &[u8], samples: usize) -> Vec<Vec<u8>> {
+    (0..samples as u64)
+        .map(|i| {
+            let alpha = [seed, &i.to_be_bytes()].concat();
+            let pi = sk.prove(&alpha);
+            PublicKey::from(*sk).verify(&alpha, &pi).unwrap().to_vec()
+        })
+        .collect()
+}
+
+pub fn self_test(source: Source, samples: usize, buckets: u64, seed: Option<&str>, version: ChainVersion, generator: GeneratorKind) -> Result<SelfTestReport, SelfTestInputError> {
+    let sample_bits = match source {
+        Source::Rng => 32 * 8,
+        Source::Vrf => 64 * 8,
+    };
+    let enough_bits = samples.checked_mul(sample_bits).is_some_and(|bits| bits >= BLOCK_FREQUENCY_BITS);
+    if !(2..=MAX_BUCKETS).contains(&buckets) || samples < buckets as usize * MIN_SAMPLES_PER_BUCKET || !enough_bits {
+        return Err(SelfTestInputError::SamplesInvalid(SamplesInvalid));
+    }
+    let seed = match seed {
+        Some(seed) => rng::decode_seed(seed).map_err(SelfTestInputError::SeedInvalid)?,
+        None => {
+            let mut seed = vec![0u8; 32];
+            OsRng.fill_bytes(&mut seed);
+            seed
+        }
+    };
+
+    let (data, pk) = match source {
+        Source::Rng => (rng_samples(&seed, samples, version, generator).map_err(SelfTestInputError::SeedInvalid)?, None),
+        Source::Vrf => {
+            let sk = SecretKey::generate(OsRng);
+            (vrf_samples(&sk, &seed, samples), Some(hex::encode(PublicKey::from(sk).to_bytes())))
+        }
+    };
+    let tests = run_tests(&data, buckets);
+    let is_rng = source == Source::Rng;
+
+    Ok(SelfTestReport {
+        source,
+        generator   : is_rng.then_some(generator),
+        version     : is_rng.then_some(version),
+        pk,
+        seed        : hex::encode(&seed),
+        samples,
+        bits        : data.iter().map(|sample| sample.len() * 8).sum(),
+        significance: TEST_SIGNIFICANCE,
+        passed      : tests.iter().all(|test| test.passed),
+        tests,
+    })
+}
+
+// Api endpoints.
+pub fn api_self_test(self_test_inputs: SelfTestInputs, max_samples: usize) -> Result<SelfTestReport, SelfTestInputError> {
+    if self_test_inputs.samples > max_samples {
+        return Err(SelfTestInputError::IterationsExceeded(IterationsExceeded));
+    }
+    self_test(
+        self_test_inputs.source,
+        self_test_inputs.samples,
+        self_test_inputs.buckets,
+        self_test_inputs.seed.as_deref(),
+        self_test_inputs.version,
+        self_test_inputs.generator,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_is_corrected_for_every_test() {
+        let samples = rng_samples(b"self-test", 1000, ChainVersion::V2, GeneratorKind::default()).unwrap();
+        let tests = run_tests(&samples, DEFAULT_BUCKETS);
+        assert_eq!(tests.len(), TESTS);
+        assert!(tests.iter().all(|test| test.passed == (test.p_value >= SIGNIFICANCE / 6.0)));
+    }
+
+    #[test]
+    fn sample_counts_are_checked_without_overflow() {
+        let invalid = |samples| matches!(
+            self_test(Source::Rng, samples, 2, Some("00"), ChainVersion::V2, GeneratorKind::default()),
+            Err(SelfTestInputError::SamplesInvalid(_))
+        );
+        assert!(invalid(9));
+        assert!(invalid(usize::MAX));
+        assert!(!invalid(10));
+    }
+
+    #[test]
+    fn zero_chi_square_passes() {
+        let test = chi_square(&[0, 1, 0, 1], 2);
+        assert_eq!((test.statistic, test.p_value), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+use crate::generator::{self, GeneratorKind};
+use crate::rng::{self, ChainVersion, IterationsExceeded, SeedInvalid};
+
+// Fisher-Yates (Durstenfeld) shuffle of items `0..n`, specified in `docs/seed-chain.md`:
+//
+//   for i in (1..n).rev():
+//     j = scale_ticket(next_block(), i + 1)
+//     swap(items[i], items[j])
+//
+// Step `i` consumes one generator block, so the permutation is reproducible from the seed.
+// The final position of a single item only depends on the swaps that touch it, which lets
+// `rank` follow it in O(n) time and O(1) memory.
+
+pub const MAX_SHUFFLE_ITEMS: usize = 100_000; // Default for `limits.max_shuffle_items`.
+
+#[derive(Debug)]
+pub struct ItemInvalid;
+
+#[derive(Debug)]
+pub enum ShuffleInputError {
+    SeedInvalid(SeedInvalid),
+    IterationsExceeded(IterationsExceeded),
+    ItemInvalid(ItemInvalid),
+}
+
+#[derive(Deserialize)]
+pub struct ShuffleInputs {
+    initial_seed: String, // Hex string.
+    n: u64,
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
+}
+
+#[derive(Serialize)]
+pub struct ShuffleOutput {
+    version    : ChainVersion,
+    generator  : GeneratorKind,
+    permutation: Vec<u64>, // permutation[position] = item.
+}
+
+impl ShuffleOutput {
+    pub fn draws(&self) -> usize {
+        self.permutation.len()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RankInputs {
+    initial_seed: String, // Hex string.
+    n: u64,
+    item: u64,
+    #[serde(default)]
+    version: ChainVersion,
+    #[serde(default)]
+    generator: GeneratorKind
+}
+
+#[derive(Serialize)]
+pub struct RankOutput {
+    version  : ChainVersion,
+    generator: GeneratorKind,
+    item     : u64,
+    position : u64,
+}
+
+// Swap targets `(i, j)` in shuffle order.
+pub(crate) fn swaps(initial_seed: &str, n: u64, version: ChainVersion, generator: GeneratorKind) -> Result<impl Iterator<Item = (u64, u64)>, SeedInvalid> {
+    let mut generator = generator::new_generator(generator, &rng::decode_seed(initial_seed)?, version)?;
+    Ok((1..n).rev().map(move |i| (i, rng::scale_ticket(&generator.next_block().random_result, i + 1))))
+}
+
+pub fn shuffle(initial_seed: &str, n: u64, version: ChainVersion, generator: GeneratorKind) -> Result<Vec<u64>, SeedInvalid> {
+    let mut permutation: Vec<u64> = (0..n).collect();
+    for (i, j) in swaps(initial_seed, n, version, generator)? {
+        permutation.swap(i as usize, j as usize);
+    }
+    Ok(permutation)
+}
+
+pub fn rank(initial_seed: &str, n: u64, item: u64, version: ChainVersion, generator: GeneratorKind) -> Result<u64, SeedInvalid> {
+    let mut position = item;
+    for (i, j) in swaps(initial_seed, n, version, generator)? {
+        if position == i {
+            position = j;
+        } else if position == j {
+            position = i;
+        }
+    }
+    Ok(position)
+}
+
+// Api endpoints.
+pub fn api_shuffle(shuffle_inputs: ShuffleInputs, max_items: usize) -> Result<ShuffleOutput, ShuffleInputError> {
+    if shuffle_inputs.n > max_items as u64 {
+        return Err(ShuffleInputError::IterationsExceeded(IterationsExceeded));
+    }
+    match shuffle(&shuffle_inputs.initial_seed, shuffle_inputs.n, shuffle_inputs.version, shuffle_inputs.generator) {
+        Ok(permutation) => Ok(ShuffleOutput{ version: shuffle_inputs.version, generator: shuffle_inputs.generator, permutation }),
+        Err(err) => Err(ShuffleInputError::SeedInvalid(err))
+    }
+}
+
+pub fn api_rank(rank_inputs: RankInputs, max_iterations: usize) -> Result<RankOutput, ShuffleInputError> {
+    if rank_inputs.n > max_iterations as u64 {
+        return Err(ShuffleInputError::IterationsExceeded(IterationsExceeded));
+    }
+    if rank_inputs.item >= rank_inputs.n {
+        return Err(ShuffleInputError::ItemInvalid(ItemInvalid));
+    }
+    match rank(&rank_inputs.initial_seed, rank_inputs.n, rank_inputs.item, rank_inputs.version, rank_inputs.generator) {
+        Ok(position) => Ok(RankOutput {
+            version  : rank_inputs.version,
+            generator: rank_inputs.generator,
+            item     : rank_inputs.item,
+            position,
+        }),
+        Err(err) => Err(ShuffleInputError::SeedInvalid(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str = "0123456789abcdef";
+
+    #[test]
+    fn shuffle_is_stable() {
+        assert_eq!(shuffle(SEED, 10, ChainVersion::V1, GeneratorKind::default()).unwrap(), vec![6, 2, 0, 5, 1, 3, 8, 4, 9, 7]);
+        assert_eq!(shuffle(SEED, 10, ChainVersion::V2, GeneratorKind::default()).unwrap(), vec![5, 9, 7, 6, 4, 1, 2, 8, 3, 0]);
+        assert_eq!(shuffle(SEED, 1, ChainVersion::V2, GeneratorKind::default()).unwrap(), vec![0]);
+        assert!(shuffle(SEED, 0, ChainVersion::V2, GeneratorKind::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rank_matches_the_shuffle() {
+        for version in [ChainVersion::V1, ChainVersion::V2] {
+            let permutation = shuffle(SEED, 50, version, GeneratorKind::default()).unwrap();
+            let mut sorted = permutation.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..50).collect::<Vec<u64>>());
+            for (position, &item) in permutation.iter().enumerate() {
+                assert_eq!(rank(SEED, 50, item, version, GeneratorKind::default()).unwrap(), position as u64);
+            }
+        }
+    }
+}
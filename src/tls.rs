@@ -0,0 +1,401 @@
+use std::convert::Infallible;
+use std::fs;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use sha2::{Sha256, Digest};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use warp::hyper::{Body, Request, Response};
+use warp::hyper::server::conn::Http;
+use warp::hyper::service::{Service, service_fn};
+
+// Native TLS termination with rustls. The certificate and key are re-read whenever their
+// modification time changes, so renewed certificates are picked up without a restart; new
+// handshakes use the new pair while open connections keep the old one.
+//
+// With a client CA configured, clients may present a certificate signed by it. Connections
+// without one are still accepted, but only verified clients get a `ClientCert` request
+// extension, which the prove and key routes require.
+
+pub const DEFAULT_RELOAD_SECS: u64 = 60;
+
+#[derive(Debug)]
+pub struct ClientCertRequired;
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(PathBuf, std::io::Error),
+    CertInvalid(PathBuf),
+    KeyInvalid(PathBuf),
+    KeyMismatch(PathBuf, PathBuf),
+    ClientCaInvalid(PathBuf),
+}
+
+impl std::fmt::Display for TlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsError::Io(path, err) => write!(f, "{} is not readable: {}", path.display(), err),
+            TlsError::CertInvalid(path) => write!(f, "{} holds no valid PEM certificate", path.display()),
+            TlsError::KeyInvalid(path) => write!(f, "{} holds no supported PEM private key", path.display()),
+            TlsError::KeyMismatch(cert, key) => write!(f, "{} does not hold the private key of {}", key.display(), cert.display()),
+            TlsError::ClientCaInvalid(path) => write!(f, "{} holds no valid PEM CA certificate", path.display()),
+        }
+    }
+}
+
+// The connection's peer address; warp's `addr::remote` is only set by its own server.
+#[derive(Clone, Copy, Debug)]
+pub struct PeerAddr(pub SocketAddr);
+
+// A verified client certificate, identified by the SHA-256 of its DER encoding.
+#[derive(Clone, Debug)]
+pub struct ClientCert {
+    pub fingerprint: String,
+}
+
+impl ClientCert {
+    pub fn new(der: &CertificateDer<'_>) -> ClientCert {
+        ClientCert{ fingerprint: hex::encode(Sha256::digest(der.as_ref())) }
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let file = fs::File::open(path).map_err(|err| TlsError::Io(path.to_path_buf(), err))?;
+    rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| TlsError::Io(path.to_path_buf(), err))
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let file = fs::File::open(path).map_err(|err| TlsError::Io(path.to_path_buf(), err))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|err| TlsError::Io(path.to_path_buf(), err))?
+        .ok_or(TlsError::KeyInvalid(path.to_path_buf()))
+}
+
+pub fn load_certified_key(cert: &Path, key: &Path) -> Result<CertifiedKey, TlsError> {
+    let certs = read_certs(cert)?;
+    if certs.is_empty() {
+        return Err(TlsError::CertInvalid(cert.to_path_buf()));
+    }
+    let signing_key = tokio_rustls::rustls::crypto::ring::sign::any_supported_type(&read_key(key)?)
+        .map_err(|_err| TlsError::KeyInvalid(key.to_path_buf()))?;
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    if !keys_match(&certified_key) {
+        return Err(TlsError::KeyMismatch(cert.to_path_buf(), key.to_path_buf()));
+    }
+    Ok(certified_key)
+}
+
+// Whether the key signs for the leaf certificate, so a renewal caught between writing the
+// certificate and the key is not loaded.
+fn keys_match(certified_key: &CertifiedKey) -> bool {
+    let Ok(cert) = webpki::EndEntityCert::try_from(&certified_key.cert[0]) else {
+        return false;
+    };
+    let algorithms = tokio_rustls::rustls::crypto::ring::default_provider().signature_verification_algorithms;
+    let schemes = algorithms.mapping.iter().map(|(scheme, _algorithms)| *scheme).collect::<Vec<_>>();
+    let Some(signer) = certified_key.key.choose_scheme(&schemes) else {
+        return false;
+    };
+    let message = b"true-random-on-ton key check";
+    let Ok(signature) = signer.sign(message) else {
+        return false;
+    };
+    algorithms.mapping.iter()
+        .filter(|(scheme, _algorithms)| *scheme == signer.scheme())
+        .flat_map(|(_scheme, algorithms)| algorithms.iter())
+        .any(|algorithm| cert.verify_signature(*algorithm, message, &signature).is_ok())
+}
+
+// Latest modification time of the certificate and key files.
+fn modified(cert: &Path, key: &Path) -> Option<SystemTime> {
+    let cert = fs::metadata(cert).and_then(|metadata| metadata.modified()).ok()?;
+    let key = fs::metadata(key).and_then(|metadata| metadata.modified()).ok()?;
+    Some(cert.max(key))
+}
+
+// Serves the current certificate to every handshake.
+#[derive(Debug)]
+pub struct CertResolver {
+    cert   : PathBuf,
+    key    : PathBuf,
+    current: RwLock<(Option<SystemTime>, Arc<CertifiedKey>)>,
+}
+
+impl CertResolver {
+    pub fn new(cert: &Path, key: &Path) -> Result<CertResolver, TlsError> {
+        let modified = modified(cert, key);
+        let certified_key = load_certified_key(cert, key)?;
+        Ok(CertResolver {
+            cert   : cert.to_path_buf(),
+            key    : key.to_path_buf(),
+            current: RwLock::new((modified, Arc::new(certified_key))),
+        })
+    }
+
+    // Reloads the pair if either file changed since the last successful load. On errors (e.g.
+    // a pair caught half-written) the current certificate stays in use and the reload is
+    // retried on the next check.
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        let modified = modified(&self.cert, &self.key);
+        if modified == self.current.read().unwrap_or_else(|err| err.into_inner()).0 {
+            return Ok(false);
+        }
+        let loaded = Arc::new(load_certified_key(&self.cert, &self.key)?);
+        *self.current.write().unwrap_or_else(|err| err.into_inner()) = (modified, loaded);
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(|err| err.into_inner()).1.clone())
+    }
+}
+
+pub fn acceptor(resolver: Arc<CertResolver>, client_ca: Option<&Path>) -> Result<TlsAcceptor, TlsError> {
+    let builder = ServerConfig::builder();
+    let builder = match client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(path)? {
+                roots.add(cert).map_err(|_err| TlsError::ClientCaInvalid(path.to_path_buf()))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .allow_unauthenticated()
+                .build()
+                .map_err(|_err| TlsError::ClientCaInvalid(path.to_path_buf()))?;
+            builder.with_client_cert_verifier(verifier)
+        },
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_cert_resolver(resolver);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+// Accepts connections on `addr` and hands them to `service`, with the peer address and the
+// verified client certificate (if any) attached to every request. Without an acceptor the
+// connections are served as plain HTTP.
+pub async fn serve<S>(service: S, addr: SocketAddr, acceptor: Option<TlsAcceptor>) -> std::io::Result<()>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                log::warn!(target: "api", "Connection could not be accepted: {}", err);
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        let (acceptor, service) = (acceptor.clone(), service.clone());
+        tokio::spawn(async move {
+            let Some(acceptor) = acceptor else {
+                return serve_connection(stream, service, remote_addr, None).await;
+            };
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::debug!(target: "api", "TLS handshake with {} failed: {}", remote_addr, err);
+                    return;
+                }
+            };
+            let client_cert = stream.get_ref().1.peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(ClientCert::new);
+            serve_connection(stream, service, remote_addr, client_cert).await
+        });
+    }
+}
+
+async fn serve_connection<IO, S>(io: IO, service: S, remote_addr: SocketAddr, client_cert: Option<ClientCert>)
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let service = service_fn(move |mut request: Request<Body>| {
+        request.extensions_mut().insert(PeerAddr(remote_addr));
+        if let Some(client_cert) = &client_cert {
+            request.extensions_mut().insert(client_cert.clone());
+        }
+        service.clone().call(request)
+    });
+    if let Err(err) = Http::new().serve_connection(io, service).await {
+        log::debug!(target: "api", "Connection with {} closed: {}", remote_addr, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use warp::Filter;
+
+    struct Issuer {
+        cert: rcgen::Certificate,
+        key : KeyPair,
+    }
+
+    struct Pair {
+        cert: String,
+        key : String,
+        der : CertificateDer<'static>,
+    }
+
+    fn ca() -> Issuer {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let key = KeyPair::generate().unwrap();
+        Issuer{ cert: params.self_signed(&key).unwrap(), key }
+    }
+
+    fn issue(issuer: &Issuer, usage: ExtendedKeyUsagePurpose) -> Pair {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.extended_key_usages = vec![usage];
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &issuer.cert, &issuer.key).unwrap();
+        Pair{ cert: cert.pem(), key: key.serialize_pem(), der: cert.der().clone() }
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tls-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    // Writes both files with an explicit modification time, so reloads do not depend on the
+    // file system's timestamp resolution.
+    fn write(path: &Path, contents: &str, modified: SystemTime) {
+        fs::write(path, contents).unwrap();
+        fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    fn write_pair(dir: &Path, pair: &Pair, modified: SystemTime) {
+        write(&dir.join("cert.pem"), &pair.cert, modified);
+        write(&dir.join("key.pem"), &pair.key, modified);
+    }
+
+    fn current(resolver: &CertResolver) -> CertificateDer<'static> {
+        resolver.current.read().unwrap().1.cert[0].clone()
+    }
+
+    // Serves the `ClientCert` fingerprint of each request, or `-` without one.
+    async fn server(resolver: Arc<CertResolver>, client_ca: Option<&Path>) -> SocketAddr {
+        let acceptor = acceptor(resolver, client_ca).unwrap();
+        let filter = warp::ext::optional::<ClientCert>()
+            .map(|client_cert: Option<ClientCert>| client_cert.map_or("-".to_string(), |client_cert| client_cert.fingerprint));
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(serve(warp::service(filter), addr, Some(acceptor)));
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        addr
+    }
+
+    // The server certificate and response body of one HTTP/1.1 request.
+    async fn request(addr: SocketAddr, ca: &Issuer, client: Option<&Pair>) -> std::io::Result<(CertificateDer<'static>, String)> {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.cert.der().clone()).unwrap();
+        let builder = ClientConfig::builder().with_root_certificates(roots);
+        let config = match client {
+            Some(pair) => {
+                let key = rustls_pemfile::private_key(&mut pair.key.as_bytes()).unwrap().unwrap();
+                builder.with_client_auth_cert(vec![pair.der.clone()], key).unwrap()
+            },
+            None => builder.with_no_client_auth(),
+        };
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(config)).connect(ServerName::try_from("localhost").unwrap(), stream).await?;
+        stream.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let server_cert = stream.get_ref().1.peer_certificates().unwrap()[0].clone().into_owned();
+        let body = response.split_once("\r\n\r\n").map(|(_head, body)| body.to_string()).unwrap_or_default();
+        Ok((server_cert, body))
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_in_the_new_pair() {
+        let (ca, dir) = (ca(), temp_dir());
+        let (old, new) = (issue(&ca, ExtendedKeyUsagePurpose::ServerAuth), issue(&ca, ExtendedKeyUsagePurpose::ServerAuth));
+        let t0 = SystemTime::now() - Duration::from_secs(60);
+        write_pair(&dir, &old, t0);
+        let resolver = Arc::new(CertResolver::new(&dir.join("cert.pem"), &dir.join("key.pem")).unwrap());
+        let addr = server(resolver.clone(), None).await;
+        assert!(!resolver.reload_if_changed().unwrap());
+        assert_eq!(request(addr, &ca, None).await.unwrap().0, old.der);
+
+        write_pair(&dir, &new, t0 + Duration::from_secs(10));
+        assert!(resolver.reload_if_changed().unwrap());
+        assert_eq!(current(&resolver), new.der);
+        assert_eq!(request(addr, &ca, None).await.unwrap().0, new.der);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn half_written_pair_keeps_the_current_one() {
+        let (ca, dir) = (ca(), temp_dir());
+        let (old, new) = (issue(&ca, ExtendedKeyUsagePurpose::ServerAuth), issue(&ca, ExtendedKeyUsagePurpose::ServerAuth));
+        let t0 = SystemTime::now() - Duration::from_secs(60);
+        write_pair(&dir, &old, t0);
+        let resolver = CertResolver::new(&dir.join("cert.pem"), &dir.join("key.pem")).unwrap();
+
+        // New certificate, old key.
+        write(&dir.join("cert.pem"), &new.cert, t0 + Duration::from_secs(10));
+        assert!(matches!(resolver.reload_if_changed(), Err(TlsError::KeyMismatch(..))));
+        assert_eq!(current(&resolver), old.der);
+
+        // Key cut short.
+        write(&dir.join("key.pem"), &new.key[..new.key.len() / 2], t0 + Duration::from_secs(20));
+        assert!(resolver.reload_if_changed().is_err());
+        assert_eq!(current(&resolver), old.der);
+
+        // The failed reloads are retried once the pair is complete.
+        write(&dir.join("key.pem"), &new.key, t0 + Duration::from_secs(30));
+        assert!(resolver.reload_if_changed().unwrap());
+        assert_eq!(current(&resolver), new.der);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_verified_clients_get_a_client_cert() {
+        let (ca, rogue_ca, dir) = (ca(), ca(), temp_dir());
+        write_pair(&dir, &issue(&ca, ExtendedKeyUsagePurpose::ServerAuth), SystemTime::now());
+        write(&dir.join("client-ca.pem"), &ca.cert.pem(), SystemTime::now());
+        let resolver = Arc::new(CertResolver::new(&dir.join("cert.pem"), &dir.join("key.pem")).unwrap());
+        let addr = server(resolver, Some(&dir.join("client-ca.pem"))).await;
+
+        let client = issue(&ca, ExtendedKeyUsagePurpose::ClientAuth);
+        assert_eq!(request(addr, &ca, Some(&client)).await.unwrap().1, ClientCert::new(&client.der).fingerprint);
+        // `allow_unauthenticated` admits clients without a certificate, but not as verified ones.
+        assert_eq!(request(addr, &ca, None).await.unwrap().1, "-");
+        // A certificate from another CA fails the handshake rather than being ignored.
+        let rogue = issue(&rogue_ca, ExtendedKeyUsagePurpose::ClientAuth);
+        assert!(request(addr, &ca, Some(&rogue)).await.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
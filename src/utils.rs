@@ -1,12 +1,12 @@
 use serde::Serialize;
 use serde::Deserialize;
 use strum::{Display, EnumString};
+use utoipa::ToSchema;
 use num_bigint::BigUint;
 use std::str::FromStr;
 use sha2::{Sha256, Sha512, Digest};
-use hex;
 
-#[derive(Debug, PartialEq, Display, EnumString, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Display, EnumString, Deserialize, Serialize, ToSchema)]
 pub enum ConvertInputValueType {
     #[strum(serialize = "Be")]
     #[serde(rename = "Be")]
@@ -27,23 +27,23 @@ pub enum ConvertInputError {
     HexStringInvalid(HexStringInvalid)
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct ConvertInputs {
     value     : String,
     value_type: ConvertInputValueType
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ConvertOutput {
     pub value: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct ShaInputs {
     value: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ShaOutput {
     pub value: String,
 }
@@ -52,8 +52,8 @@ pub fn convert_to_hex_string(int_string: String, value_type: ConvertInputValueTy
     match BigUint::from_str(&int_string) {
         Ok(biguint) => {
             match value_type {
-                ConvertInputValueType::Be => Ok(hex::encode(&biguint.to_bytes_be())),
-                ConvertInputValueType::Le => Ok(hex::encode(&biguint.to_bytes_le())),
+                ConvertInputValueType::Be => Ok(hex::encode(biguint.to_bytes_be())),
+                ConvertInputValueType::Le => Ok(hex::encode(biguint.to_bytes_le())),
             }
         },
         Err(_err) => Err(IntStringInvalid)
@@ -0,0 +1,537 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{ecvrf, keystore, logging, rng, utils};
+use crate::entropy::EntropyFailed;
+use crate::generator::GeneratorKind;
+use crate::rng::ChainVersion;
+
+// The `/api/v2` surface. The v1 routes stay frozen for existing integrators; v2 serves the
+// same operations with one set of conventions:
+//   - fields are snake_case and name their encoding: `*_hex` for hex strings, `*_decimal`
+//     for decimal strings;
+//   - a success is `{data, page?, request_id}`, a failure `{error: {code, message}, request_id}`,
+//     and the HTTP status is the only status;
+//   - error codes are stable snake_case identifiers, see `error_code`;
+//   - lists take `start` and `count`, and `page.next_start` is absent on the last page.
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Serialize, ToSchema)]
+pub struct Envelope<T> {
+    pub data      : T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page      : Option<Page>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(data: T) -> Envelope<T> {
+        Envelope{ data, page: None, request_id: logging::request_id() }
+    }
+
+    pub fn paged(data: T, page: Page) -> Envelope<T> {
+        Envelope{ data, page: Some(page), request_id: logging::request_id() }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::Page)]
+pub struct Page {
+    pub start     : usize,
+    // Items in this page.
+    pub count     : usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_start: Option<usize>,
+    // Items in the whole list, when it is finite.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total     : Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::ErrorEnvelope)]
+pub struct ErrorEnvelope {
+    pub error     : ErrorBody,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::ErrorBody)]
+pub struct ErrorBody {
+    #[schema(example = "sk_invalid")]
+    pub code   : String,
+    pub message: String,
+}
+
+// `SKInvalid` -> `sk_invalid`, `VRFVerifyFailed` -> `vrf_verify_failed`: the rejection kind
+// counted in `rejections_total`, in snake case.
+pub fn error_code(kind: &str) -> String {
+    let chars: Vec<char> = kind.chars().collect();
+    let mut code = String::with_capacity(kind.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        let word_start = i > 0 && c.is_ascii_uppercase()
+            && (chars[i - 1].is_ascii_lowercase() || chars.get(i + 1).is_some_and(char::is_ascii_lowercase));
+        if word_start {
+            code.push('_');
+        }
+        code.push(c.to_ascii_lowercase());
+    }
+    code
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    // First item, from 0.
+    #[serde(default)]
+    start: usize,
+    // At most `MAX_PAGE_SIZE`.
+    #[serde(default = "default_page_size")]
+    count: usize,
+}
+
+fn default_page_size() -> usize {
+    DEFAULT_PAGE_SIZE
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = v2::ByteOrder)]
+pub enum ByteOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+impl From<ByteOrder> for utils::ConvertInputValueType {
+    fn from(byte_order: ByteOrder) -> utils::ConvertInputValueType {
+        match byte_order {
+            ByteOrder::BigEndian => utils::ConvertInputValueType::Be,
+            ByteOrder::LittleEndian => utils::ConvertInputValueType::Le,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::ProveInputs)]
+pub struct ProveInputs {
+    sk_hex   : String,
+    alpha_hex: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::KeyProveInputs)]
+pub struct KeyProveInputs {
+    alpha_hex: String,
+}
+
+// `pi_hex` is `gamma_hex || c_hex || s_hex`, the form `verify` takes.
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::ProofOutput)]
+pub struct ProofOutput {
+    pub gamma_hex: String,
+    pub c_hex    : String,
+    pub s_hex    : String,
+    pub pi_hex   : String,
+}
+
+impl From<ecvrf::VRFOutput> for ProofOutput {
+    fn from(vrf_output: ecvrf::VRFOutput) -> ProofOutput {
+        ProofOutput {
+            pi_hex   : format!("{}{}{}", vrf_output.Gamma, vrf_output.c, vrf_output.s),
+            gamma_hex: vrf_output.Gamma,
+            c_hex    : vrf_output.c,
+            s_hex    : vrf_output.s,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::VerifyInputs)]
+pub struct VerifyInputs {
+    pk_hex   : String,
+    alpha_hex: String,
+    pi_hex   : String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::VerifyOutput)]
+pub struct VerifyOutput {
+    pub beta_hex: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::PKInputs)]
+pub struct PKInputs {
+    sk_hex: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::PKOutput)]
+pub struct PKOutput {
+    pub pk_hex: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::SKOutput)]
+pub struct SKOutput {
+    pub sk_hex: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::KeyOutput)]
+pub struct KeyOutput {
+    pub id    : String,
+    pub pk_hex: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::IntToHexInputs)]
+pub struct IntToHexInputs {
+    value_decimal: String,
+    byte_order   : ByteOrder,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::HexOutput)]
+pub struct HexOutput {
+    pub value_hex: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::HexToIntInputs)]
+pub struct HexToIntInputs {
+    value_hex : String,
+    byte_order: ByteOrder,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::DecimalOutput)]
+pub struct DecimalOutput {
+    pub value_decimal: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::DigestInputs)]
+pub struct DigestInputs {
+    data_hex: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::DigestOutput)]
+pub struct DigestOutput {
+    pub digest_hex: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::TicketsInputs)]
+pub struct TicketsInputs {
+    seed_hex     : String,
+    // Tickets are in [0, limit).
+    limit        : u64,
+    #[serde(default)]
+    start        : usize,
+    count        : usize,
+    #[serde(default)]
+    chain_version: ChainVersion,
+    #[serde(default)]
+    generator    : GeneratorKind,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::Ticket)]
+pub struct Ticket {
+    // Position in the chain, from 0.
+    pub index            : usize,
+    pub ticket           : u64,
+    pub random_result_hex: String,
+    // Chain state after this ticket. SHA-512 chain only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_seed_hex     : Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::TicketsOutput)]
+pub struct TicketsOutput {
+    pub chain_version: ChainVersion,
+    pub generator    : GeneratorKind,
+    pub tickets      : Vec<Ticket>,
+    // Resumes the chain at `page.next_start` with `start: 0`. SHA-512 chain only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_seed_hex: Option<String>,
+}
+
+impl TicketsOutput {
+    pub fn draws(&self) -> usize {
+        self.tickets.len()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::DistributionInputs)]
+pub struct DistributionInputs {
+    seed_hex     : String,
+    draws        : Vec<rng::Distribution>,
+    #[serde(default)]
+    chain_version: ChainVersion,
+    #[serde(default)]
+    generator    : GeneratorKind,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[schema(as = v2::DistributionResult)]
+pub enum DistributionResult {
+    Range { value: i64, random_result_hex: String },
+    Float { value_decimal: String, random_result_hex: String },
+    Dice { rolls: Vec<u64>, total: u64, random_results_hex: Vec<String> },
+    Coin { value: bool, random_result_hex: String },
+    Table { index: usize, value: String, random_result_hex: String },
+}
+
+impl From<rng::DistributionResult> for DistributionResult {
+    fn from(result: rng::DistributionResult) -> DistributionResult {
+        match result {
+            rng::DistributionResult::Range { value, random_result } => DistributionResult::Range{ value, random_result_hex: random_result },
+            rng::DistributionResult::Float { value, random_result } => DistributionResult::Float{ value_decimal: value, random_result_hex: random_result },
+            rng::DistributionResult::Dice { rolls, total, random_results } => DistributionResult::Dice{ rolls, total, random_results_hex: random_results },
+            rng::DistributionResult::Coin { value, random_result } => DistributionResult::Coin{ value, random_result_hex: random_result },
+            rng::DistributionResult::Table { index, value, random_result } => DistributionResult::Table{ index, value, random_result_hex: random_result },
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::DistributionOutput)]
+pub struct DistributionOutput {
+    pub chain_version: ChainVersion,
+    pub generator    : GeneratorKind,
+    pub results      : Vec<DistributionResult>,
+}
+
+impl DistributionOutput {
+    pub fn draws(&self) -> usize {
+        self.results.len()
+    }
+}
+
+// Api endpoints.
+pub fn api_vrf_prove(prove_inputs: ProveInputs) -> Result<ProofOutput, ecvrf::VRFInputError> {
+    let sk = ecvrf::decode_sk(prove_inputs.sk_hex).map_err(ecvrf::VRFInputError::SKInvalid)?;
+    match hex::decode(prove_inputs.alpha_hex) {
+        Ok(alpha) => Ok(ecvrf::prove(&sk, &alpha).into()),
+        Err(_err) => Err(ecvrf::VRFInputError::AlphaInvalid(ecvrf::AlphaInvalid))
+    }
+}
+
+pub fn api_vrf_verify(verify_inputs: VerifyInputs) -> Result<VerifyOutput, ecvrf::VRFVerifyInputError> {
+    Ok(VerifyOutput{ beta_hex: ecvrf::verify(verify_inputs.pk_hex, verify_inputs.alpha_hex, verify_inputs.pi_hex)? })
+}
+
+pub fn api_get_pk(pk_inputs: PKInputs) -> Result<PKOutput, ecvrf::VRFInputError> {
+    match ecvrf::decode_sk(pk_inputs.sk_hex) {
+        Ok(sk) => Ok(PKOutput{ pk_hex: ecvrf::get_pk(sk) }),
+        Err(err) => Err(ecvrf::VRFInputError::SKInvalid(err))
+    }
+}
+
+pub fn api_generate_sk() -> Result<SKOutput, EntropyFailed> {
+    Ok(SKOutput{ sk_hex: ecvrf::generate_sk()? })
+}
+
+// Keys in id order; `count` is capped at `MAX_PAGE_SIZE`.
+pub fn api_list_keys(keyring: &keystore::Keyring, page_query: PageQuery) -> (Vec<KeyOutput>, Page) {
+    let keys: Vec<KeyOutput> = keystore::api_list_keys(keyring).keys
+        .into_iter()
+        .skip(page_query.start)
+        .take(page_query.count.min(MAX_PAGE_SIZE))
+        .map(|key| KeyOutput{ id: key.id, pk_hex: key.pk })
+        .collect();
+    let next_start = page_query.start.saturating_add(keys.len());
+    let page = Page {
+        start     : page_query.start,
+        count     : keys.len(),
+        next_start: (next_start < keyring.count()).then_some(next_start),
+        total     : Some(keyring.count()),
+    };
+    (keys, page)
+}
+
+pub fn api_key_prove(keyring: &keystore::Keyring, id: &str, prove_inputs: KeyProveInputs) -> Result<ProofOutput, keystore::KeyProveInputError> {
+    let sk = keyring.get(id).ok_or(keystore::KeyProveInputError::KeyNotFound(keystore::KeyNotFound))?;
+    match hex::decode(prove_inputs.alpha_hex) {
+        Ok(alpha) => Ok(ecvrf::prove(sk, &alpha).into()),
+        Err(_err) => Err(keystore::KeyProveInputError::AlphaInvalid(ecvrf::AlphaInvalid))
+    }
+}
+
+pub fn api_int_to_hex(convert_inputs: IntToHexInputs) -> Result<HexOutput, utils::ConvertInputError> {
+    match utils::convert_to_hex_string(convert_inputs.value_decimal, convert_inputs.byte_order.into()) {
+        Ok(value_hex) => Ok(HexOutput{ value_hex }),
+        Err(err) => Err(utils::ConvertInputError::IntStringInvalid(err))
+    }
+}
+
+pub fn api_hex_to_int(convert_inputs: HexToIntInputs) -> Result<DecimalOutput, utils::ConvertInputError> {
+    match utils::convert_to_int_string(convert_inputs.value_hex, convert_inputs.byte_order.into()) {
+        Ok(value_decimal) => Ok(DecimalOutput{ value_decimal }),
+        Err(err) => Err(utils::ConvertInputError::HexStringInvalid(err))
+    }
+}
+
+pub fn api_sha256(digest_inputs: DigestInputs) -> Result<DigestOutput, utils::ConvertInputError> {
+    match utils::sha256(digest_inputs.data_hex) {
+        Ok(digest_hex) => Ok(DigestOutput{ digest_hex }),
+        Err(err) => Err(utils::ConvertInputError::HexStringInvalid(err))
+    }
+}
+
+pub fn api_sha512(digest_inputs: DigestInputs) -> Result<DigestOutput, utils::ConvertInputError> {
+    match utils::sha512(digest_inputs.data_hex) {
+        Ok(digest_hex) => Ok(DigestOutput{ digest_hex }),
+        Err(err) => Err(utils::ConvertInputError::HexStringInvalid(err))
+    }
+}
+
+// The chain is unbounded, so `page.next_start` is always set and `page.total` never is.
+pub fn api_tickets(tickets_inputs: TicketsInputs, max_count: usize, max_iterations: usize) -> Result<(TicketsOutput, Page), rng::RngInputError> {
+    rng::check_page(tickets_inputs.start, tickets_inputs.count, max_count, max_iterations)?;
+    let (results, next_seed) = rng::random_page(
+        &tickets_inputs.seed_hex,
+        tickets_inputs.start,
+        tickets_inputs.count,
+        tickets_inputs.limit,
+        tickets_inputs.chain_version,
+        tickets_inputs.generator,
+    )?;
+
+    let tickets: Vec<Ticket> = results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| Ticket {
+            index            : tickets_inputs.start + i,
+            ticket           : result.ticket_number,
+            random_result_hex: result.random_result,
+            new_seed_hex     : result.new_seed,
+        })
+        .collect();
+    let page = Page {
+        start     : tickets_inputs.start,
+        count     : tickets.len(),
+        next_start: Some(tickets_inputs.start + tickets.len()),
+        total     : None,
+    };
+    let tickets_output = TicketsOutput {
+        chain_version: tickets_inputs.chain_version,
+        generator    : tickets_inputs.generator,
+        tickets,
+        next_seed_hex: next_seed,
+    };
+    Ok((tickets_output, page))
+}
+
+pub fn api_distribution(distribution_inputs: DistributionInputs, max_iterations: usize) -> Result<DistributionOutput, rng::RngInputError> {
+    rng::distribution_blocks(&distribution_inputs.draws, max_iterations)?;
+    let results = rng::distribution(&distribution_inputs.seed_hex, &distribution_inputs.draws, distribution_inputs.chain_version, distribution_inputs.generator)?;
+
+    Ok(DistributionOutput {
+        chain_version: distribution_inputs.chain_version,
+        generator    : distribution_inputs.generator,
+        results      : results.into_iter().map(DistributionResult::from).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vrf_r255::SecretKey;
+
+    const SEED_HEX: &str = "9b0f6a6c1e1c5a0d3b8e2f4a7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d";
+
+    fn keyring(count: usize) -> keystore::Keyring {
+        keystore::Keyring::from_keys((0..count).map(|i| {
+            let mut sk_bytes = [0u8; 32];
+            sk_bytes[..8].copy_from_slice(&(i as u64 + 1).to_le_bytes());
+            (format!("key-{:04}", i), SecretKey::from_bytes(sk_bytes).unwrap())
+        }))
+    }
+
+    fn tickets_inputs(start: usize, count: usize) -> TicketsInputs {
+        TicketsInputs{ seed_hex: SEED_HEX.to_string(), limit: 100, start, count, chain_version: ChainVersion::V1, generator: GeneratorKind::Sha512Chain }
+    }
+
+    #[test]
+    fn envelopes_omit_absent_fields() {
+        assert_eq!(serde_json::to_value(Envelope::new(1)).unwrap(), serde_json::json!({"data": 1}));
+        let page = Page{ start: 0, count: 1, next_start: None, total: Some(1) };
+        assert_eq!(serde_json::to_value(Envelope::paged(1, page)).unwrap(), serde_json::json!({"data": 1, "page": {"start": 0, "count": 1, "total": 1}}));
+        let error = ErrorEnvelope{ error: ErrorBody{ code: error_code("SKInvalid"), message: "VRF secret key is invalid.".to_string() }, request_id: None };
+        assert_eq!(serde_json::to_value(error).unwrap(), serde_json::json!({"error": {"code": "sk_invalid", "message": "VRF secret key is invalid."}}));
+    }
+
+    #[test]
+    fn error_codes_are_snake_case() {
+        for (kind, code) in [
+            ("SKInvalid", "sk_invalid"),
+            ("PKMismatch", "pk_mismatch"),
+            ("VRFVerifyFailed", "vrf_verify_failed"),
+            ("BodyDeserializeError", "body_deserialize_error"),
+            ("NotFound", "not_found"),
+            ("Unhandled", "unhandled"),
+        ] {
+            assert_eq!(error_code(kind), code);
+        }
+    }
+
+    #[test]
+    fn key_pages() {
+        let five = keyring(5);
+        let (keys, page) = api_list_keys(&five, PageQuery{ start: 0, count: 2 });
+        assert_eq!(keys.iter().map(|key| key.id.as_str()).collect::<Vec<_>>(), ["key-0000", "key-0001"]);
+        assert_eq!((page.count, page.next_start, page.total), (2, Some(2), Some(5)));
+
+        let (keys, page) = api_list_keys(&five, PageQuery{ start: 4, count: 2 });
+        assert_eq!((keys.len(), page.next_start), (1, None));
+
+        // Past the end: an empty last page.
+        let (keys, page) = api_list_keys(&five, PageQuery{ start: 9, count: 2 });
+        assert_eq!((keys.len(), page.start, page.count, page.next_start, page.total), (0, 9, 0, None, Some(5)));
+
+        // An empty page in the middle of the list still points at the rest.
+        let (keys, page) = api_list_keys(&five, PageQuery{ start: 1, count: 0 });
+        assert_eq!((keys.len(), page.next_start), (0, Some(1)));
+
+        let (keys, _page) = api_list_keys(&keyring(MAX_PAGE_SIZE + 1), PageQuery{ start: 0, count: MAX_PAGE_SIZE + 1 });
+        assert_eq!(keys.len(), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn ticket_pages_resume_the_chain() {
+        let (all, _page) = api_tickets(tickets_inputs(0, 6), 100, 100).unwrap();
+        let (first, page) = api_tickets(tickets_inputs(0, 3), 100, 100).unwrap();
+        assert_eq!((page.next_start, page.total), (Some(3), None));
+        let (second, _page) = api_tickets(tickets_inputs(3, 3), 100, 100).unwrap();
+        let tickets: Vec<u64> = first.tickets.iter().chain(&second.tickets).map(|ticket| ticket.ticket).collect();
+        assert_eq!(tickets, all.tickets.iter().map(|ticket| ticket.ticket).collect::<Vec<_>>());
+        assert_eq!(second.tickets[0].index, 3);
+        assert!(all.tickets.iter().all(|ticket| ticket.ticket < 100));
+
+        // `next_seed_hex` with `start: 0` continues where the page ended.
+        let resumed = TicketsInputs{ seed_hex: first.next_seed_hex.unwrap(), ..tickets_inputs(0, 3) };
+        let (resumed, _page) = api_tickets(resumed, 100, 100).unwrap();
+        assert_eq!(resumed.tickets.iter().map(|ticket| ticket.ticket).collect::<Vec<_>>(), tickets[3..]);
+    }
+
+    #[test]
+    fn ticket_page_edges() {
+        let (empty, page) = api_tickets(tickets_inputs(2, 0), 100, 100).unwrap();
+        assert!(empty.tickets.is_empty());
+        assert_eq!((page.count, page.next_start), (0, Some(2)));
+
+        assert!(matches!(api_tickets(tickets_inputs(0, 101), 100, 1000), Err(rng::RngInputError::IterationsExceeded(_))));
+        assert!(matches!(api_tickets(tickets_inputs(950, 100), 100, 1000), Err(rng::RngInputError::IterationsExceeded(_))));
+        assert!(matches!(api_tickets(tickets_inputs(usize::MAX, 1), 100, 1000), Err(rng::RngInputError::IterationsExceeded(_))));
+    }
+}
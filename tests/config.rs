@@ -0,0 +1,49 @@
+// Checks the precedence of the config file, environment variables and flags through the
+// binary's `--print-config`, so the environment is only ever set on the child process.
+
+use std::process::Command;
+
+use true_random_on_ton::config::Config;
+use true_random_on_ton::shuffle;
+
+#[test]
+fn flags_override_env_which_overrides_the_file() {
+    let path = std::env::temp_dir().join(format!("config-test-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, concat!(
+        "[server]\nlisten = \"127.0.0.1:1001\"\n",
+        "[limits]\nmax_iterations = 100\nmax_stream_iterations = 5000\n",
+        "[beacon]\ninterval_secs = 7\n",
+    )).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_true-random-on-ton"))
+        .env_clear()
+        .env("SERVER_LISTEN", "127.0.0.1:2002")
+        .env("RNG_MAX_ITERATIONS", "200")
+        .env("RATE_LIMIT_TRUST_FORWARDED_FOR", "true")
+        .args(["--config", path.to_str().unwrap(), "--max-iterations", "300", "--print-config"])
+        .output()
+        .expect("binary runs");
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let config: Config = toml::from_str(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    assert_eq!(config.limits.max_iterations, 300); // Flag over env and file.
+    assert_eq!(config.server.listen, "127.0.0.1:2002".parse().unwrap()); // Env over file.
+    assert!(config.rate_limit.trust_forwarded_for); // Env over default.
+    assert_eq!(config.limits.max_stream_iterations, 5000); // File over default.
+    assert_eq!(config.beacon.interval_secs, 7);
+    assert_eq!(config.limits.max_shuffle_items, shuffle::MAX_SHUFFLE_ITEMS); // Default.
+}
+
+#[test]
+fn invalid_config_exits_with_every_problem() {
+    let output = Command::new(env!("CARGO_BIN_EXE_true-random-on-ton"))
+        .env_clear()
+        .args(["--max-iterations", "0", "--beacon-interval-secs", "0", "--print-config"])
+        .output()
+        .expect("binary runs");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("limits.max_iterations"), "{}", stderr);
+    assert!(stderr.contains("beacon.interval_secs"), "{}", stderr);
+}
@@ -0,0 +1,231 @@
+// Checks `/openapi.json` against the running server: every route is either documented or
+// listed in `UNDOCUMENTED`, every request example matches its schema and is accepted by the
+// handler, and every response matches the schema documented for its status.
+
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use true_random_on_ton::metrics::ROUTES;
+
+// Routes whose types live outside `ecvrf`, `utils`, `rng` and `v2`, routes that need a loaded
+// keystore key, and the documentation itself.
+const UNDOCUMENTED: [&str; 27] = [
+    "/api/vrf/pk/derive",
+    "/api/vrf/sk/mnemonic",
+    "/api/vrf/sk/mnemonic/restore",
+    "/api/vrf/sk/shares",
+    "/api/vrf/sk/shares/recover",
+    "/api/vrf/keys",
+    "/api/vrf/keys/{id}/prove",
+    "/api/random/shuffle",
+    "/api/random/shuffle/rank",
+    "/api/random/prizes",
+    "/api/merkle/commit",
+    "/api/merkle/prove",
+    "/api/merkle/proofs",
+    "/api/merkle/verify",
+    "/api/drand/verify",
+    "/api/drand/alpha",
+    "/api/admin/selftest",
+    "/api/admin/ratelimit",
+    "/api/health/entropy",
+    "/api/v2/vrf/keys/{id}/prove",
+    "/beacon",
+    "/beacon/latest",
+    "/beacon/round/{round}",
+    "/metrics",
+    "/openapi.json",
+    "/docs",
+    "/docs/{file}",
+];
+
+struct Server {
+    child: Child,
+    url  : String,
+}
+
+impl Server {
+    fn start() -> Server {
+        let port = TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("a free port").port();
+        let child = Command::new(env!("CARGO_BIN_EXE_true-random-on-ton"))
+            .env_clear()
+            .env("SERVER_LISTEN", format!("127.0.0.1:{}", port))
+            .env("RUST_LOG", "off")
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("server starts");
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(30), "server did not listen within 30s");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Server{ child, url: format!("http://127.0.0.1:{}", port) }
+    }
+
+    fn spec(&self) -> Value {
+        ureq::get(&format!("{}/openapi.json", self.url)).call().expect("/openapi.json is served").into_json().expect("/openapi.json is JSON")
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn resolve<'a>(spec: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema["$ref"].as_str() {
+        Some(reference) => {
+            let name = reference.strip_prefix("#/components/schemas/").expect("references point at components");
+            resolve(spec, &spec["components"]["schemas"][name])
+        },
+        None => schema,
+    }
+}
+
+fn type_matches(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => panic!("unsupported schema type {}", name),
+    }
+}
+
+// The subset of JSON Schema that utoipa emits. Objects may not carry undocumented fields.
+fn check(spec: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    let schema = resolve(spec, schema);
+    if let Some(variants) = schema["oneOf"].as_array() {
+        return match variants.iter().any(|variant| check(spec, variant, value, at).is_ok()) {
+            true => Ok(()),
+            false => Err(format!("{}: {} matches no variant", at, value)),
+        };
+    }
+    if let Some(parts) = schema["allOf"].as_array() {
+        return parts.iter().try_for_each(|part| check(spec, part, value, at));
+    }
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(name) => vec![name.as_str()],
+        Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+        _ => return Ok(()),
+    };
+    if !types.iter().any(|name| type_matches(value, name)) {
+        return Err(format!("{}: {} is not {:?}", at, value, types));
+    }
+    if let Some(allowed) = schema["enum"].as_array() && !allowed.contains(value) {
+        return Err(format!("{}: {} is not one of {:?}", at, value, allowed));
+    }
+    if let Some(minimum) = schema["minimum"].as_f64() && value.as_f64().is_some_and(|number| number < minimum) {
+        return Err(format!("{}: {} is below {}", at, value, minimum));
+    }
+    match value {
+        Value::Object(fields) => {
+            let properties = schema["properties"].as_object().cloned().unwrap_or_default();
+            for required in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                if !fields.contains_key(required) {
+                    return Err(format!("{}: required field {} is missing", at, required));
+                }
+            }
+            for (name, field) in fields {
+                let property = properties.get(name).ok_or_else(|| format!("{}: field {} is not documented", at, name))?;
+                check(spec, property, field, &format!("{}.{}", at, name))?;
+            }
+            Ok(())
+        },
+        Value::Array(items) => items.iter().enumerate().try_for_each(|(i, item)| check(spec, &schema["items"], item, &format!("{}[{}]", at, i))),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn every_route_is_documented_or_listed() {
+    let server = Server::start();
+    let spec = server.spec();
+    let documented: Vec<&str> = spec["paths"].as_object().expect("paths").keys().map(String::as_str).collect();
+    for path in &documented {
+        assert!(ROUTES.contains(path), "{} is documented but not served", path);
+        assert!(!UNDOCUMENTED.contains(path), "{} is documented and listed as undocumented", path);
+    }
+    for route in ROUTES {
+        assert!(documented.contains(&route) || UNDOCUMENTED.contains(&route), "{} is neither documented nor listed as undocumented", route);
+    }
+}
+
+#[test]
+fn examples_and_responses_match_the_spec() {
+    let server = Server::start();
+    let spec = server.spec();
+    for (path, operations) in spec["paths"].as_object().expect("paths") {
+        for (method, operation) in operations.as_object().expect("operations") {
+            let at = format!("{} {}", method.to_uppercase(), path);
+            let request = ureq::request(&method.to_uppercase(), &format!("{}{}", server.url, path));
+            let response = match operation["requestBody"]["content"]["application/json"].as_object() {
+                Some(content) => {
+                    let example = content.get("example").unwrap_or_else(|| panic!("{}: request body has no example", at));
+                    if let Err(err) = check(&spec, &content["schema"], example, &format!("{} request", at)) {
+                        panic!("{}", err);
+                    }
+                    request.send_json(example.clone())
+                },
+                None => request.call(),
+            };
+            let response = match response {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                Err(err) => panic!("{}: {}", at, err),
+            };
+
+            let status = response.status();
+            let documented = &operation["responses"][status.to_string()];
+            assert!(documented.is_object(), "{}: status {} is not documented", at, status);
+            assert_eq!(status, 200, "{}: the example is rejected", at);
+            let content_type = response.content_type().to_string();
+            let schema = &documented["content"][&content_type]["schema"];
+            assert!(schema.is_object(), "{}: content type {} is not documented", at, content_type);
+
+            let body = response.into_string().expect("response body is text");
+            let values: Vec<Value> = match content_type.as_str() {
+                "application/x-ndjson" => body.lines().map(|line| serde_json::from_str(line).expect("NDJSON lines are JSON")).collect(),
+                _ => vec![serde_json::from_str(&body).expect("response is JSON")],
+            };
+            for value in values {
+                if let Err(err) = check(&spec, schema, &value, &format!("{} response", at)) {
+                    panic!("{}", err);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn error_responses_match_the_spec() {
+    let server = Server::start();
+    let spec = server.spec();
+    for (path, operations) in spec["paths"].as_object().expect("paths") {
+        for (method, operation) in operations.as_object().expect("operations") {
+            if !operation["requestBody"].is_object() {
+                continue;
+            }
+            let at = format!("{} {}", method.to_uppercase(), path);
+            let response = match ureq::request(&method.to_uppercase(), &format!("{}{}", server.url, path)).send_json(serde_json::json!({})) {
+                Err(ureq::Error::Status(_, response)) => response,
+                Ok(response) => panic!("{}: an empty body is accepted with {}", at, response.status()),
+                Err(err) => panic!("{}: {}", at, err),
+            };
+            let status = response.status();
+            let schema = &operation["responses"][status.to_string()]["content"]["application/json"]["schema"];
+            assert!(schema.is_object(), "{}: status {} is not documented as JSON", at, status);
+            let value: Value = response.into_json().expect("error body is JSON");
+            if let Err(err) = check(&spec, schema, &value, &format!("{} error", at)) {
+                panic!("{}", err);
+            }
+        }
+    }
+}
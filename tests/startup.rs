@@ -0,0 +1,123 @@
+// Checks that startup failures exit with status 1 and a message on stderr instead of a panic.
+
+use std::net::TcpListener;
+use std::process::{Command, Output};
+
+use true_random_on_ton::keystore;
+
+fn start(env: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_true-random-on-ton"))
+        .env_clear()
+        .env("SERVER_LISTEN", "127.0.0.1:0")
+        .envs(env.iter().copied())
+        .output()
+        .expect("binary runs")
+}
+
+fn assert_exits_with(output: Output, message: &str) {
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains(message), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn unreachable_drand_url_exits() {
+    // A port nothing listens on once the listener is dropped.
+    let port = TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).unwrap().port();
+    let url = format!("http://127.0.0.1:{}", port);
+    assert_exits_with(start(&[("DRAND_URL", &url)]), "drand.url must serve a drand chain info document at /info");
+}
+
+#[test]
+fn invalid_drand_chain_info_exits() {
+    let path = std::env::temp_dir().join(format!("startup-test-{}.json", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "{}").unwrap();
+    let output = start(&[("DRAND_CHAIN_INFO", path.to_str().unwrap())]);
+    std::fs::remove_file(&path).unwrap();
+    assert_exits_with(output, "drand.chain_info must be a readable drand chain info file");
+}
+
+// A keystore directory holding one key encrypted with "right passphrase", and the key's id.
+fn keystore_dir() -> (std::path::PathBuf, String) {
+    let dir = std::env::temp_dir().join(format!("startup-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    let sk = vrf_r255::SecretKey::from_bytes([7u8; 32]).unwrap();
+    let keystore = keystore::encrypt(&sk, "right passphrase");
+    keystore::write(&keystore::path(&dir, &keystore.id), &keystore).unwrap();
+    (dir, keystore.id)
+}
+
+#[test]
+fn keystore_without_passphrase_exits() {
+    let (dir, _id) = keystore_dir();
+    let output = start(&[("VRF_KEYSTORE_DIR", dir.to_str().unwrap())]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_exits_with(output, "VRF_KEYSTORE_PASSPHRASE or VRF_KEYSTORE_PASSPHRASE_FILE must be set with keystore.dir");
+}
+
+#[test]
+fn keystore_with_wrong_passphrase_exits() {
+    let (dir, _id) = keystore_dir();
+    let output = start(&[(keystore::PASSPHRASE_ENV, "wrong passphrase"), ("VRF_KEYSTORE_DIR", dir.to_str().unwrap())]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_exits_with(output, "Passphrase is invalid.");
+}
+
+#[test]
+fn unknown_beacon_key_exits() {
+    let (dir, _id) = keystore_dir();
+    let output = start(&[(keystore::PASSPHRASE_ENV, "right passphrase"), ("VRF_KEYSTORE_DIR", dir.to_str().unwrap()), ("BEACON_KEY_ID", "missing")]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_exits_with(output, "beacon.key_id must name a keystore loaded from keystore.dir");
+}
+
+#[test]
+fn invalid_beacon_file_exits() {
+    let (dir, id) = keystore_dir();
+    let file = dir.join("beacon.ndjson");
+    std::fs::write(&file, "not a round\n").unwrap();
+    let output = start(&[
+        (keystore::PASSPHRASE_ENV, "right passphrase"),
+        ("VRF_KEYSTORE_DIR", dir.to_str().unwrap()),
+        ("BEACON_KEY_ID", &id),
+        ("BEACON_FILE", file.to_str().unwrap()),
+    ]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_exits_with(output, "beacon.file is invalid at round 1: record is not valid JSON");
+}
+
+#[test]
+fn hs256_jwt_without_secret_exits() {
+    let path = std::env::temp_dir().join(format!("startup-test-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "[auth.jwt]\nalgorithms = [\"HS256\"]\n").unwrap();
+    let output = start(&[("CONFIG_FILE", path.to_str().unwrap())]);
+    std::fs::remove_file(&path).unwrap();
+    assert_exits_with(output, "AUTH_JWT_SECRET must be set for HS* JWT algorithms");
+}
+
+#[test]
+fn invalid_tls_certificate_exits() {
+    let dir = std::env::temp_dir().join(format!("startup-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+    std::fs::write(&cert, "not a certificate").unwrap();
+    std::fs::write(&key, "not a key").unwrap();
+    let output = start(&[("SERVER_TLS_CERT", cert.to_str().unwrap()), ("SERVER_TLS_KEY", key.to_str().unwrap())]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_exits_with(output, "server.tls is invalid");
+}
+
+#[test]
+fn listen_address_in_use_exits() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let listen = listener.local_addr().unwrap().to_string();
+    assert_exits_with(start(&[("SERVER_LISTEN", &listen)]), "server.listen could not be bound");
+}
+
+#[test]
+fn grpc_listen_address_in_use_exits() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let listen = listener.local_addr().unwrap().to_string();
+    assert_exits_with(start(&[("GRPC_LISTEN", &listen)]), "grpc.listen could not be bound");
+}